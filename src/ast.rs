@@ -7,11 +7,14 @@ use syntax::{Properties, SourceLocation, Syntax};
 pub mod ast1;
 pub mod ast2;
 
+use itertools::Itertools;
 use std::{
-    collections::BTreeSet,
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet},
     fmt::{self, Debug},
+    hash::{DefaultHasher, Hash, Hasher},
     iter,
-    mem::swap,
+    mem::{self, swap},
     rc::Rc,
 };
 
@@ -66,6 +69,7 @@ macro_rules! list {
 pub enum Function {
     Lambda(Lambda),
     Primitive(Primitive),
+    CaseLambda(CaseLambda),
 }
 #[macro_export]
 /// s-expression like quasi-quoter
@@ -174,10 +178,25 @@ impl fmt::Display for Function {
         match self {
             Self::Lambda(l) => write!(f, "(lambda {} {})", l.param, l.body),
             Self::Primitive(p) => write!(f, "<primitive-procedure-{}>", p.name),
+            Self::CaseLambda(c) => write!(
+                f,
+                "(case-lambda {})",
+                c.clauses
+                    .iter()
+                    .map(|(arity, clause)| format!("[{arity} {clause}]"))
+                    .join(" ")
+            ),
         }
     }
 }
 
+/// What applying a function results in, either its values or (for a user defined function) the
+/// body it still has to evaluate, so that the evaluator can run it without growing the Rust stack.
+pub(crate) enum Applied {
+    Values(Values),
+    Body(Rc<Ast1>, EnvRef),
+}
+
 // TODO: how does function application work if we are currying all the functions, espically for
 // apply transformer
 // from looking at compilation code:
@@ -186,50 +205,77 @@ impl fmt::Display for Function {
 // variadiacs are fully applied
 impl Function {
     pub(crate) fn apply(&self, args: Ast) -> Result<Values, Error> {
+        match self.apply_tail(args)? {
+            Applied::Values(values) => Ok(values),
+            Applied::Body(body, env) => Evaluator::eval_function_body(&body, env),
+        }
+    }
+
+    // like apply, but the body of a user defined function is given back instead of evaluated, so
+    // that a call in tail position can be evaluated in place of the expression it is in
+    pub(crate) fn apply_tail(&self, args: Ast) -> Result<Applied, Error> {
         match self {
-            Self::Lambda(Lambda { body, env, param }) => match param {
+            Self::Lambda(
+                lambda @ Lambda {
+                    body, env, param, ..
+                },
+            ) => match param {
                 Param::Zero => {
                     if args == Ast::TheEmptyList {
                         let env = Env::new_scope(env.clone());
-                        Evaluator::eval(*body.clone(), env)
+                        Ok(Applied::Body(body.clone(), env))
                     } else {
-                        Err("empty lambda must be applied to no arguements".into())
+                        Err(lambda.error("empty lambda must be applied to no arguements"))
                     }
                 }
                 Param::One(n) => {
-                    let Pair(arg, args) =
-                        *matches_to!(args => Ast::Pair | "expected at least one arguement")?;
-                    let curried = Evaluator::eval(
-                        *body.clone(),
-                        Env::new_lambda_env(env.clone(), Symbol(n.clone()), arg),
-                    )?;
+                    let missing = lambda.error("expected at least one arguement");
+                    let Pair(arg, args) = *matches_to!(args => Ast::Pair | missing)?;
+                    let env = Env::new_lambda_env(env.clone(), Symbol(n.clone()), arg);
                     if args == Ast::TheEmptyList {
-                        Ok(curried)
+                        Ok(Applied::Body(body.clone(), env))
                     } else {
-                        let curried = curried.into_single().map_err(|_| {
-                            Error::Other("arity error expected one curried value".to_string())
-                        })?;
+                        // only the last of the curried applications is in tail position
+                        let curried = Evaluator::eval_function_body(body, env)?
+                            .into_single()
+                            .map_err(|_| lambda.error("arity error expected one curried value"))?;
                         let curried = matches_to!(curried => Ast::Function)
-                            .ok_or(Error::Other("expected function to be curried".to_string()))?;
-                        curried.apply(args)
+                            .ok_or_else(|| lambda.error("expected function to be curried"))?;
+                        curried.apply_tail(args)
                     }
                 }
                 Param::AtLeast1(n) => {
                     if args == Ast::TheEmptyList {
-                        Err("+ requires at least one argument".into())
+                        Err(lambda.error("+ requires at least one argument"))
                     } else {
-                        Evaluator::eval(
-                            *body.clone(),
+                        Ok(Applied::Body(
+                            body.clone(),
                             Env::new_lambda_env(env.clone(), Symbol(n.clone()), args),
-                        )
+                        ))
                     }
                 }
-                Param::AtLeast0(n) => Evaluator::eval(
-                    *body.clone(),
+                Param::AtLeast0(n) => Ok(Applied::Body(
+                    body.clone(),
                     Env::new_lambda_env(env.clone(), Symbol(n.clone()), args),
-                ),
+                )),
             },
-            Self::Primitive(p) => (p.operation)(args),
+            // a primitive is always given the values of its arguments, even when evaluating lazily
+            Self::Primitive(p) => (p.operation)(Evaluator::force_args(args)?)
+                .map(|values| Applied::Values(Evaluator::observable_values(values))),
+            Self::CaseLambda(CaseLambda { clauses }) => {
+                let argc = args.size();
+                let (arity, clause) = Arity::select(clauses, argc)
+                    .ok_or_else(|| format!("case-lambda: no clause accepts {argc} arguments"))?;
+                // there is no way to partially apply a function to no arguments, so that just
+                // gives back the clause
+                if argc == 0 && !arity.accepts(0) {
+                    Ok(Applied::Values(Values::Single(Ast::Function(
+                        clause.clone(),
+                    ))))
+                } else {
+                    clause.apply_tail(args)
+                }
+            }
         }
     }
 
@@ -244,9 +290,20 @@ impl Function {
 
 #[derive(Clone)]
 pub struct Lambda {
-    pub body: Box<Ast1>,
+    pub body: Rc<Ast1>,
     pub env: EnvRef,
     pub param: Param,
+    /// From the `inferred-name` syntax property, used in error messages.
+    pub name: Option<Symbol>,
+}
+
+impl Lambda {
+    fn error(&self, message: &str) -> Error {
+        match &self.name {
+            Some(name) => Error::Other(format!("{name}: {message}")),
+            None => Error::Other(message.to_string()),
+        }
+    }
 }
 
 impl PartialEq for Lambda {
@@ -262,6 +319,170 @@ impl fmt::Debug for Lambda {
     }
 }
 
+/// An argument of a user defined function that is only evaluated once its value is needed (see
+/// [`Evaluator::force`]), which is then remembered, so it is evaluated at most once.
+///
+/// A `?` that keeps the outcome of its first test is also a thunk, so that each copy of it shares
+/// that outcome.
+#[derive(Clone)]
+pub struct Thunk(Rc<RefCell<ThunkState>>);
+
+enum ThunkState {
+    Delayed(Ast1, EnvRef),
+    Forced(Ast),
+}
+
+impl Thunk {
+    pub(crate) fn new(expr: Ast1, env: EnvRef) -> Self {
+        Self(Rc::new(RefCell::new(ThunkState::Delayed(expr, env))))
+    }
+
+    pub(crate) fn maybe() -> Self {
+        Self(Rc::new(RefCell::new(ThunkState::Forced(Ast::Boolean(
+            Boolean::Maybe,
+        )))))
+    }
+
+    // from now on the value is the outcome of testing it
+    pub(crate) fn observe(&self, outcome: bool) {
+        *self.0.borrow_mut() = ThunkState::Forced(Ast::Boolean(outcome.into()));
+    }
+
+    // the value may be another thunk, which is left for the caller to force
+    pub(crate) fn force_once(&self) -> Result<Ast, Error> {
+        let (expr, env) = match &*self.0.borrow() {
+            ThunkState::Forced(value) => return Ok(value.clone()),
+            ThunkState::Delayed(expr, env) => (expr.clone(), env.clone()),
+        };
+        let value = Evaluator::eval_single_value(expr, env)?;
+        *self.0.borrow_mut() = ThunkState::Forced(value.clone());
+        Ok(value)
+    }
+}
+
+impl PartialEq for Thunk {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Display for Thunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.0.borrow() {
+            ThunkState::Delayed(..) => write!(f, "<thunk>"),
+            ThunkState::Forced(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl fmt::Debug for Thunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &*self.0.borrow() {
+            ThunkState::Delayed(expr, _) => write!(f, "(thunk {expr})"),
+            ThunkState::Forced(value) => write!(f, "(thunk {value:?})"),
+        }
+    }
+}
+
+/// A table from keys to values, where two keys are the same key if they are `equal?` (so lists and
+/// strings are compared by what is in them).
+///
+/// Copies of a table share it, so a change through one copy is seen through all of them.
+#[derive(Clone, Default, Debug)]
+pub struct HashTable(Rc<RefCell<BTreeMap<u64, Vec<(Ast, Ast)>>>>);
+
+impl HashTable {
+    // keys that are equal have the same hash, as it only uses what equality looks at
+    fn hash(key: &Ast) -> u64 {
+        fn hash_key(key: &Ast, state: &mut DefaultHasher) {
+            mem::discriminant(key).hash(state);
+            match key {
+                // 0 and -0 are equal
+                Ast::Number(n) => (n + 0.).to_bits().hash(state),
+                Ast::String(s) | Ast::Label(s) => s.hash(state),
+                Ast::Symbol(s) => s.hash(state),
+                Ast::Boolean(b) => mem::discriminant(b).hash(state),
+                Ast::Pair(pair) => {
+                    hash_key(&pair.0, state);
+                    hash_key(&pair.1, state);
+                }
+                _ => {}
+            }
+        }
+        let mut state = DefaultHasher::new();
+        hash_key(key, &mut state);
+        state.finish()
+    }
+
+    pub(crate) fn get(&self, key: &Ast) -> Option<Ast> {
+        self.0.borrow().get(&Self::hash(key)).and_then(|bucket| {
+            bucket
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value.clone())
+        })
+    }
+
+    pub(crate) fn insert(&self, key: Ast, value: Ast) {
+        let mut table = self.0.borrow_mut();
+        let bucket = table.entry(Self::hash(&key)).or_default();
+        match bucket.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, old)) => *old = value,
+            None => bucket.push((key, value)),
+        }
+    }
+
+    pub(crate) fn remove(&self, key: &Ast) {
+        let mut table = self.0.borrow_mut();
+        let hash = Self::hash(key);
+        if let Some(bucket) = table.get_mut(&hash) {
+            bucket.retain(|(k, _)| k != key);
+            if bucket.is_empty() {
+                table.remove(&hash);
+            }
+        }
+    }
+
+    pub(crate) fn keys(&self) -> Vec<Ast> {
+        self.0
+            .borrow()
+            .values()
+            .flatten()
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.borrow().values().map(Vec::len).sum()
+    }
+}
+
+impl PartialEq for HashTable {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Display for HashTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries = self
+            .0
+            .borrow()
+            .values()
+            .flatten()
+            .map(|(key, value)| format!("({key} . {value})"))
+            .join(" ");
+        write!(f, "#hash({entries})")
+    }
+}
+
+/// A function made up of several clauses, each application runs the clause that fits the number of
+/// arguments it is given (see [`Arity::select`]).
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CaseLambda {
+    pub clauses: Vec<(Arity, Function)>,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Primitive {
     pub operation: fn(Ast) -> Result<Values, Error>,
@@ -293,6 +514,8 @@ pub enum Ast {
     Symbol(Symbol),
     Function(Function),
     Label(RC<str>),
+    Thunk(Thunk),
+    Hash(HashTable),
 }
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
@@ -391,6 +614,8 @@ impl fmt::Display for Ast {
             Self::Function(function) => write!(f, "{function}"),
             Self::Boolean(b) => write!(f, "{b}"),
             Self::Label(f0) => write!(f, "@{f0}"),
+            Self::Thunk(thunk) => write!(f, "{thunk}"),
+            Self::Hash(table) => write!(f, "{table}"),
             Self::TheEmptyList => write!(f, "()"),
         }
     }
@@ -774,6 +999,62 @@ pub enum Boolean {
     /// ?
     Maybe,
 }
+impl From<bool> for Boolean {
+    fn from(value: bool) -> Self {
+        if value {
+            Self::True
+        } else {
+            Self::False
+        }
+    }
+}
+// `?` is between `|` and `&`, so with three valued (Kleene) logic and is the lesser of its
+// arguments, or is the greater one and not flips the order
+impl Boolean {
+    const fn rank(self) -> u8 {
+        match self {
+            Self::False => 0,
+            Self::Maybe => 1,
+            Self::True => 2,
+        }
+    }
+
+    const fn from_rank(rank: u8) -> Self {
+        match rank {
+            0 => Self::False,
+            1 => Self::Maybe,
+            _ => Self::True,
+        }
+    }
+
+    #[must_use]
+    pub fn and(self, other: Self) -> Self {
+        Self::from_rank(self.rank().min(other.rank()))
+    }
+
+    #[must_use]
+    pub fn or(self, other: Self) -> Self {
+        Self::from_rank(self.rank().max(other.rank()))
+    }
+
+    #[must_use]
+    pub fn xor(self, other: Self) -> Self {
+        self.and(!other).or((!self).and(other))
+    }
+
+    #[must_use]
+    pub fn implies(self, other: Self) -> Self {
+        (!self).or(other)
+    }
+}
+
+impl std::ops::Not for Boolean {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self::from_rank(2 - self.rank())
+    }
+}
 impl From<Boolean> for u64 {
     fn from(value: Boolean) -> Self {
         match value {
@@ -803,6 +1084,49 @@ pub enum Varidiac {
     AtLeast0,
 }
 
+/// The number of arguments a case-lambda clause takes, `(N)` takes exactly `N`, `(N *)` takes `N`
+/// or more, and `(N +)` takes more than `N`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Arity(pub usize, pub Option<Varidiac>);
+
+impl Arity {
+    /// The least number of arguments this accepts.
+    #[must_use]
+    pub const fn min(self) -> usize {
+        match self.1 {
+            Some(Varidiac::AtLeast1) => self.0 + 1,
+            _ => self.0,
+        }
+    }
+
+    #[must_use]
+    pub const fn accepts(self, argc: usize) -> bool {
+        if self.1.is_some() {
+            argc >= self.min()
+        } else {
+            argc == self.0
+        }
+    }
+
+    /// Picks the clause to run when given `argc` arguments, the first clause that accepts exactly
+    /// that many, and if there is none the first clause that needs more (which is then curried).
+    pub fn select<T>(clauses: &[(Self, T)], argc: usize) -> Option<&(Self, T)> {
+        clauses
+            .iter()
+            .find(|(arity, _)| arity.accepts(argc))
+            .or_else(|| clauses.iter().find(|(arity, _)| arity.min() > argc))
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.1 {
+            Some(variadic) => write!(f, "({} {variadic})", self.0),
+            None => write!(f, "({})", self.0),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Param {
     // All the variants besides Zero have a number, so even after auto currying the compiler still
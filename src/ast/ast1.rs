@@ -8,7 +8,7 @@ use itertools::Itertools;
 
 use crate::interior_mut::{MUTEX, RC};
 
-use super::{Ast, ModuleType, Param};
+use super::{Arity, Ast, ModuleType, Param, Symbol};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct Tree {
@@ -77,10 +77,14 @@ pub enum Ast1 {
 
     // special forms
     If(Box<Ast1>, Box<Ast1>, Box<Ast1>),
+    WithContinuationMark(Box<Ast1>, Box<Ast1>, Box<Ast1>),
+    CurrentContinuationMarks,
     DefineValues(Vec<RC<str>>, Box<Ast1>),
     LetValues(Vec<(Vec<RC<str>>, Ast1)>, Box<Ast1>),
     LetRecValues(Vec<(Vec<RC<str>>, Ast1)>, Box<Ast1>),
-    Lambda(Param, Box<Ast1>),
+    // the name is from the inferred-name syntax property
+    Lambda(Param, Box<Ast1>, Option<Symbol>),
+    CaseLambda(Vec<(Arity, Ast1)>),
     Application(Box<Ast1>, Vec<Ast1>),
     Expression(Box<Ast1>),
     Begin(Vec<Ast1>),
@@ -110,9 +114,22 @@ impl Display for Ast1 {
             }
 
             Self::If(cond, cons, alt) => write!(f, "(if {cond} {cons} {alt})"),
-            Self::DefineValues(v, val) => todo!(),
-            // write!(f, "(define {v} {val})"),
-            Self::Lambda(param, body) => write!(f, "(lambda ({param} {body})",),
+            Self::WithContinuationMark(key, val, body) => {
+                write!(f, "(with-continuation-mark {key} {val} {body})")
+            }
+            Self::CurrentContinuationMarks => write!(f, "(current-continuation-marks)"),
+            Self::DefineValues(v, val) => {
+                write!(f, "(define-values ({}) {val})", v.iter().join(" "))
+            }
+            Self::Lambda(param, body, _) => write!(f, "(lambda ({param} {body})",),
+            Self::CaseLambda(clauses) => write!(
+                f,
+                "(case-lambda {})",
+                clauses
+                    .iter()
+                    .map(|(arity, clause)| format!("[{arity} {clause}]"))
+                    .join(" ")
+            ),
             Self::Begin(b) => write!(f, "(begin {})", b.iter().map(ToString::to_string).join(" ")),
             Self::Begin0(b) => write!(
                 f,
@@ -2,7 +2,7 @@ use std::{fmt, rc::Rc};
 
 use itertools::Itertools;
 
-use super::{ast1::Label, Ast, ModuleType, Param};
+use super::{ast1::Label, Arity, Ast, ModuleType, Param, Symbol};
 
 #[derive(Debug, Clone)]
 pub enum Ast2 {
@@ -10,10 +10,14 @@ pub enum Ast2 {
 
     // special forms
     If(Box<Ast2>, Box<Ast2>, Box<Ast2>),
+    WithContinuationMark(Box<Ast2>, Box<Ast2>, Box<Ast2>),
+    CurrentContinuationMarks,
     DefineValues(Vec<Rc<str>>, Box<Ast2>),
     LetValues(Vec<(Vec<Rc<str>>, Ast2)>, Box<Ast2>),
     LetRecValues(Vec<(Vec<Rc<str>>, Ast2)>, Box<Ast2>),
-    Lambda(Param, Box<Ast2>),
+    // the name is from the inferred-name syntax property
+    Lambda(Param, Box<Ast2>, Option<Symbol>),
+    CaseLambda(Vec<(Arity, Ast2)>),
     Application(Box<Ast2>, Vec<Ast2>),
     Expression(Box<Ast2>),
     Begin(Vec<Ast2>),
@@ -37,9 +41,22 @@ impl fmt::Display for Ast2 {
             }
 
             Self::If(cond, cons, alt) => write!(f, "(if {cond} {cons} {alt})"),
-            Self::DefineValues(v, val) => todo!(),
-            // write!(f, "(define {v} {val})"),
-            Self::Lambda(param, body) => write!(f, "(lambda ({param} {body})",),
+            Self::WithContinuationMark(key, val, body) => {
+                write!(f, "(with-continuation-mark {key} {val} {body})")
+            }
+            Self::CurrentContinuationMarks => write!(f, "(current-continuation-marks)"),
+            Self::DefineValues(v, val) => {
+                write!(f, "(define-values ({}) {val})", v.iter().join(" "))
+            }
+            Self::Lambda(param, body, _) => write!(f, "(lambda ({param} {body})",),
+            Self::CaseLambda(clauses) => write!(
+                f,
+                "(case-lambda {})",
+                clauses
+                    .iter()
+                    .map(|(arity, clause)| format!("[{arity} {clause}]"))
+                    .join(" ")
+            ),
             Self::Begin(b) => write!(f, "(begin {})", b.iter().map(ToString::to_string).join(" ")),
             Self::Begin0(b) => write!(
                 f,
@@ -87,7 +104,7 @@ mod impl_transformer {
             };
             match value {
                 Ast1::Module(name, kind) => Ok((Self::Module(name, kind), state)),
-                Ast1::Quote(q) => Ok((Self::Quote(q), state)),
+                Ast1::Quote(q) => convert_basic(q).map(|q| (Self::Quote(q), state)),
                 Ast1::Application(f, exprs) => {
                     let (f, state) = pass2_box(f, state)?;
                     exprs
@@ -96,15 +113,12 @@ mod impl_transformer {
                         .transform_all()
                         .map(|(ast, state)| (Self::Application(f, ast), state))
                 }
-                Ast1::Basic(Ast::Label(l)) => {
-                    if let Some(l) = state.get(&Label(l.clone())) {
-                        Ok((Self::Goto(l.clone()), state))
-                    } else if state.has_key(&Label(l.clone())) {
-                        Ok((Self::Basic(Ast::Label(l)), state))
-                    } else {
-                        Err(format!("Label {l} invalid"))
-                    }
-                }
+                // the links are checked before this (see `Expander::check_labels`), so a label
+                // that is not linked from is just a place that nothing jumps to
+                Ast1::Basic(Ast::Label(l)) => match state.get(&Label(l.clone())) {
+                    Some(l) => Ok((Self::Goto(l.clone()), state)),
+                    None => Ok((Self::Basic(Ast::Label(l)), state)),
+                },
                 Ast1::Basic(b) => convert_basic(b).map(|b| (Self::Basic(b), state)),
                 Ast1::If(cond, then, alt) => {
                     let (cond, state) = pass2_box(cond, state)?;
@@ -112,15 +126,26 @@ mod impl_transformer {
                     let (alt, state) = pass2_box(alt, state)?;
                     Ok((Self::If(cond, then, alt), state))
                 }
+                Ast1::WithContinuationMark(key, val, body) => {
+                    let (key, state) = pass2_box(key, state)?;
+                    let (val, state) = pass2_box(val, state)?;
+                    let (body, state) = pass2_box(body, state)?;
+                    Ok((Self::WithContinuationMark(key, val, body), state))
+                }
+                Ast1::CurrentContinuationMarks => Ok((Self::CurrentContinuationMarks, state)),
                 Ast1::DefineValues(i, expr) => {
                     pass2_box(expr, state).map(|(expr, state)| (Self::DefineValues(i, expr), state))
                 }
                 Ast1::Loop(expr) => {
                     pass2_box(expr, state).map(|(expr, state)| (Self::Loop(expr), state))
                 }
-                Ast1::Lambda(pc, expr) => {
-                    pass2_box(expr, state).map(|(expr, state)| (Self::Lambda(pc, expr), state))
-                }
+                Ast1::Lambda(pc, expr, name) => pass2_box(expr, state)
+                    .map(|(expr, state)| (Self::Lambda(pc, expr, name), state)),
+                Ast1::CaseLambda(clauses) => clauses
+                    .into_iter()
+                    .transform::<(_, Self)>(state)
+                    .transform_all()
+                    .map(|(clauses, state)| (Self::CaseLambda(clauses), state)),
                 Ast1::Begin(exprs) => exprs
                     .into_iter()
                     .transform::<Self>(state)
@@ -183,7 +208,11 @@ mod impl_transformer {
                 Ok(Ast::Syntax(Box::new(syntax.with(a))))
             }
             Ast::Function(Function::Primitive(p)) => Ok(p.name.into()),
-            Ast::Function(Function::Lambda(_)) => unreachable!(),
+            // a table made while expanding (say by a macro) has nothing it could be compiled to
+            Ast::Hash(_) => Err("cannot embed a hash table in compiled code".to_string()),
+            Ast::Function(Function::Lambda(_) | Function::CaseLambda(_)) | Ast::Thunk(_) => {
+                unreachable!()
+            }
         }
     }
 }
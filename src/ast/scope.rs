@@ -166,7 +166,7 @@ impl Expander {
         }
     }
 
-    fn find_all_matching_bindings<'a>(
+    pub(crate) fn find_all_matching_bindings<'a>(
         id: &'a Syntax<Symbol>,
         scopes: &'a BTreeSet<Scope>,
     ) -> impl Iterator<Item = (BTreeSet<Scope>, Binding)> + Clone + 'a {
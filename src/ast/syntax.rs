@@ -18,6 +18,17 @@ pub struct SourceLocation {
     line: u32,
     column: u32,
 }
+impl SourceLocation {
+    #[must_use]
+    pub const fn new(file: String, line: u32, column: u32) -> Self {
+        Self { file, line, column }
+    }
+}
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
 #[derive(Clone, PartialEq)]
 pub struct Syntax<T>(pub T, pub ScopeSet, pub SourceLocation, pub Properties);
 
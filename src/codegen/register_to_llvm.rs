@@ -2,13 +2,14 @@ use inkwell::{
     basic_block::BasicBlock,
     builder::{Builder, BuilderError},
     context::Context,
+    intrinsics::Intrinsic,
     llvm_sys::core::LLVMAddDestination,
     module::{Linkage, Module},
     passes::PassManager,
     types::{BasicType, BasicTypeEnum, FunctionType, IntType, PointerType, StructType},
     values::{
-        AggregateValue, AnyValue, AsValueRef, BasicValue, BasicValueEnum, FunctionValue,
-        InstructionValue, IntValue, PhiValue, PointerValue, StructValue,
+        AggregateValue, AnyValue, AsValueRef, BasicValue, BasicValueEnum, FloatValue,
+        FunctionValue, InstructionValue, IntValue, PhiValue, PointerValue, StructValue,
     },
     AddressSpace, FloatPredicate, IntPredicate,
 };
@@ -121,7 +122,7 @@ macro_rules! extract {
     };
 }
 
-fixed_map!(TypeMap, BasicTypeEnum<'ctx>,TypeIndex {empty bool number string symbol label cons  thunk lambda}
+fixed_map!(TypeMap, BasicTypeEnum<'ctx>,TypeIndex {empty bool number string symbol label cons  thunk lambda hash}
       fn new(
         empty: BasicTypeEnum<'ctx>,
         bool: BasicTypeEnum<'ctx>,
@@ -131,7 +132,8 @@ fixed_map!(TypeMap, BasicTypeEnum<'ctx>,TypeIndex {empty bool number string symb
         label: BasicTypeEnum<'ctx>,
         cons: BasicTypeEnum<'ctx>,
         thunk: BasicTypeEnum<'ctx>,
-        lambda: BasicTypeEnum<'ctx>
+        lambda: BasicTypeEnum<'ctx>,
+        hash: BasicTypeEnum<'ctx>
     ) -> Self {
         Self {
             empty,
@@ -143,6 +145,7 @@ fixed_map!(TypeMap, BasicTypeEnum<'ctx>,TypeIndex {empty bool number string symb
             cons,
             thunk,
             lambda,
+            hash,
         }
     }
 );
@@ -157,6 +160,7 @@ pub struct RegiMap<'ctx> {
     ContinueMulti: PointerValue<'ctx>,
     Thunk: PointerValue<'ctx>,
     Values: PointerValue<'ctx>,
+    Marks: PointerValue<'ctx>,
 }
 impl<'ctx> RegiMap<'ctx> {
     pub fn new(builder: &Builder<'ctx>, ty: StructType<'ctx>, values_ty: StructType<'ctx>) -> Self {
@@ -174,6 +178,7 @@ impl<'ctx> RegiMap<'ctx> {
             ContinueMulti: create_register("continue-multi"),
             Thunk: create_register("thunk"),
             Values: create_register("thunk"),
+            Marks: create_register("marks"),
         }
     }
     pub const fn get(&self, index: Register) -> PointerValue<'ctx> {
@@ -186,6 +191,7 @@ impl<'ctx> RegiMap<'ctx> {
             Register::Thunk => self.Thunk,
             Register::Values => self.Values,
             Register::ContinueMulti => self.ContinueMulti,
+            Register::Marks => self.Marks,
         }
     }
 }
@@ -202,6 +208,7 @@ pub enum TypeIndex {
     cons = 6,
     thunk = 7,
     lambda = 8,
+    hash = 9,
 }
 
 pub struct Types<'ctx> {
@@ -223,6 +230,9 @@ pub struct Functions<'ctx> {
     rand: FunctionValue<'ctx>,
     srand: FunctionValue<'ctx>,
     time: FunctionValue<'ctx>,
+    getenv: FunctionValue<'ctx>,
+    strtoul: FunctionValue<'ctx>,
+    memcmp: FunctionValue<'ctx>,
 }
 
 impl<'ctx> Functions<'ctx> {
@@ -272,6 +282,37 @@ impl<'ctx> Functions<'ctx> {
                 .fn_type(&[context.ptr_type(AddressSpace::default()).into()], false),
             Some(Linkage::External),
         );
+        let getenv = module.add_function(
+            "getenv",
+            context
+                .ptr_type(AddressSpace::default())
+                .fn_type(&[context.ptr_type(AddressSpace::default()).into()], false),
+            Some(Linkage::External),
+        );
+        let strtoul = module.add_function(
+            "strtoul",
+            context.i64_type().fn_type(
+                &[
+                    context.ptr_type(AddressSpace::default()).into(),
+                    context.ptr_type(AddressSpace::default()).into(),
+                    context.i32_type().into(),
+                ],
+                false,
+            ),
+            Some(Linkage::External),
+        );
+        let memcmp = module.add_function(
+            "memcmp",
+            context.i32_type().fn_type(
+                &[
+                    context.ptr_type(AddressSpace::default()).into(),
+                    context.ptr_type(AddressSpace::default()).into(),
+                    context.i64_type().into(),
+                ],
+                false,
+            ),
+            Some(Linkage::External),
+        );
         Self {
             exit,
             strncmp,
@@ -279,6 +320,9 @@ impl<'ctx> Functions<'ctx> {
             rand,
             srand,
             time,
+            getenv,
+            strtoul,
+            memcmp,
         }
     }
 }
@@ -327,6 +371,10 @@ pub struct CodeGen<'a, 'ctx> {
     error_block: BasicBlock<'ctx>,
     error_phi: inkwell::values::PhiValue<'ctx>,
     stop: PointerValue<'ctx>,
+    // the seed of the rng, so that it can be printed when the program fails
+    seed: PointerValue<'ctx>,
+    // whether a `?` keeps the outcome of its first test
+    observe_once: bool,
     expect_single_block: BasicBlock<'ctx>,
 }
 
@@ -355,11 +403,14 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
     extract!(get_string, unchecked_get_string, string, "string");
     extract!(get_thunk, unchecked_get_thunk, thunk, "thunk");
     extract!(get_lambda, unchecked_get_lambda, lambda, "lambda");
+    extract!(get_hash, unchecked_get_hash, hash, "hash");
     pub fn new(
         context: &'ctx Context,
         builder: &'a Builder<'ctx>,
         module: &'a Module<'ctx>,
         fpm: &'a PassManager<FunctionValue<'ctx>>,
+        seed: Option<u32>,
+        observe_once: bool,
     ) -> Self {
         let pointer = context.ptr_type(AddressSpace::default());
         let object = context.struct_type(&[context.i32_type().into(), pointer.into()], false);
@@ -397,9 +448,24 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                 context
                     .struct_type(&[object.into(), object.into(), object.into()], false)
                     .into(),
+                // the number of keys, the number of buckets, and the buckets (each a list of key
+                // value pairs), which are changed in place so each copy of the table sees them
+                context
+                    .struct_type(
+                        &[
+                            context.i32_type().into(),
+                            context.i32_type().into(),
+                            pointer.into(),
+                        ],
+                        false,
+                    )
+                    .into(),
             ),
         };
         let functions = Functions::new(module, context);
+        let seed_global = module.add_global(context.i32_type(), None, "seed");
+        seed_global.set_initializer(&context.i32_type().const_zero());
+        let seed_global = seed_global.as_pointer_value();
         let main = module.add_function("main", context.i32_type().fn_type(&[], false), None);
         let entry_bb = context.append_basic_block(main, "entry");
         let expect_single_block = context.append_basic_block(main, "error-mv");
@@ -410,21 +476,70 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
             error,
             functions.printf,
             functions.exit,
+            seed_global,
         );
         builder.position_at_end(entry_bb);
-        // init random number seed
+        // init random number seed, a seed given at compile time is used over the one in the
+        // EVERYTHING_SEED environment variable, which is used over the time
         {
-            let time = builder
-                .build_call(
-                    functions.time,
-                    &[types.pointer.const_null().into()],
-                    "get time to further randomize rng",
-                )
-                .unwrap()
-                .try_as_basic_value()
-                .unwrap_left();
+            let seed = if let Some(seed) = seed {
+                context.i32_type().const_int(seed.into(), false)
+            } else {
+                let time = builder
+                    .build_call(
+                        functions.time,
+                        &[types.pointer.const_null().into()],
+                        "get time to further randomize rng",
+                    )
+                    .unwrap()
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_int_value();
+                let name = builder
+                    .build_global_string_ptr("EVERYTHING_SEED", "seed-env-name")
+                    .unwrap()
+                    .as_pointer_value();
+                let env = builder
+                    .build_call(functions.getenv, &[name.into()], "get seed from env")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_pointer_value();
+                let no_env = builder.build_is_null(env, "no seed in env").unwrap();
+                // strtoul cannot be given null, so it is given "0" instead when there is no seed
+                // in the environment (and its result is not used)
+                let zero = builder
+                    .build_global_string_ptr("0", "zero")
+                    .unwrap()
+                    .as_pointer_value();
+                let env = builder
+                    .build_select(no_env, zero, env, "seed string")
+                    .unwrap();
+                let env_seed = builder
+                    .build_call(
+                        functions.strtoul,
+                        &[
+                            env.into(),
+                            types.pointer.const_null().into(),
+                            context.i32_type().const_int(10, false).into(),
+                        ],
+                        "parse seed",
+                    )
+                    .unwrap()
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_int_value();
+                let env_seed = builder
+                    .build_int_truncate(env_seed, context.i32_type(), "seed as int")
+                    .unwrap();
+                builder
+                    .build_select(no_env, time, env_seed, "seed")
+                    .unwrap()
+                    .into_int_value()
+            };
+            builder.build_store(seed_global, seed).unwrap();
             builder
-                .build_call(functions.srand, &[time.into()], "set rng seed")
+                .build_call(functions.srand, &[seed.into()], "set rng seed")
                 .unwrap();
         }
         let registers = RegiMap::new(builder, object, values_type);
@@ -450,6 +565,8 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
             functions,
             error_phi,
             error_block,
+            seed: seed_global,
+            observe_once,
             expect_single_block,
         };
         let curret_block = this.builder.get_insert_block().unwrap();
@@ -504,6 +621,11 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                         .build_global_string_ptr("false", "false")
                         .unwrap()
                         .as_pointer_value();
+                    let maybe_str = this
+                        .builder
+                        .build_global_string_ptr("maybe", "maybe")
+                        .unwrap()
+                        .as_pointer_value();
                     let condition = this
                         .builder
                         .build_int_compare(
@@ -513,9 +635,22 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                             "is true",
                         )
                         .unwrap();
+                    let is_maybe = this
+                        .builder
+                        .build_int_compare(
+                            IntPredicate::EQ,
+                            value.into_int_value(),
+                            this.types.types.bool.into_int_type().const_int(2, false),
+                            "is maybe",
+                        )
+                        .unwrap();
                     let value = this
                         .builder
-                        .build_select(condition, true_str, false_str, "bool value")
+                        .build_select(is_maybe, maybe_str, false_str, "bool value")
+                        .unwrap();
+                    let value = this
+                        .builder
+                        .build_select(condition, true_str.into(), value, "bool value")
                         .unwrap();
                     this.builder
                         .build_call(this.functions.printf, &[value.into()], "printf debug")
@@ -562,23 +697,105 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                 let lambda_bb = make_print_block("lambda", |this, _, _| {
                     this.make_printf("user define procedure", vec![]);
                 });
+                // each key and value is printed as (key . value)
+                let hash_bb = make_print_block("hash", |this, _, exp| {
+                    let table = this.unchecked_get_hash(exp).into_struct_value();
+                    let bucket_count = this
+                        .builder
+                        .build_extract_value(table, 1, "bucket count")
+                        .unwrap()
+                        .into_int_value();
+                    let buckets = this
+                        .builder
+                        .build_extract_value(table, 2, "buckets")
+                        .unwrap()
+                        .into_pointer_value();
+                    this.make_printf("#hash(", vec![]);
+                    let first =
+                        this.build_variable("first", this.context.bool_type().const_all_ones());
+                    let rest = this.build_variable("rest", this.empty());
+                    this.build_for(
+                        "print:hash",
+                        this.context.i32_type().const_zero(),
+                        bucket_count,
+                        |this, i| {
+                            let bucket = this
+                                .load_variable(this.bucket_pointer(buckets, i), this.types.object);
+                            this.builder.build_store(rest, bucket).unwrap();
+                            this.build_while(
+                                "print:bucket",
+                                |this| {
+                                    let rest = this
+                                        .load_variable(rest, this.types.object)
+                                        .into_struct_value();
+                                    this.builder
+                                        .build_not(this.is_hempty(rest), "not empty")
+                                        .unwrap()
+                                },
+                                |this| {
+                                    let cell = this
+                                        .load_variable(rest, this.types.object)
+                                        .into_struct_value();
+                                    let entry = this.make_unchecked_car(cell);
+                                    let first_value = this
+                                        .load_variable(first, this.context.bool_type())
+                                        .into_int_value();
+                                    let open = this
+                                        .builder
+                                        .build_select(
+                                            first_value,
+                                            this.builder
+                                                .build_global_string_ptr("(", "open")
+                                                .unwrap()
+                                                .as_pointer_value(),
+                                            this.builder
+                                                .build_global_string_ptr(" (", "open")
+                                                .unwrap()
+                                                .as_pointer_value(),
+                                            "open",
+                                        )
+                                        .unwrap();
+                                    this.builder
+                                        .build_call(
+                                            this.functions.printf,
+                                            &[open.into()],
+                                            "print open",
+                                        )
+                                        .unwrap();
+                                    this.print_object(this.make_unchecked_car(entry));
+                                    this.make_printf(" . ", vec![]);
+                                    this.print_object(this.make_unchecked_cdr(entry));
+                                    this.make_printf(")", vec![]);
+                                    this.builder
+                                        .build_store(first, this.context.bool_type().const_zero())
+                                        .unwrap();
+                                    this.builder
+                                        .build_store(rest, this.make_unchecked_cdr(cell))
+                                        .unwrap();
+                                },
+                            );
+                        },
+                    );
+                    this.make_printf(")", vec![]);
+                });
                 this.builder.position_at_end(entry);
                 this.set_error("invalid type", 1);
-                let make_number = |n| this.context.i32_type().const_int(n, false);
+                let make_number = |n| this.context.i32_type().const_int(n as u64, false);
+                // primitives are lambdas, so they never get to primitive_bb
                 this.builder.build_switch(
                     ty.into_int_value(),
                     this.error_block,
                     &[
-                        (make_number(0), empty_bb),
-                        (make_number(1), bool_bb),
-                        (make_number(2), number_bb),
-                        (make_number(3), string_bb),
-                        (make_number(4), symbol_bb),
-                        (make_number(5), label_bb),
-                        (make_number(6), cons_bb),
-                        (make_number(7), primitive_bb),
-                        (make_number(8), thunk_bb),
-                        (make_number(9), lambda_bb),
+                        (make_number(TypeIndex::empty), empty_bb),
+                        (make_number(TypeIndex::bool), bool_bb),
+                        (make_number(TypeIndex::number), number_bb),
+                        (make_number(TypeIndex::string), string_bb),
+                        (make_number(TypeIndex::symbol), symbol_bb),
+                        (make_number(TypeIndex::label), label_bb),
+                        (make_number(TypeIndex::cons), cons_bb),
+                        (make_number(TypeIndex::thunk), thunk_bb),
+                        (make_number(TypeIndex::lambda), lambda_bb),
+                        (make_number(TypeIndex::hash), hash_bb),
                     ],
                 );
             },
@@ -679,6 +896,7 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                     let primitive_bb = this.context.append_basic_block(this.current, "primitive");
                     let label_bb = this.context.append_basic_block(this.current, "label");
                     let lambda_bb = this.context.append_basic_block(this.current, "lambda");
+                    let hash_bb = this.context.append_basic_block(this.current, "hash");
                     let make_number = |n| this.context.i32_type().const_int(n as u64, false);
                     this.builder.build_switch(
                         t1,
@@ -693,6 +911,7 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                             (make_number(TypeIndex::lambda), lambda_bb),
                             (make_number(TypeIndex::label), label_bb),
                             (make_number(TypeIndex::thunk), thunk_bb),
+                            (make_number(TypeIndex::hash), hash_bb),
                         ],
                     );
                     this.set_error("invalid type", 1);
@@ -747,6 +966,31 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                         // );
                         this.builder.build_return(Some(&equal));
                     }
+                    {
+                        // tables are only equal to themselves
+                        this.builder.position_at_end(hash_bb);
+                        let address = |e: StructValue<'ctx>| {
+                            let table = this
+                                .builder
+                                .build_extract_value(e, 1, "table")
+                                .unwrap()
+                                .into_pointer_value();
+                            this.builder
+                                .build_ptr_to_int(table, this.context.i64_type(), "address")
+                                .unwrap()
+                        };
+                        let same = this.extend_from_native_bool(
+                            this.builder
+                                .build_int_compare(
+                                    IntPredicate::EQ,
+                                    address(e1),
+                                    address(e2),
+                                    "same table",
+                                )
+                                .unwrap(),
+                        );
+                        this.builder.build_return(Some(&same)).unwrap();
+                    }
                     {
                         this.builder.position_at_end(string_bb);
 
@@ -848,10 +1092,330 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
         // panic!()
     }
 
+    // a hash of an object for hash tables, objects that are equal (see make_eq_obj) have the same
+    // hash, and objects that can only be equal to themselves just use their type as their hash
+    fn make_hash_obj(&mut self) {
+        let i32_type = self.context.i32_type();
+        self.create_function(
+            "hash-obj",
+            i32_type.fn_type(&[self.types.object.into()], false),
+            |this, hash_fn, _| {
+                let obj = hash_fn.get_first_param().unwrap().into_struct_value();
+                let ty = this.extract_type(obj).unwrap().into_int_value();
+                let block = |name: &str| {
+                    this.context
+                        .append_basic_block(this.current, &format!("hash:{name}"))
+                };
+                let bool_bb = block("bool");
+                let number_bb = block("number");
+                let string_bb = block("string");
+                let cons_bb = block("cons");
+                let other_bb = block("other");
+                let make_number = |n| i32_type.const_int(n as u64, false);
+                this.builder
+                    .build_switch(
+                        ty,
+                        other_bb,
+                        &[
+                            (make_number(TypeIndex::bool), bool_bb),
+                            (make_number(TypeIndex::number), number_bb),
+                            (make_number(TypeIndex::string), string_bb),
+                            (make_number(TypeIndex::symbol), string_bb),
+                            (make_number(TypeIndex::cons), cons_bb),
+                        ],
+                    )
+                    .unwrap();
+                {
+                    this.builder.position_at_end(other_bb);
+                    this.builder.build_return(Some(&ty)).unwrap();
+                }
+                {
+                    this.builder.position_at_end(bool_bb);
+                    let b = this.unchecked_get_bool(obj).into_int_value();
+                    let hash = this
+                        .builder
+                        .build_int_z_extend(b, i32_type, "hash")
+                        .unwrap();
+                    this.builder.build_return(Some(&hash)).unwrap();
+                }
+                {
+                    this.builder.position_at_end(number_bb);
+                    let n = this.unchecked_get_number(obj).into_float_value();
+                    // 0 and -0 are equal, but their bits are not
+                    let n = this
+                        .builder
+                        .build_float_add(n, n.get_type().const_zero(), "without -0")
+                        .unwrap();
+                    let i64_type = this.context.i64_type();
+                    let bits = this
+                        .builder
+                        .build_bit_cast(n, i64_type, "bits")
+                        .unwrap()
+                        .into_int_value();
+                    let high = this
+                        .builder
+                        .build_right_shift(bits, i64_type.const_int(32, false), false, "high")
+                        .unwrap();
+                    let bits = this.builder.build_xor(bits, high, "bits").unwrap();
+                    let hash = this
+                        .builder
+                        .build_int_truncate(bits, i32_type, "hash")
+                        .unwrap();
+                    this.builder.build_return(Some(&hash)).unwrap();
+                }
+                {
+                    // fnv-1a over the bytes
+                    this.builder.position_at_end(string_bb);
+                    let (len, data) = this.string_parts(this.unchecked_get_symbol(obj));
+                    let hash =
+                        this.build_variable("hash", i32_type.const_int(2_166_136_261, false));
+                    this.build_for("hash:string", i32_type.const_zero(), len, |this, i| {
+                        let byte = this
+                            .builder
+                            .build_int_z_extend(this.string_byte(data, i), i32_type, "byte")
+                            .unwrap();
+                        let hash_value = this.load_variable(hash, i32_type).into_int_value();
+                        let hash_value = this.builder.build_xor(hash_value, byte, "hash").unwrap();
+                        let hash_value = this
+                            .builder
+                            .build_int_mul(
+                                hash_value,
+                                i32_type.const_int(16_777_619, false),
+                                "hash",
+                            )
+                            .unwrap();
+                        this.builder.build_store(hash, hash_value).unwrap();
+                    });
+                    let hash = this.load_variable(hash, i32_type);
+                    this.builder.build_return(Some(&hash)).unwrap();
+                }
+                {
+                    this.builder.position_at_end(cons_bb);
+                    let hash_of = |e: StructValue<'ctx>| {
+                        this.builder
+                            .build_call(hash_fn, &[e.into()], "hash")
+                            .unwrap()
+                            .try_as_basic_value()
+                            .unwrap_left()
+                            .into_int_value()
+                    };
+                    let car = hash_of(this.make_unchecked_car(obj));
+                    let cdr = hash_of(this.make_unchecked_cdr(obj));
+                    let car = this
+                        .builder
+                        .build_int_mul(car, i32_type.const_int(31, false), "car hash")
+                        .unwrap();
+                    let hash = this.builder.build_int_add(car, cdr, "hash").unwrap();
+                    this.builder.build_return(Some(&hash)).unwrap();
+                }
+            },
+        );
+    }
+
+    // the marks (a list of frames, each a list of (key . value) pairs) with a mark from key to value
+    // in the innermost frame, a mark in that frame with the same key is replaced (keeping its place
+    // in the frame), otherwise the new mark is the innermost one
+    fn make_set_mark(&mut self) {
+        let object = self.types.object;
+        self.create_function(
+            "set-mark",
+            object.fn_type(&[object.into(), object.into(), object.into()], false),
+            |this, set_mark, _| {
+                let key = set_mark.get_nth_param(0).unwrap().into_struct_value();
+                let value = set_mark.get_nth_param(1).unwrap().into_struct_value();
+                let marks = set_mark.get_nth_param(2).unwrap().into_struct_value();
+                let load = |variable| this.load_variable(variable, object).into_struct_value();
+                // without any frame yet the mark is in a new one
+                let marks_variable = this.build_variable("marks", marks);
+                let no_frame = this.context.append_basic_block(this.current, "no-frame");
+                let frame = this.context.append_basic_block(this.current, "frame");
+                this.builder
+                    .build_conditional_branch(this.is_hempty(marks), no_frame, frame)
+                    .unwrap();
+                this.builder.position_at_end(no_frame);
+                this.builder
+                    .build_store(marks_variable, this.make_cons(this.empty(), this.empty()))
+                    .unwrap();
+                this.builder.build_unconditional_branch(frame).unwrap();
+                this.builder.position_at_end(frame);
+                let marks = load(marks_variable);
+                let frame = this.make_unchecked_car(marks);
+
+                // the marks before the one with the same key (in reverse), and the ones after it
+                let before = this.build_variable("before", this.empty());
+                let after = this.build_variable("after", frame);
+                let rest = this.build_variable("rest", frame);
+                let searching =
+                    this.build_variable("searching", this.context.bool_type().const_all_ones());
+                this.build_while(
+                    "set-mark:search",
+                    |this| {
+                        let searching = this
+                            .load_variable(searching, this.context.bool_type())
+                            .into_int_value();
+                        let not_empty = this
+                            .builder
+                            .build_not(this.is_hempty(load(rest)), "not empty")
+                            .unwrap();
+                        this.builder
+                            .build_and(searching, not_empty, "searching")
+                            .unwrap()
+                    },
+                    |this| {
+                        let cell = load(rest);
+                        let mark = this.make_unchecked_car(cell);
+                        let same = this.compare_objects(this.make_unchecked_car(mark), key);
+                        let same = this
+                            .builder
+                            .build_int_compare(
+                                IntPredicate::NE,
+                                same,
+                                same.get_type().const_zero(),
+                                "same",
+                            )
+                            .unwrap();
+                        let select = |then: StructValue<'ctx>, otherwise: StructValue<'ctx>| {
+                            this.builder
+                                .build_select(same, then, otherwise, "select")
+                                .unwrap()
+                        };
+                        let before_load = load(before);
+                        this.builder
+                            .build_store(
+                                before,
+                                select(before_load, this.make_cons(mark, before_load)),
+                            )
+                            .unwrap();
+                        this.builder
+                            .build_store(after, select(this.make_unchecked_cdr(cell), load(after)))
+                            .unwrap();
+                        this.builder
+                            .build_store(rest, this.make_unchecked_cdr(cell))
+                            .unwrap();
+                        let not_same = this.builder.build_not(same, "not same").unwrap();
+                        this.builder.build_store(searching, not_same).unwrap();
+                    },
+                );
+                // with no mark for the key the whole frame is after the new mark
+                let not_found = this
+                    .load_variable(searching, this.context.bool_type())
+                    .into_int_value();
+                let before_load = this
+                    .builder
+                    .build_select(not_found, this.empty(), load(before), "before")
+                    .unwrap();
+                this.builder.build_store(before, before_load).unwrap();
+
+                let mark = this.make_cons(key, value);
+                let new_frame = this.build_variable("new frame", this.make_cons(mark, load(after)));
+                this.build_while(
+                    "set-mark:rebuild",
+                    |this| {
+                        this.builder
+                            .build_not(this.is_hempty(load(before)), "not empty")
+                            .unwrap()
+                    },
+                    |this| {
+                        let cell = load(before);
+                        this.builder
+                            .build_store(
+                                new_frame,
+                                this.make_cons(this.make_unchecked_car(cell), load(new_frame)),
+                            )
+                            .unwrap();
+                        this.builder
+                            .build_store(before, this.make_unchecked_cdr(cell))
+                            .unwrap();
+                    },
+                );
+                let marks = this.make_cons(load(new_frame), this.make_unchecked_cdr(marks));
+                this.builder.build_return(Some(&marks)).unwrap();
+            },
+        );
+    }
+
+    // the marks of every frame as one list of (key . value) pairs, with the innermost mark first
+    fn make_continuation_marks(&mut self) {
+        let object = self.types.object;
+        self.create_function(
+            "continuation-marks",
+            object.fn_type(&[object.into()], false),
+            |this, continuation_marks, _| {
+                let marks = continuation_marks
+                    .get_first_param()
+                    .unwrap()
+                    .into_struct_value();
+                let load = |variable| this.load_variable(variable, object).into_struct_value();
+                let not_empty = |this: &Self, variable| {
+                    this.builder
+                        .build_not(this.is_hempty(load(variable)), "not empty")
+                        .unwrap()
+                };
+                // each mark is consed onto the reversed marks, which are then reversed back
+                let reversed = this.build_variable("reversed", this.empty());
+                let frames = this.build_variable("frames", marks);
+                let rest = this.build_variable("rest", this.empty());
+                this.build_while(
+                    "continuation-marks:frames",
+                    |this| not_empty(this, frames),
+                    |this| {
+                        let frame = load(frames);
+                        this.builder
+                            .build_store(rest, this.make_unchecked_car(frame))
+                            .unwrap();
+                        this.build_while(
+                            "continuation-marks:marks",
+                            |this| not_empty(this, rest),
+                            |this| {
+                                let cell = load(rest);
+                                this.builder
+                                    .build_store(
+                                        reversed,
+                                        this.make_cons(
+                                            this.make_unchecked_car(cell),
+                                            load(reversed),
+                                        ),
+                                    )
+                                    .unwrap();
+                                this.builder
+                                    .build_store(rest, this.make_unchecked_cdr(cell))
+                                    .unwrap();
+                            },
+                        );
+                        this.builder
+                            .build_store(frames, this.make_unchecked_cdr(frame))
+                            .unwrap();
+                    },
+                );
+                let result = this.build_variable("result", this.empty());
+                this.build_while(
+                    "continuation-marks:reverse",
+                    |this| not_empty(this, reversed),
+                    |this| {
+                        let cell = load(reversed);
+                        this.builder
+                            .build_store(
+                                result,
+                                this.make_cons(this.make_unchecked_car(cell), load(result)),
+                            )
+                            .unwrap();
+                        this.builder
+                            .build_store(reversed, this.make_unchecked_cdr(cell))
+                            .unwrap();
+                    },
+                );
+                this.builder.build_return(Some(&load(result))).unwrap();
+            },
+        );
+    }
+
     fn init_primitives(&mut self) {
         // seems to problem with primitive that retunrn something meaningful not returning properly unless / possiblely some other action done on the in the primtive function
         self.make_print();
         self.make_eq_obj();
+        self.make_hash_obj();
+        self.make_set_mark();
+        self.make_continuation_marks();
         let primitive_newline = self.create_simple_primitive("newline", |this, __| {
             this.builder.build_call(
                 this.functions.printf,
@@ -902,6 +1466,163 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
             let not_truthy = this.make_object(&not_truthy, TypeIndex::bool);
             Values::Single(not_truthy)
         });
+        let primitive_kleene_and = self.create_kleene_primitive("kleene-and", Self::kleene_and);
+        let primitive_kleene_or = self.create_kleene_primitive("kleene-or", Self::kleene_or);
+        let primitive_xor = self.create_kleene_primitive("xor", |this, a, b| {
+            let a_not_b = this.kleene_and(a, this.kleene_not(b));
+            let b_not_a = this.kleene_and(this.kleene_not(a), b);
+            this.kleene_or(a_not_b, b_not_a)
+        });
+        let primitive_implies = self.create_kleene_primitive("implies", |this, a, b| {
+            this.kleene_or(this.kleene_not(a), b)
+        });
+        let primitive_collapse = self.create_simple_primitive("collapse", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let arg = this.make_car(argl);
+            let is_maybe = this.is_kleene_maybe(arg);
+            let flip = this
+                .builder
+                .build_call(this.functions.rand, &[], "random bool")
+                .unwrap()
+                .try_as_basic_value()
+                .unwrap_left();
+            let flip = this
+                .builder
+                .build_int_signed_rem(
+                    flip.into_int_value(),
+                    this.context.i32_type().const_int(2, false),
+                    "truncate to bool",
+                )
+                .unwrap();
+            let flip = this
+                .builder
+                .build_int_truncate(
+                    flip,
+                    this.types.types.get(TypeIndex::bool).into_int_type(),
+                    "to bool",
+                )
+                .unwrap();
+            let collapsed = this.make_object(&flip, TypeIndex::bool);
+            let collapsed = this
+                .builder
+                .build_select(is_maybe, collapsed, arg, "collapse")
+                .unwrap();
+            Values::Single(collapsed.into_struct_value())
+        });
+        let primitive_maybe = self.create_simple_primitive("maybe?", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let val = this.make_car(argl);
+            let maybe = this.make_object(&this.is_kleene_maybe(val), TypeIndex::bool);
+            Values::Single(maybe)
+        });
+        let primitive_definitely = self.create_simple_primitive("definitely?", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let val = this.make_car(argl);
+            let definitely = this
+                .builder
+                .build_not(this.is_kleene_maybe(val), "definitely")
+                .unwrap();
+            let definitely = this.make_object(&definitely, TypeIndex::bool);
+            Values::Single(definitely)
+        });
+        let primitive_add = self.create_arithmetic_primitive("+", 0., false, |this, a, b| {
+            this.builder.build_float_add(a, b, "add").unwrap()
+        });
+        let primitive_sub = self.create_arithmetic_primitive("-", 0., true, |this, a, b| {
+            this.builder.build_float_sub(a, b, "sub").unwrap()
+        });
+        let primitive_mul = self.create_arithmetic_primitive("*", 1., false, |this, a, b| {
+            this.builder.build_float_mul(a, b, "mul").unwrap()
+        });
+        let primitive_div = self.create_arithmetic_primitive("/", 1., true, |this, a, b| {
+            this.check_divisor("/", b);
+            this.builder.build_float_div(a, b, "div").unwrap()
+        });
+        let primitive_quotient = self.create_binary_number_primitive("quotient", |this, a, b| {
+            this.check_divisor("quotient", b);
+            let quotient = this.builder.build_float_div(a, b, "div").unwrap();
+            this.call_number_intrinsic("llvm.trunc", &[quotient])
+        });
+        let primitive_remainder = self.create_binary_number_primitive("remainder", |this, a, b| {
+            this.check_divisor("remainder", b);
+            this.builder.build_float_rem(a, b, "remainder").unwrap()
+        });
+        // like remainder, but with the sign of the divisor
+        let primitive_modulo = self.create_binary_number_primitive("modulo", |this, a, b| {
+            this.check_divisor("modulo", b);
+            let remainder = this.builder.build_float_rem(a, b, "remainder").unwrap();
+            let zero = remainder.get_type().const_zero();
+            let compare = |predicate, lhs, rhs| {
+                this.builder
+                    .build_float_compare(predicate, lhs, rhs, "compare")
+                    .unwrap()
+            };
+            let signs_differ = this
+                .builder
+                .build_xor(
+                    compare(FloatPredicate::OLT, remainder, zero),
+                    compare(FloatPredicate::OLT, b, zero),
+                    "signs differ",
+                )
+                .unwrap();
+            let needs_divisor = this
+                .builder
+                .build_and(
+                    compare(FloatPredicate::ONE, remainder, zero),
+                    signs_differ,
+                    "needs divisor",
+                )
+                .unwrap();
+            let modulo = this
+                .builder
+                .build_float_add(remainder, b, "modulo")
+                .unwrap();
+            this.builder
+                .build_select(needs_divisor, modulo, remainder, "modulo")
+                .unwrap()
+                .into_float_value()
+        });
+        let primitive_less = self.create_comparison_primitive("<", FloatPredicate::OLT, "");
+        let primitive_less_equal = self.create_comparison_primitive("<=", FloatPredicate::OLE, "");
+        let primitive_greater = self.create_comparison_primitive(">", FloatPredicate::OGT, "");
+        let primitive_greater_equal =
+            self.create_comparison_primitive(">=", FloatPredicate::OGE, "");
+        let primitive_number_equal = self.create_comparison_primitive(
+            "=",
+            FloatPredicate::OEQ,
+            ", use equal? to compare anything else",
+        );
+        let primitive_abs = self.create_unary_number_primitive("abs", |this, n| {
+            this.call_number_intrinsic("llvm.fabs", &[n])
+        });
+        let primitive_floor = self.create_unary_number_primitive("floor", |this, n| {
+            this.call_number_intrinsic("llvm.floor", &[n])
+        });
+        let primitive_ceiling = self.create_unary_number_primitive("ceiling", |this, n| {
+            this.call_number_intrinsic("llvm.ceil", &[n])
+        });
+        // halfway cases are rounded to the even number
+        let primitive_round = self.create_unary_number_primitive("round", |this, n| {
+            this.call_number_intrinsic("llvm.roundeven", &[n])
+        });
+        let primitive_sqrt = self.create_unary_number_primitive("sqrt", |this, n| {
+            this.call_number_intrinsic("llvm.sqrt", &[n])
+        });
+        let primitive_expt = self.create_binary_number_primitive("expt", |this, a, b| {
+            this.call_number_intrinsic("llvm.pow", &[a, b])
+        });
+        let primitive_null = self.create_simple_primitive("null?", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let val = this.make_car(argl);
+            let null = this.make_object(&this.is_hempty(val), TypeIndex::bool);
+            Values::Single(null)
+        });
+        let primitive_pair = self.create_simple_primitive("pair?", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let val = this.make_car(argl);
+            let pair = this.make_object(&this.is_cons(val), TypeIndex::bool);
+            Values::Single(pair)
+        });
         let primitive_print = self.create_simple_primitive("print", |this, _| {
             let argl = this.load_register(Register::Argl);
             let val = this.make_car(argl);
@@ -1109,20 +1830,51 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
 
         let primitives = [
             ("newline", primitive_newline),
-            ("=", primitive_eq),
+            ("=", primitive_number_equal),
+            ("equal?", primitive_eq),
             ("print", primitive_print),
             ("not", primitive_not),
+            ("kleene-and", primitive_kleene_and),
+            ("kleene-or", primitive_kleene_or),
+            ("xor", primitive_xor),
+            ("implies", primitive_implies),
+            ("collapse", primitive_collapse),
+            ("maybe?", primitive_maybe),
+            ("definitely?", primitive_definitely),
+            ("null?", primitive_null),
+            ("pair?", primitive_pair),
             ("set_cdr!", primitive_set_cdr),
             ("set_car!", primitive_set_car),
             ("cons", primitive_cons),
             ("+1", primitive_add1),
             ("-1", primitive_sub1),
+            ("+", primitive_add),
+            ("-", primitive_sub),
+            ("*", primitive_mul),
+            ("/", primitive_div),
+            ("quotient", primitive_quotient),
+            ("remainder", primitive_remainder),
+            ("modulo", primitive_modulo),
+            ("<", primitive_less),
+            ("<=", primitive_less_equal),
+            (">", primitive_greater),
+            (">=", primitive_greater_equal),
+            ("abs", primitive_abs),
+            ("floor", primitive_floor),
+            ("ceiling", primitive_ceiling),
+            ("round", primitive_round),
+            ("sqrt", primitive_sqrt),
+            ("expt", primitive_expt),
             ("values", values),
         ];
         let accesors = self.init_accessors();
+        let strings = self.init_string_primitives();
+        let hashes = self.init_hash_primitives();
         let primitive_env = primitives
             .into_iter()
             .chain(accesors)
+            .chain(strings)
+            .chain(hashes)
             .map(|(name, function)| self.make_primitive_pair(name, function))
             // .chain(iter::once(call_with_values))
             .fold(
@@ -1275,6 +2027,7 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
             self.types.error,
             self.functions.printf,
             self.functions.exit,
+            self.seed,
         )
     }
 
@@ -1360,7 +2113,7 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                 "not bool check",
             )
             .unwrap();
-        let value = self
+        let value_ptr = self
             .builder
             .build_extract_value(val, 1, "get object context")
             .unwrap();
@@ -1368,7 +2121,7 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
             .builder
             .build_load(
                 self.types.types.get(TypeIndex::bool),
-                value.into_pointer_value(),
+                value_ptr.into_pointer_value(),
                 "get bool value",
             )
             .unwrap();
@@ -1415,6 +2168,20 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
             .builder
             .build_int_truncate(bool, self.module.get_context().bool_type(), "to bool")
             .unwrap();
+        if self.observe_once {
+            // each copy of the `?` points to the same value, so they all keep this outcome
+            let outcome = self
+                .builder
+                .build_int_z_extend(
+                    bool,
+                    self.types.types.get(TypeIndex::bool).into_int_type(),
+                    "outcome",
+                )
+                .unwrap();
+            self.builder
+                .build_store(value_ptr.into_pointer_value(), outcome)
+                .unwrap();
+        }
         phi.add_incoming(&[(&bool, maybe)]);
         self.builder.build_unconditional_branch(done);
         self.builder.position_at_end(not_maybe);
@@ -1439,25 +2206,2133 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
         phi.as_any_value_enum().into_int_value()
     }
 
-    fn compile_instructions(&mut self, instruction: Instruction) {
-        match instruction {
-            Instruction::Assign(r, e) => {
-                let expr = self.compile_expr(e);
-                self.assign_register(r, expr);
-            }
-            Instruction::Test(p) => {
-                self.builder
-                    .build_store(self.flag, self.compile_perform(p))
-                    .unwrap();
-            }
-            Instruction::Branch(l) => {
-                let flag = self
-                    .builder
-                    .build_load(self.types.object, self.flag, "load flag")
-                    .unwrap();
-                // TODO: hack technically (in most cases) if we have a branch, the next instruction after it will be a label so we dont need a new block for each branch but rather we should either "peek" ahead to the next instruction to obtain the label
-                // or encode the label as part of the branch variant
-                let next_label = self.context.append_basic_block(self.current, "next-block");
+    // the place of a value in three valued (Kleene) logic, 0 for `|`, 1 for `?` and 2 for `&` (or
+    // any other value, as they are all true), so that and is the lesser of two ranks and or is the
+    // greater one
+    fn kleene_rank(&self, val: StructValue<'ctx>) -> IntValue<'ctx> {
+        let rank_type = self.types.types.get(TypeIndex::bool).into_int_type();
+        let is_bool = self.is_boolean(val);
+        let not_bool = self.builder.get_insert_block().unwrap();
+        let bool_block = self
+            .context
+            .append_basic_block(self.current, "kleene-rank:bool");
+        let done = self
+            .context
+            .append_basic_block(self.current, "kleene-rank:done");
+        self.builder
+            .build_conditional_branch(is_bool, bool_block, done)
+            .unwrap();
+        self.builder.position_at_end(bool_block);
+        let value = self.unchecked_get_bool(val).into_int_value();
+        let is_value = |n| {
+            self.builder
+                .build_int_compare(
+                    IntPredicate::EQ,
+                    value,
+                    rank_type.const_int(n, false),
+                    "is value",
+                )
+                .unwrap()
+        };
+        // & is stored as 1 and ? as 2, so they are swapped
+        let rank = self
+            .builder
+            .build_select(
+                is_value(2),
+                rank_type.const_int(1, false),
+                rank_type.const_zero(),
+                "rank",
+            )
+            .unwrap();
+        let rank = self
+            .builder
+            .build_select(
+                is_value(1),
+                rank_type.const_int(2, false).into(),
+                rank,
+                "rank",
+            )
+            .unwrap();
+        self.builder.build_unconditional_branch(done).unwrap();
+        self.builder.position_at_end(done);
+        let phi = self.builder.build_phi(rank_type, "rank").unwrap();
+        phi.add_incoming(&[(&rank_type.const_int(2, false), not_bool)]);
+        phi.add_incoming(&[(&rank, bool_block)]);
+        phi.as_basic_value().into_int_value()
+    }
+
+    fn kleene_boolean(&self, rank: IntValue<'ctx>) -> StructValue<'ctx> {
+        let bool_type = self.types.types.get(TypeIndex::bool).into_int_type();
+        let is_rank = |n| {
+            self.builder
+                .build_int_compare(
+                    IntPredicate::EQ,
+                    rank,
+                    bool_type.const_int(n, false),
+                    "is rank",
+                )
+                .unwrap()
+        };
+        let value = self
+            .builder
+            .build_select(
+                is_rank(1),
+                bool_type.const_int(2, false),
+                bool_type.const_zero(),
+                "boolean",
+            )
+            .unwrap();
+        let value = self
+            .builder
+            .build_select(
+                is_rank(2),
+                bool_type.const_int(1, false).into(),
+                value,
+                "boolean",
+            )
+            .unwrap();
+        self.make_object(&value, TypeIndex::bool)
+    }
+
+    fn is_kleene_maybe(&self, val: StructValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_int_compare(
+                IntPredicate::EQ,
+                self.kleene_rank(val),
+                self.types
+                    .types
+                    .get(TypeIndex::bool)
+                    .into_int_type()
+                    .const_int(1, false),
+                "is maybe",
+            )
+            .unwrap()
+    }
+
+    fn kleene_and(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> IntValue<'ctx> {
+        let a_less = self
+            .builder
+            .build_int_compare(IntPredicate::ULT, a, b, "and")
+            .unwrap();
+        self.builder
+            .build_select(a_less, a, b, "and")
+            .unwrap()
+            .into_int_value()
+    }
+
+    fn kleene_or(&self, a: IntValue<'ctx>, b: IntValue<'ctx>) -> IntValue<'ctx> {
+        let a_greater = self
+            .builder
+            .build_int_compare(IntPredicate::UGT, a, b, "or")
+            .unwrap();
+        self.builder
+            .build_select(a_greater, a, b, "or")
+            .unwrap()
+            .into_int_value()
+    }
+
+    fn kleene_not(&self, a: IntValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_int_sub(a.get_type().const_int(2, false), a, "not")
+            .unwrap()
+    }
+
+    // a primitive that takes two arguments and combines their ranks with `op`
+    fn create_kleene_primitive(
+        &mut self,
+        name: &str,
+        op: impl FnOnce(&Self, IntValue<'ctx>, IntValue<'ctx>) -> IntValue<'ctx>,
+    ) -> BasicBlock<'ctx> {
+        self.create_simple_primitive(name, |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let a = this.kleene_rank(this.make_car(argl));
+            let b = this.kleene_rank(this.make_cadr(argl));
+            Values::Single(this.kleene_boolean(op(this, a, b)))
+        })
+    }
+
+    // calls the llvm intrinsic `name` (like llvm.floor) for numbers
+    fn call_number_intrinsic(&self, name: &str, args: &[FloatValue<'ctx>]) -> FloatValue<'ctx> {
+        let intrinsic = Intrinsic::find(name)
+            .and_then(|i| i.get_declaration(self.module, &[self.types.types.number]))
+            .unwrap();
+        let args = args.iter().map(|&arg| arg.into()).collect_vec();
+        self.builder
+            .build_call(intrinsic, &args, name)
+            .unwrap()
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_float_value()
+    }
+
+    // branches to the error block (with `reason`) unless `holds` is true
+    fn check(&self, name: &str, holds: IntValue<'ctx>, reason: &str) {
+        let ok = self
+            .context
+            .append_basic_block(self.current, &format!("{name}:ok"));
+        self.set_error(reason, 1);
+        self.builder
+            .build_conditional_branch(holds, ok, self.error_block)
+            .unwrap();
+        self.builder.position_at_end(ok);
+    }
+
+    fn check_divisor(&self, name: &str, divisor: FloatValue<'ctx>) {
+        let non_zero = self
+            .builder
+            .build_float_compare(
+                FloatPredicate::UNE,
+                divisor,
+                divisor.get_type().const_zero(),
+                "is non-zero",
+            )
+            .unwrap();
+        self.check(name, non_zero, &format!("{name}: division by zero\n"));
+    }
+
+    fn create_unary_number_primitive(
+        &mut self,
+        name: &str,
+        op: impl FnOnce(&Self, FloatValue<'ctx>) -> FloatValue<'ctx>,
+    ) -> BasicBlock<'ctx> {
+        self.create_simple_primitive(name, |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let n = this.get_number(this.make_car(argl)).into_float_value();
+            Values::Single(this.make_object(&op(this, n), TypeIndex::number))
+        })
+    }
+
+    fn create_binary_number_primitive(
+        &mut self,
+        name: &str,
+        op: impl FnOnce(&Self, FloatValue<'ctx>, FloatValue<'ctx>) -> FloatValue<'ctx>,
+    ) -> BasicBlock<'ctx> {
+        self.create_simple_primitive(name, |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let a = this.get_number(this.make_car(argl)).into_float_value();
+            let b = this.get_number(this.make_cadr(argl)).into_float_value();
+            Values::Single(this.make_object(&op(this, a, b), TypeIndex::number))
+        })
+    }
+
+    // a variadic primitive that folds its numbers with `op`, (op) is `identity` (or an arity error
+    // if it `needs_argument`), (op x) is (op identity x), and (op x y z) is (op (op x y) z)
+    fn create_arithmetic_primitive(
+        &mut self,
+        name: &str,
+        identity: f64,
+        needs_argument: bool,
+        op: impl FnOnce(&Self, FloatValue<'ctx>, FloatValue<'ctx>) -> FloatValue<'ctx>,
+    ) -> BasicBlock<'ctx> {
+        self.create_simple_primitive(name, |this, _| {
+            let number = this.types.types.number.into_float_type();
+            let argl = this.load_register(Register::Argl);
+            let acc = this.builder.build_alloca(number, "acc").unwrap();
+            let rest = this
+                .builder
+                .build_alloca(this.types.object, "rest")
+                .unwrap();
+            this.builder
+                .build_store(acc, number.const_float(identity))
+                .unwrap();
+            this.builder.build_store(rest, argl).unwrap();
+            if needs_argument {
+                let some = this
+                    .builder
+                    .build_not(this.is_hempty(argl), "not empty")
+                    .unwrap();
+                this.check(
+                    name,
+                    some,
+                    &format!("arity error: expected at least 1 argument, got 0, {name}\n"),
+                );
+            }
+            let block = |end| {
+                this.context
+                    .append_basic_block(this.current, &format!("{name}:{end}"))
+            };
+            let one = block("one");
+            let many = block("many");
+            let fold = block("fold");
+            let fold_next = block("fold-next");
+            let done = block("done");
+            this.builder
+                .build_conditional_branch(this.is_hempty(argl), fold, one)
+                .unwrap();
+
+            this.builder.position_at_end(one);
+            this.builder
+                .build_conditional_branch(this.is_hempty(this.make_unchecked_cdr(argl)), fold, many)
+                .unwrap();
+
+            // with more than one number the fold starts from the first one
+            this.builder.position_at_end(many);
+            let first = this.get_number(this.make_unchecked_car(argl));
+            this.builder.build_store(acc, first).unwrap();
+            this.builder
+                .build_store(rest, this.make_unchecked_cdr(argl))
+                .unwrap();
+            this.builder.build_unconditional_branch(fold).unwrap();
+
+            this.builder.position_at_end(fold);
+            let rest_load = this
+                .builder
+                .build_load(this.types.object, rest, "load rest")
+                .unwrap()
+                .into_struct_value();
+            this.builder
+                .build_conditional_branch(this.is_hempty(rest_load), done, fold_next)
+                .unwrap();
+
+            this.builder.position_at_end(fold_next);
+            let n = this
+                .get_number(this.make_unchecked_car(rest_load))
+                .into_float_value();
+            let acc_load = this
+                .builder
+                .build_load(number, acc, "load acc")
+                .unwrap()
+                .into_float_value();
+            // op is only compiled once, its code runs for each number
+            let result = op(this, acc_load, n);
+            this.builder.build_store(acc, result).unwrap();
+            this.builder
+                .build_store(rest, this.make_unchecked_cdr(rest_load))
+                .unwrap();
+            this.builder.build_unconditional_branch(fold).unwrap();
+
+            this.builder.position_at_end(done);
+            let result = this.builder.build_load(number, acc, "load acc").unwrap();
+            Values::Single(this.make_object(&result, TypeIndex::number))
+        })
+    }
+
+    // like get_number, but the error names the primitive and ends with `hint`
+    fn get_number_argument(
+        &self,
+        name: &str,
+        val: StructValue<'ctx>,
+        hint: &str,
+    ) -> BasicValueEnum<'ctx> {
+        self.check(
+            name,
+            self.is_number(val),
+            &format!("{name}: expected a number{hint}\n"),
+        );
+        self.unchecked_get_number(val)
+    }
+
+    // a variadic primitive that is whether each number is `predicate` to the one after it, `hint`
+    // is added to the error for something that is not a number
+    fn create_comparison_primitive(
+        &mut self,
+        name: &str,
+        predicate: FloatPredicate,
+        hint: &str,
+    ) -> BasicBlock<'ctx> {
+        self.create_simple_primitive(name, |this, _| {
+            let number = this.types.types.number.into_float_type();
+            let bool_type = this.context.bool_type();
+            let argl = this.load_register(Register::Argl);
+            let all = this.builder.build_alloca(bool_type, "all").unwrap();
+            let prev = this.builder.build_alloca(number, "prev").unwrap();
+            let rest = this
+                .builder
+                .build_alloca(this.types.object, "rest")
+                .unwrap();
+            this.builder
+                .build_store(all, bool_type.const_all_ones())
+                .unwrap();
+            let block = |end| {
+                this.context
+                    .append_basic_block(this.current, &format!("{name}:{end}"))
+            };
+            let first = block("first");
+            let compare = block("compare");
+            let compare_next = block("compare-next");
+            let done = block("done");
+            this.builder
+                .build_conditional_branch(this.is_hempty(argl), done, first)
+                .unwrap();
+
+            this.builder.position_at_end(first);
+            let first_number = this.get_number_argument(name, this.make_unchecked_car(argl), hint);
+            this.builder.build_store(prev, first_number).unwrap();
+            this.builder
+                .build_store(rest, this.make_unchecked_cdr(argl))
+                .unwrap();
+            this.builder.build_unconditional_branch(compare).unwrap();
+
+            this.builder.position_at_end(compare);
+            let rest_load = this
+                .builder
+                .build_load(this.types.object, rest, "load rest")
+                .unwrap()
+                .into_struct_value();
+            this.builder
+                .build_conditional_branch(this.is_hempty(rest_load), done, compare_next)
+                .unwrap();
+
+            // every number is checked, even once one comparison is false
+            this.builder.position_at_end(compare_next);
+            let n = this
+                .get_number_argument(name, this.make_unchecked_car(rest_load), hint)
+                .into_float_value();
+            let prev_load = this
+                .builder
+                .build_load(number, prev, "load prev")
+                .unwrap()
+                .into_float_value();
+            let holds = this
+                .builder
+                .build_float_compare(predicate, prev_load, n, name)
+                .unwrap();
+            let all_load = this
+                .builder
+                .build_load(bool_type, all, "load all")
+                .unwrap()
+                .into_int_value();
+            let all_holds = this.builder.build_and(all_load, holds, "all").unwrap();
+            this.builder.build_store(all, all_holds).unwrap();
+            this.builder.build_store(prev, n).unwrap();
+            this.builder
+                .build_store(rest, this.make_unchecked_cdr(rest_load))
+                .unwrap();
+            this.builder.build_unconditional_branch(compare).unwrap();
+
+            this.builder.position_at_end(done);
+            let result = this.builder.build_load(bool_type, all, "load all").unwrap();
+            Values::Single(this.make_object(&result, TypeIndex::bool))
+        })
+    }
+
+    // a stack variable that starts as `value`, it should be made outside of any loop
+    fn build_variable(&self, name: &str, value: impl BasicValue<'ctx>) -> PointerValue<'ctx> {
+        let value = value.as_basic_value_enum();
+        let variable = self.builder.build_alloca(value.get_type(), name).unwrap();
+        self.builder.build_store(variable, value).unwrap();
+        variable
+    }
+
+    fn load_variable(
+        &self,
+        variable: PointerValue<'ctx>,
+        kind: impl BasicType<'ctx>,
+    ) -> BasicValueEnum<'ctx> {
+        self.builder
+            .build_load(kind, variable, "load variable")
+            .unwrap()
+    }
+
+    // runs the code from `body` for as long as the code from `condition` is true (both are only
+    // compiled once)
+    fn build_while(
+        &self,
+        name: &str,
+        condition: impl FnOnce(&Self) -> IntValue<'ctx>,
+        body: impl FnOnce(&Self),
+    ) {
+        let block = |end| {
+            self.context
+                .append_basic_block(self.current, &format!("{name}:{end}"))
+        };
+        let test = block("test");
+        let body_block = block("body");
+        let done = block("done");
+        self.builder.build_unconditional_branch(test).unwrap();
+
+        self.builder.position_at_end(test);
+        let holds = condition(self);
+        self.builder
+            .build_conditional_branch(holds, body_block, done)
+            .unwrap();
+
+        self.builder.position_at_end(body_block);
+        body(self);
+        self.builder.build_unconditional_branch(test).unwrap();
+
+        self.builder.position_at_end(done);
+    }
+
+    // runs the code from `body` with each i from `start` up to (but not including) `end`
+    fn build_for(
+        &self,
+        name: &str,
+        start: IntValue<'ctx>,
+        end: IntValue<'ctx>,
+        body: impl FnOnce(&Self, IntValue<'ctx>),
+    ) {
+        let i32_type = self.context.i32_type();
+        let i = self.build_variable("i", start);
+        self.build_while(
+            name,
+            |this| {
+                let i = this.load_variable(i, i32_type).into_int_value();
+                this.builder
+                    .build_int_compare(IntPredicate::SLT, i, end, "in range")
+                    .unwrap()
+            },
+            |this| {
+                let i_value = this.load_variable(i, i32_type).into_int_value();
+                body(this, i_value);
+                let next = this
+                    .builder
+                    .build_int_add(i_value, i32_type.const_int(1, false), "next")
+                    .unwrap();
+                this.builder.build_store(i, next).unwrap();
+            },
+        );
+    }
+
+    // runs the code from `body` with each element of `list`
+    fn build_for_each(
+        &self,
+        name: &str,
+        list: StructValue<'ctx>,
+        body: impl FnOnce(&Self, StructValue<'ctx>),
+    ) {
+        let rest = self.build_variable("rest", list);
+        self.build_while(
+            name,
+            |this| {
+                let rest = this
+                    .load_variable(rest, this.types.object)
+                    .into_struct_value();
+                this.builder
+                    .build_not(this.is_hempty(rest), "not empty")
+                    .unwrap()
+            },
+            |this| {
+                let rest_value = this
+                    .load_variable(rest, this.types.object)
+                    .into_struct_value();
+                body(this, this.make_car(rest_value));
+                this.builder
+                    .build_store(rest, this.make_cdr(rest_value))
+                    .unwrap();
+            },
+        );
+    }
+
+    // adds `value` to the end of the list that goes from the variables `head` to `tail`
+    fn push_list(
+        &self,
+        head: PointerValue<'ctx>,
+        tail: PointerValue<'ctx>,
+        value: StructValue<'ctx>,
+    ) {
+        let cell = self.make_cons(value, self.empty());
+        let tail_value = self
+            .load_variable(tail, self.types.object)
+            .into_struct_value();
+        let block = |end| {
+            self.context
+                .append_basic_block(self.current, &format!("push-list:{end}"))
+        };
+        let first = block("first");
+        let after = block("after");
+        let done = block("done");
+        self.builder
+            .build_conditional_branch(self.is_hempty(tail_value), first, after)
+            .unwrap();
+
+        self.builder.position_at_end(first);
+        self.builder.build_store(head, cell).unwrap();
+        self.builder.build_unconditional_branch(done).unwrap();
+
+        self.builder.position_at_end(after);
+        self.make_unchecked_set_cdr(tail_value, cell);
+        self.builder.build_unconditional_branch(done).unwrap();
+
+        self.builder.position_at_end(done);
+        self.builder.build_store(tail, cell).unwrap();
+    }
+
+    // the length (in bytes) and the data of a string or symbol
+    fn string_parts(&self, s: BasicValueEnum<'ctx>) -> (IntValue<'ctx>, PointerValue<'ctx>) {
+        let s = s.into_struct_value();
+        let len = self
+            .builder
+            .build_extract_value(s, 0, "string length")
+            .unwrap()
+            .into_int_value();
+        let data = self
+            .builder
+            .build_extract_value(s, 1, "string data")
+            .unwrap()
+            .into_pointer_value();
+        (len, data)
+    }
+
+    fn make_string(
+        &self,
+        len: IntValue<'ctx>,
+        data: PointerValue<'ctx>,
+        index: TypeIndex,
+    ) -> StructValue<'ctx> {
+        let s = self.types.string.const_zero();
+        let s = self
+            .builder
+            .build_insert_value(s, len, 0, "insert string length")
+            .unwrap();
+        let s = self
+            .builder
+            .build_insert_value(s, data, 1, "insert string data")
+            .unwrap();
+        self.make_object(&s.into_struct_value(), index)
+    }
+
+    fn byte_pointer(&self, data: PointerValue<'ctx>, offset: IntValue<'ctx>) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder.build_in_bounds_gep(
+                self.context.i8_type(),
+                data,
+                &[offset],
+                "byte pointer",
+            )
+        }
+        .unwrap()
+    }
+
+    fn string_byte(&self, data: PointerValue<'ctx>, offset: IntValue<'ctx>) -> IntValue<'ctx> {
+        self.builder
+            .build_load(
+                self.context.i8_type(),
+                self.byte_pointer(data, offset),
+                "byte",
+            )
+            .unwrap()
+            .into_int_value()
+    }
+
+    // the byte at `offset`, or zero past the end of the string
+    fn peek_byte(
+        &self,
+        len: IntValue<'ctx>,
+        data: PointerValue<'ctx>,
+        offset: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let in_range = self
+            .builder
+            .build_int_compare(IntPredicate::SLT, offset, len, "in range")
+            .unwrap();
+        let current = self.builder.get_insert_block().unwrap();
+        let read = self.context.append_basic_block(self.current, "peek:read");
+        let done = self.context.append_basic_block(self.current, "peek:done");
+        self.builder
+            .build_conditional_branch(in_range, read, done)
+            .unwrap();
+
+        self.builder.position_at_end(read);
+        let byte = self.string_byte(data, offset);
+        self.builder.build_unconditional_branch(done).unwrap();
+
+        self.builder.position_at_end(done);
+        let i8_type = self.context.i8_type();
+        let peeked = self.builder.build_phi(i8_type, "peeked").unwrap();
+        peeked.add_incoming(&[(&i8_type.const_zero(), current), (&byte, read)]);
+        peeked.as_basic_value().into_int_value()
+    }
+
+    fn memcmp(
+        &self,
+        a: PointerValue<'ctx>,
+        b: PointerValue<'ctx>,
+        len: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let len = self
+            .builder
+            .build_int_z_extend(len, self.context.i64_type(), "length")
+            .unwrap();
+        self.builder
+            .build_call(
+                self.functions.memcmp,
+                &[a.into(), b.into(), len.into()],
+                "memcmp",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value()
+    }
+
+    // utf-8 continuation bytes look like 10xxxxxx, every other byte starts a character
+    fn is_char_start(&self, byte: IntValue<'ctx>) -> IntValue<'ctx> {
+        let i8_type = self.context.i8_type();
+        let top = self
+            .builder
+            .build_and(byte, i8_type.const_int(0xC0, false), "top bits")
+            .unwrap();
+        self.builder
+            .build_int_compare(
+                IntPredicate::NE,
+                top,
+                i8_type.const_int(0x80, false),
+                "is char start",
+            )
+            .unwrap()
+    }
+
+    fn char_count(
+        &self,
+        name: &str,
+        len: IntValue<'ctx>,
+        data: PointerValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let count = self.build_variable("count", i32_type.const_zero());
+        self.build_for(
+            &format!("{name}:count"),
+            i32_type.const_zero(),
+            len,
+            |this, i| {
+                let start = this.is_char_start(this.string_byte(data, i));
+                let start = this
+                    .builder
+                    .build_int_z_extend(start, i32_type, "start")
+                    .unwrap();
+                let count_value = this.load_variable(count, i32_type).into_int_value();
+                let count_value = this
+                    .builder
+                    .build_int_add(count_value, start, "count")
+                    .unwrap();
+                this.builder.build_store(count, count_value).unwrap();
+            },
+        );
+        self.load_variable(count, i32_type).into_int_value()
+    }
+
+    // the byte offset of the character at `index`, which can also be the number of characters (the
+    // offset of the end of the string)
+    fn char_offset(
+        &self,
+        name: &str,
+        len: IntValue<'ctx>,
+        data: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> IntValue<'ctx> {
+        let i32_type = self.context.i32_type();
+        let offset = self.build_variable("offset", len);
+        let count = self.build_variable("count", i32_type.const_zero());
+        self.build_for(
+            &format!("{name}:offset"),
+            i32_type.const_zero(),
+            len,
+            |this, i| {
+                let count_value = this.load_variable(count, i32_type).into_int_value();
+                let start = this.is_char_start(this.string_byte(data, i));
+                let is_index = this
+                    .builder
+                    .build_int_compare(IntPredicate::EQ, count_value, index, "is index")
+                    .unwrap();
+                let is_index = this.builder.build_and(start, is_index, "found").unwrap();
+                let offset_value = this.load_variable(offset, i32_type);
+                let offset_value = this
+                    .builder
+                    .build_select(is_index, i.into(), offset_value, "offset")
+                    .unwrap();
+                this.builder.build_store(offset, offset_value).unwrap();
+                let start = this
+                    .builder
+                    .build_int_z_extend(start, i32_type, "start")
+                    .unwrap();
+                let count_value = this
+                    .builder
+                    .build_int_add(count_value, start, "count")
+                    .unwrap();
+                this.builder.build_store(count, count_value).unwrap();
+            },
+        );
+        let count = self.load_variable(count, i32_type).into_int_value();
+        let in_range = self
+            .builder
+            .build_int_compare(IntPredicate::ULE, index, count, "in range")
+            .unwrap();
+        self.check(name, in_range, &format!("{name}: index out of range\n"));
+        self.load_variable(offset, i32_type).into_int_value()
+    }
+
+    // a whole number that is not negative, which is how strings are indexed
+    fn get_index(&self, name: &str, obj: StructValue<'ctx>) -> IntValue<'ctx> {
+        let n = self.get_number(obj).into_float_value();
+        let number = n.get_type();
+        let compare = |predicate, other: FloatValue<'ctx>| {
+            self.builder
+                .build_float_compare(predicate, n, other, "index")
+                .unwrap()
+        };
+        let not_negative = compare(FloatPredicate::OGE, number.const_zero());
+        let fits = compare(FloatPredicate::OLT, number.const_float(f64::from(i32::MAX)));
+        let whole = compare(
+            FloatPredicate::OEQ,
+            self.call_number_intrinsic("llvm.floor", &[n]),
+        );
+        let is_index = self.builder.build_and(not_negative, fits, "index").unwrap();
+        let is_index = self.builder.build_and(is_index, whole, "index").unwrap();
+        self.check(name, is_index, &format!("{name}: expected an index\n"));
+        self.builder
+            .build_float_to_unsigned_int(n, self.context.i32_type(), "index")
+            .unwrap()
+    }
+
+    // the characters of the string from start up to end
+    fn substring(
+        &self,
+        name: &str,
+        len: IntValue<'ctx>,
+        data: PointerValue<'ctx>,
+        start: IntValue<'ctx>,
+        end: IntValue<'ctx>,
+    ) -> StructValue<'ctx> {
+        let ordered = self
+            .builder
+            .build_int_compare(IntPredicate::ULE, start, end, "ordered")
+            .unwrap();
+        self.check(name, ordered, &format!("{name}: start is after end\n"));
+        let start = self.char_offset(name, len, data, start);
+        let end = self.char_offset(name, len, data, end);
+        let len = self.builder.build_int_sub(end, start, "length").unwrap();
+        self.make_string(len, self.byte_pointer(data, start), TypeIndex::string)
+    }
+
+    // the character for the hex digit `d`
+    fn hex_digit(&self, d: FloatValue<'ctx>) -> IntValue<'ctx> {
+        let i8_type = self.context.i8_type();
+        let d = self
+            .builder
+            .build_float_to_unsigned_int(d, i8_type, "digit")
+            .unwrap();
+        let is_decimal = self
+            .builder
+            .build_int_compare(
+                IntPredicate::ULT,
+                d,
+                i8_type.const_int(10, false),
+                "is decimal",
+            )
+            .unwrap();
+        let decimal = self
+            .builder
+            .build_int_add(d, i8_type.const_int(u64::from(b'0'), false), "decimal")
+            .unwrap();
+        let letter = self
+            .builder
+            .build_int_add(d, i8_type.const_int(u64::from(b'a' - 10), false), "letter")
+            .unwrap();
+        self.builder
+            .build_select(is_decimal, decimal, letter, "digit")
+            .unwrap()
+            .into_int_value()
+    }
+
+    // the value of the digit `byte`, and whether it is one (letters are only digits with 0x)
+    fn parse_digit(
+        &self,
+        byte: IntValue<'ctx>,
+        hex: IntValue<'ctx>,
+    ) -> (FloatValue<'ctx>, IntValue<'ctx>) {
+        let i8_type = self.context.i8_type();
+        let between = |low: u8, high: u8| {
+            let compare = |predicate, bound: u8| {
+                self.builder
+                    .build_int_compare(
+                        predicate,
+                        byte,
+                        i8_type.const_int(u64::from(bound), false),
+                        "digit",
+                    )
+                    .unwrap()
+            };
+            let above = compare(IntPredicate::UGE, low);
+            let below = compare(IntPredicate::ULE, high);
+            self.builder.build_and(above, below, "between").unwrap()
+        };
+        let is_decimal = between(b'0', b'9');
+        let is_letter = between(b'a', b'f');
+        let is_letter = self.builder.build_and(hex, is_letter, "is letter").unwrap();
+        let is_digit = self
+            .builder
+            .build_or(is_decimal, is_letter, "is digit")
+            .unwrap();
+        let decimal = self
+            .builder
+            .build_int_sub(byte, i8_type.const_int(u64::from(b'0'), false), "decimal")
+            .unwrap();
+        let letter = self
+            .builder
+            .build_int_sub(
+                byte,
+                i8_type.const_int(u64::from(b'a' - 10), false),
+                "letter",
+            )
+            .unwrap();
+        let digit = self
+            .builder
+            .build_select(is_decimal, decimal, letter, "digit")
+            .unwrap()
+            .into_int_value();
+        let digit = self
+            .builder
+            .build_unsigned_int_to_float(digit, self.types.types.number.into_float_type(), "digit")
+            .unwrap();
+        (digit, is_digit)
+    }
+
+    // the same digits as number_to_string in the interpreter, the buffer fits the most that a
+    // float can have: a sign, 0x, 256 whole digits, a `%` and 269 fraction digits
+    fn number_to_string(&self, n: FloatValue<'ctx>) -> StructValue<'ctx> {
+        let number = n.get_type();
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+        let sixteen = number.const_float(16.);
+        let result = self.build_variable("result", self.empty());
+        let block = |end| {
+            self.context
+                .append_basic_block(self.current, &format!("number->string:{end}"))
+        };
+        let finite = block("finite");
+        let not_finite = block("not-finite");
+        let done = block("done");
+        // n - n is nan for nan and the infinities
+        let difference = self.builder.build_float_sub(n, n, "difference").unwrap();
+        let is_finite = self
+            .builder
+            .build_float_compare(
+                FloatPredicate::OEQ,
+                difference,
+                number.const_zero(),
+                "is finite",
+            )
+            .unwrap();
+        self.builder
+            .build_conditional_branch(is_finite, finite, not_finite)
+            .unwrap();
+
+        self.builder.position_at_end(not_finite);
+        let is_nan = self
+            .builder
+            .build_float_compare(FloatPredicate::UNO, n, n, "is nan")
+            .unwrap();
+        let is_positive = self
+            .builder
+            .build_float_compare(FloatPredicate::OGT, n, number.const_zero(), "is positive")
+            .unwrap();
+        let infinity = self
+            .builder
+            .build_select(
+                is_positive,
+                self.create_string("inf"),
+                self.create_string("-inf"),
+                "infinity",
+            )
+            .unwrap();
+        let special = self
+            .builder
+            .build_select(
+                is_nan,
+                self.create_string("nan").into(),
+                infinity,
+                "special",
+            )
+            .unwrap();
+        self.builder.build_store(result, special).unwrap();
+        self.builder.build_unconditional_branch(done).unwrap();
+
+        self.builder.position_at_end(finite);
+        let buffer = self
+            .builder
+            .build_array_malloc(i8_type, i32_type.const_int(530, false), "buffer")
+            .unwrap();
+        let write = |at, byte: u8| {
+            self.builder
+                .build_store(
+                    self.byte_pointer(buffer, at),
+                    i8_type.const_int(u64::from(byte), false),
+                )
+                .unwrap();
+        };
+        // the sign is only kept (by moving past it) for negative numbers
+        let is_negative = self
+            .builder
+            .build_float_compare(FloatPredicate::OLT, n, number.const_zero(), "is negative")
+            .unwrap();
+        let sign = self
+            .builder
+            .build_int_z_extend(is_negative, i32_type, "sign")
+            .unwrap();
+        write(i32_type.const_zero(), b'-');
+        write(sign, b'0');
+        let after_zero = self
+            .builder
+            .build_int_add(sign, i32_type.const_int(1, false), "after 0")
+            .unwrap();
+        write(after_zero, b'x');
+        let start = self
+            .builder
+            .build_int_add(sign, i32_type.const_int(2, false), "after 0x")
+            .unwrap();
+
+        let n = self.call_number_intrinsic("llvm.fabs", &[n]);
+        let whole = self.call_number_intrinsic("llvm.floor", &[n]);
+        let fraction = self.builder.build_float_sub(n, whole, "fraction").unwrap();
+        let fraction = self.build_variable("fraction", fraction);
+        let divide = |this: &Self, n: FloatValue<'ctx>| {
+            let quotient = this
+                .builder
+                .build_float_div(n, sixteen, "quotient")
+                .unwrap();
+            this.call_number_intrinsic("llvm.floor", &[quotient])
+        };
+        // the number of whole digits, there is always at least one
+        let digits = self.build_variable("digits", i32_type.const_int(1, false));
+        let rest = self.build_variable("rest", divide(self, whole));
+        self.build_while(
+            "number->string:count",
+            |this| {
+                let rest = this.load_variable(rest, number).into_float_value();
+                this.builder
+                    .build_float_compare(FloatPredicate::OGT, rest, number.const_zero(), "more")
+                    .unwrap()
+            },
+            |this| {
+                let digits_value = this.load_variable(digits, i32_type).into_int_value();
+                let digits_value = this
+                    .builder
+                    .build_int_add(digits_value, i32_type.const_int(1, false), "digits")
+                    .unwrap();
+                this.builder.build_store(digits, digits_value).unwrap();
+                let rest_value = this.load_variable(rest, number).into_float_value();
+                this.builder
+                    .build_store(rest, divide(this, rest_value))
+                    .unwrap();
+            },
+        );
+        // the digits are found from the last one
+        let digits = self.load_variable(digits, i32_type).into_int_value();
+        let end = self.builder.build_int_add(start, digits, "end").unwrap();
+        let last = self
+            .builder
+            .build_int_sub(end, i32_type.const_int(1, false), "last")
+            .unwrap();
+        self.builder.build_store(rest, whole).unwrap();
+        self.build_for(
+            "number->string:whole",
+            i32_type.const_zero(),
+            digits,
+            |this, i| {
+                let rest_value = this.load_variable(rest, number).into_float_value();
+                let next = divide(this, rest_value);
+                let shifted = this
+                    .builder
+                    .build_float_mul(next, sixteen, "shifted")
+                    .unwrap();
+                let d = this
+                    .builder
+                    .build_float_sub(rest_value, shifted, "digit")
+                    .unwrap();
+                let at = this.builder.build_int_sub(last, i, "at").unwrap();
+                this.builder
+                    .build_store(this.byte_pointer(buffer, at), this.hex_digit(d))
+                    .unwrap();
+                this.builder.build_store(rest, next).unwrap();
+            },
+        );
+
+        // like the sign the `%` is only kept if there is a fraction
+        let fraction_value = self.load_variable(fraction, number).into_float_value();
+        let has_fraction = self
+            .builder
+            .build_float_compare(
+                FloatPredicate::OGT,
+                fraction_value,
+                number.const_zero(),
+                "has fraction",
+            )
+            .unwrap();
+        write(end, b'%');
+        let has_fraction = self
+            .builder
+            .build_int_z_extend(has_fraction, i32_type, "has fraction")
+            .unwrap();
+        let position = self
+            .builder
+            .build_int_add(end, has_fraction, "position")
+            .unwrap();
+        let position = self.build_variable("position", position);
+        self.build_while(
+            "number->string:fraction",
+            |this| {
+                let fraction = this.load_variable(fraction, number).into_float_value();
+                this.builder
+                    .build_float_compare(FloatPredicate::OGT, fraction, number.const_zero(), "more")
+                    .unwrap()
+            },
+            |this| {
+                let fraction_value = this.load_variable(fraction, number).into_float_value();
+                let shifted = this
+                    .builder
+                    .build_float_mul(fraction_value, sixteen, "shifted")
+                    .unwrap();
+                let d = this.call_number_intrinsic("llvm.floor", &[shifted]);
+                let at = this.load_variable(position, i32_type).into_int_value();
+                this.builder
+                    .build_store(this.byte_pointer(buffer, at), this.hex_digit(d))
+                    .unwrap();
+                let at = this
+                    .builder
+                    .build_int_add(at, i32_type.const_int(1, false), "position")
+                    .unwrap();
+                this.builder.build_store(position, at).unwrap();
+                let rest = this
+                    .builder
+                    .build_float_sub(shifted, d, "fraction")
+                    .unwrap();
+                this.builder.build_store(fraction, rest).unwrap();
+            },
+        );
+        let len = self.load_variable(position, i32_type).into_int_value();
+        self.builder
+            .build_store(result, self.make_string(len, buffer, TypeIndex::string))
+            .unwrap();
+        self.builder.build_unconditional_branch(done).unwrap();
+
+        self.builder.position_at_end(done);
+        self.load_variable(result, self.types.object)
+            .into_struct_value()
+    }
+
+    // the same as string_to_number in the interpreter, a number or false if the string is not one
+    fn string_to_number(&self, len: IntValue<'ctx>, data: PointerValue<'ctx>) -> StructValue<'ctx> {
+        let number = self.types.types.number.into_float_type();
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+        let bool_type = self.context.bool_type();
+        let one = i32_type.const_int(1, false);
+        let is_byte = |byte: IntValue<'ctx>, expected: u8| {
+            self.builder
+                .build_int_compare(
+                    IntPredicate::EQ,
+                    byte,
+                    i8_type.const_int(u64::from(expected), false),
+                    "is byte",
+                )
+                .unwrap()
+        };
+        let is_negative = is_byte(self.peek_byte(len, data, i32_type.const_zero()), b'-');
+        let start = self
+            .builder
+            .build_int_z_extend(is_negative, i32_type, "start")
+            .unwrap();
+        let after_zero = self.builder.build_int_add(start, one, "after 0").unwrap();
+        let is_zero = is_byte(self.peek_byte(len, data, start), b'0');
+        let is_x = is_byte(self.peek_byte(len, data, after_zero), b'x');
+        let is_hex = self.builder.build_and(is_zero, is_x, "is hex").unwrap();
+        let prefix = self
+            .builder
+            .build_int_z_extend(is_hex, i32_type, "prefix")
+            .unwrap();
+        let prefix = self
+            .builder
+            .build_int_mul(prefix, i32_type.const_int(2, false), "prefix")
+            .unwrap();
+        let start = self.builder.build_int_add(start, prefix, "start").unwrap();
+        let i = self.build_variable("i", start);
+        let n = self.build_variable("n", number.const_zero());
+        let scale = self.build_variable("scale", number.const_float(1.));
+        let valid = self.build_variable("valid", bool_type.const_all_ones());
+        let whole_digits = self.build_variable("whole digits", i32_type.const_zero());
+        let fraction_digits = self.build_variable("fraction digits", i32_type.const_zero());
+        let increment = |this: &Self, variable| {
+            let value = this.load_variable(variable, i32_type).into_int_value();
+            let value = this.builder.build_int_add(value, one, "increment").unwrap();
+            this.builder.build_store(variable, value).unwrap();
+        };
+        let in_range = |this: &Self| {
+            let i = this.load_variable(i, i32_type).into_int_value();
+            this.builder
+                .build_int_compare(IntPredicate::SLT, i, len, "in range")
+                .unwrap()
+        };
+        // reads the digit at i into n, `add` finds the new n from the old one and the digit
+        let read_digit = |this: &Self,
+                          add: &dyn Fn(
+            &Self,
+            FloatValue<'ctx>,
+            FloatValue<'ctx>,
+        ) -> FloatValue<'ctx>| {
+            let i_value = this.load_variable(i, i32_type).into_int_value();
+            let (digit, is_digit) = this.parse_digit(this.string_byte(data, i_value), is_hex);
+            let valid_value = this.load_variable(valid, bool_type).into_int_value();
+            let valid_value = this
+                .builder
+                .build_and(valid_value, is_digit, "valid")
+                .unwrap();
+            this.builder.build_store(valid, valid_value).unwrap();
+            let n_value = this.load_variable(n, number).into_float_value();
+            this.builder
+                .build_store(n, add(this, n_value, digit))
+                .unwrap();
+            increment(this, i);
+        };
+        self.build_while(
+            "string->number:whole",
+            |this| {
+                let i_value = this.load_variable(i, i32_type).into_int_value();
+                let not_fraction = this
+                    .builder
+                    .build_not(is_byte(this.peek_byte(len, data, i_value), b'%'), "not %")
+                    .unwrap();
+                this.builder
+                    .build_and(in_range(this), not_fraction, "whole")
+                    .unwrap()
+            },
+            |this| {
+                read_digit(this, &|this, n, digit| {
+                    this.call_number_intrinsic("llvm.fma", &[n, number.const_float(16.), digit])
+                });
+                increment(this, whole_digits);
+            },
+        );
+        // the loop only stops before the end at a `%`
+        let has_fraction = in_range(self);
+        let has_fraction_offset = self
+            .builder
+            .build_int_z_extend(has_fraction, i32_type, "has fraction")
+            .unwrap();
+        let i_value = self.load_variable(i, i32_type).into_int_value();
+        let i_value = self
+            .builder
+            .build_int_add(i_value, has_fraction_offset, "after %")
+            .unwrap();
+        self.builder.build_store(i, i_value).unwrap();
+        self.build_while("string->number:fraction", in_range, |this| {
+            let scale_value = this.load_variable(scale, number).into_float_value();
+            let scale_value = this
+                .builder
+                .build_float_div(scale_value, number.const_float(16.), "scale")
+                .unwrap();
+            this.builder.build_store(scale, scale_value).unwrap();
+            read_digit(this, &|this, n, digit| {
+                this.call_number_intrinsic("llvm.fma", &[digit, scale_value, n])
+            });
+            increment(this, fraction_digits);
+        });
+        // there has to be a digit (after the `%` if there is one)
+        let digits = self
+            .builder
+            .build_select(has_fraction, fraction_digits, whole_digits, "digits")
+            .unwrap()
+            .into_pointer_value();
+        let digits = self.load_variable(digits, i32_type).into_int_value();
+        let has_digits = self
+            .builder
+            .build_int_compare(
+                IntPredicate::UGT,
+                digits,
+                i32_type.const_zero(),
+                "has digits",
+            )
+            .unwrap();
+        let valid = self.load_variable(valid, bool_type).into_int_value();
+        let is_number = self
+            .builder
+            .build_and(valid, has_digits, "is number")
+            .unwrap();
+        let n = self.load_variable(n, number).into_float_value();
+        let negated = self.builder.build_float_neg(n, "negated").unwrap();
+        let n = self
+            .builder
+            .build_select(is_negative, negated, n, "signed")
+            .unwrap();
+        let not_number = self.make_object(&bool_type.const_zero(), TypeIndex::bool);
+        self.builder
+            .build_select(
+                is_number,
+                self.make_object(&n, TypeIndex::number),
+                not_number,
+                "string->number",
+            )
+            .unwrap()
+            .into_struct_value()
+    }
+
+    fn create_string_comparison_primitive(
+        &mut self,
+        name: &str,
+        compare: impl FnOnce(&Self, IntValue<'ctx>, IntValue<'ctx>, IntValue<'ctx>) -> IntValue<'ctx>,
+    ) -> BasicBlock<'ctx> {
+        self.create_simple_primitive(name, |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let (a_len, a) = this.string_parts(this.get_string(this.make_car(argl)));
+            let (b_len, b) = this.string_parts(this.get_string(this.make_cadr(argl)));
+            let a_shorter = this
+                .builder
+                .build_int_compare(IntPredicate::ULT, a_len, b_len, "a shorter")
+                .unwrap();
+            let shortest = this
+                .builder
+                .build_select(a_shorter, a_len, b_len, "shortest")
+                .unwrap()
+                .into_int_value();
+            // the order of the common part, which is by bytes, and so by characters in utf-8
+            let order = this.memcmp(a, b, shortest);
+            let result = compare(this, order, a_len, b_len);
+            Values::Single(this.make_object(&result, TypeIndex::bool))
+        })
+    }
+
+    fn init_string_primitives(&mut self) -> Vec<(&'static str, BasicBlock<'ctx>)> {
+        let i8_type = self.context.i8_type();
+        let i32_type = self.context.i32_type();
+        let string_length = self.create_simple_primitive("string-length", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let (len, data) = this.string_parts(this.get_string(this.make_car(argl)));
+            let count = this.char_count("string-length", len, data);
+            let count = this
+                .builder
+                .build_unsigned_int_to_float(
+                    count,
+                    this.types.types.number.into_float_type(),
+                    "length",
+                )
+                .unwrap();
+            Values::Single(this.make_object(&count, TypeIndex::number))
+        });
+        // the first pass finds the length of the new string, and the second copies each string in
+        let string_append = self.create_simple_primitive("string-append", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let total = this.build_variable("total", i32_type.const_zero());
+            this.build_for_each("string-append:length", argl, |this, s| {
+                let (len, _) = this.string_parts(this.get_string(s));
+                let total_value = this.load_variable(total, i32_type).into_int_value();
+                let total_value = this
+                    .builder
+                    .build_int_add(total_value, len, "total")
+                    .unwrap();
+                this.builder.build_store(total, total_value).unwrap();
+            });
+            let total = this.load_variable(total, i32_type).into_int_value();
+            let buffer = this
+                .builder
+                .build_array_malloc(i8_type, total, "buffer")
+                .unwrap();
+            let position = this.build_variable("position", i32_type.const_zero());
+            this.build_for_each("string-append:copy", argl, |this, s| {
+                let (len, data) = this.string_parts(this.unchecked_get_string(s));
+                let at = this.load_variable(position, i32_type).into_int_value();
+                this.builder
+                    .build_memcpy(this.byte_pointer(buffer, at), 1, data, 1, len)
+                    .unwrap();
+                let at = this.builder.build_int_add(at, len, "position").unwrap();
+                this.builder.build_store(position, at).unwrap();
+            });
+            Values::Single(this.make_string(total, buffer, TypeIndex::string))
+        });
+        // the new strings share the data of the old one
+        let substring = self.create_simple_primitive("substring", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let (len, data) = this.string_parts(this.get_string(this.make_car(argl)));
+            let start = this.get_index("substring", this.make_cadr(argl));
+            let rest = this.make_cddr(argl);
+            let end = this.build_variable("end", i32_type.const_zero());
+            let block = |end| {
+                this.context
+                    .append_basic_block(this.current, &format!("substring:{end}"))
+            };
+            let to_length = block("to-length");
+            let to_end = block("to-end");
+            let done = block("done");
+            this.builder
+                .build_conditional_branch(this.is_hempty(rest), to_length, to_end)
+                .unwrap();
+
+            this.builder.position_at_end(to_length);
+            let count = this.char_count("substring", len, data);
+            this.builder.build_store(end, count).unwrap();
+            this.builder.build_unconditional_branch(done).unwrap();
+
+            this.builder.position_at_end(to_end);
+            let end_index = this.get_index("substring", this.make_car(rest));
+            this.builder.build_store(end, end_index).unwrap();
+            this.builder.build_unconditional_branch(done).unwrap();
+
+            this.builder.position_at_end(done);
+            let end = this.load_variable(end, i32_type).into_int_value();
+            Values::Single(this.substring("substring", len, data, start, end))
+        });
+        let string_ref = self.create_simple_primitive("string-ref", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let (len, data) = this.string_parts(this.get_string(this.make_car(argl)));
+            let i = this.get_index("string-ref", this.make_cadr(argl));
+            let end = this
+                .builder
+                .build_int_add(i, i32_type.const_int(1, false), "end")
+                .unwrap();
+            Values::Single(this.substring("string-ref", len, data, i, end))
+        });
+        let string_equal =
+            self.create_string_comparison_primitive("string=?", |this, order, a_len, b_len| {
+                let same = this
+                    .builder
+                    .build_int_compare(IntPredicate::EQ, order, i32_type.const_zero(), "same")
+                    .unwrap();
+                let same_length = this
+                    .builder
+                    .build_int_compare(IntPredicate::EQ, a_len, b_len, "same length")
+                    .unwrap();
+                this.builder
+                    .build_and(same, same_length, "string=?")
+                    .unwrap()
+            });
+        // when one string starts with the other the shorter one is first
+        let string_less =
+            self.create_string_comparison_primitive("string<?", |this, order, a_len, b_len| {
+                let less = this
+                    .builder
+                    .build_int_compare(IntPredicate::SLT, order, i32_type.const_zero(), "less")
+                    .unwrap();
+                let same = this
+                    .builder
+                    .build_int_compare(IntPredicate::EQ, order, i32_type.const_zero(), "same")
+                    .unwrap();
+                let shorter = this
+                    .builder
+                    .build_int_compare(IntPredicate::ULT, a_len, b_len, "shorter")
+                    .unwrap();
+                let same_but_shorter = this.builder.build_and(same, shorter, "prefix").unwrap();
+                this.builder
+                    .build_or(less, same_but_shorter, "string<?")
+                    .unwrap()
+            });
+        // strings and symbols are stored the same way
+        let string_to_symbol = self.create_simple_primitive("string->symbol", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let s = this.get_string(this.make_car(argl));
+            Values::Single(this.make_object(&s, TypeIndex::symbol))
+        });
+        let symbol_to_string = self.create_simple_primitive("symbol->string", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let s = this.get_symbol(this.make_car(argl));
+            Values::Single(this.make_object(&s, TypeIndex::string))
+        });
+        let number_to_string = self.create_simple_primitive("number->string", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let n = this.get_number(this.make_car(argl)).into_float_value();
+            Values::Single(this.number_to_string(n))
+        });
+        let string_to_number = self.create_simple_primitive("string->number", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let (len, data) = this.string_parts(this.get_string(this.make_car(argl)));
+            Values::Single(this.string_to_number(len, data))
+        });
+        // a match is only searched for after the previous one, so matches do not overlap
+        let string_split = self.create_simple_primitive("string-split", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let (len, data) = this.string_parts(this.get_string(this.make_car(argl)));
+            let (separator_len, separator) =
+                this.string_parts(this.get_string(this.make_cadr(argl)));
+            let not_empty = this
+                .builder
+                .build_int_compare(
+                    IntPredicate::UGT,
+                    separator_len,
+                    i32_type.const_zero(),
+                    "not empty",
+                )
+                .unwrap();
+            this.check(
+                "string-split",
+                not_empty,
+                "string-split: the separator cannot be empty\n",
+            );
+            let head = this.build_variable("head", this.empty());
+            let tail = this.build_variable("tail", this.empty());
+            let start = this.build_variable("start", i32_type.const_zero());
+            let i = this.build_variable("i", i32_type.const_zero());
+            // the part from start up to `end`
+            let part = |this: &Self, end: IntValue<'ctx>| {
+                let start = this.load_variable(start, i32_type).into_int_value();
+                let len = this.builder.build_int_sub(end, start, "length").unwrap();
+                this.make_string(len, this.byte_pointer(data, start), TypeIndex::string)
+            };
+            this.build_while(
+                "string-split",
+                |this| {
+                    let i = this.load_variable(i, i32_type).into_int_value();
+                    let end = this.builder.build_int_add(i, separator_len, "end").unwrap();
+                    this.builder
+                        .build_int_compare(IntPredicate::SLE, end, len, "fits")
+                        .unwrap()
+                },
+                |this| {
+                    let i_value = this.load_variable(i, i32_type).into_int_value();
+                    let order =
+                        this.memcmp(this.byte_pointer(data, i_value), separator, separator_len);
+                    let matches = this
+                        .builder
+                        .build_int_compare(
+                            IntPredicate::EQ,
+                            order,
+                            i32_type.const_zero(),
+                            "matches",
+                        )
+                        .unwrap();
+                    let block = |end| {
+                        this.context
+                            .append_basic_block(this.current, &format!("string-split:{end}"))
+                    };
+                    let found = block("found");
+                    let not_found = block("not-found");
+                    let next = block("next");
+                    this.builder
+                        .build_conditional_branch(matches, found, not_found)
+                        .unwrap();
+
+                    this.builder.position_at_end(found);
+                    this.push_list(head, tail, part(this, i_value));
+                    let after = this
+                        .builder
+                        .build_int_add(i_value, separator_len, "after")
+                        .unwrap();
+                    this.builder.build_store(i, after).unwrap();
+                    this.builder.build_store(start, after).unwrap();
+                    this.builder.build_unconditional_branch(next).unwrap();
+
+                    this.builder.position_at_end(not_found);
+                    let after = this
+                        .builder
+                        .build_int_add(i_value, i32_type.const_int(1, false), "after")
+                        .unwrap();
+                    this.builder.build_store(i, after).unwrap();
+                    this.builder.build_unconditional_branch(next).unwrap();
+
+                    this.builder.position_at_end(next);
+                },
+            );
+            this.push_list(head, tail, part(this, len));
+            Values::Single(
+                this.load_variable(head, this.types.object)
+                    .into_struct_value(),
+            )
+        });
+        // like string-append, but with the separator copied before each string but the first
+        let string_join = self.create_simple_primitive("string-join", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let strings = this.make_car(argl);
+            let (separator_len, separator) =
+                this.string_parts(this.get_string(this.make_cadr(argl)));
+            let total = this.build_variable("total", i32_type.const_zero());
+            let count = this.build_variable("count", i32_type.const_zero());
+            this.build_for_each("string-join:length", strings, |this, s| {
+                let (len, _) = this.string_parts(this.get_string(s));
+                let total_value = this.load_variable(total, i32_type).into_int_value();
+                let total_value = this
+                    .builder
+                    .build_int_add(total_value, len, "total")
+                    .unwrap();
+                this.builder.build_store(total, total_value).unwrap();
+                let count_value = this.load_variable(count, i32_type).into_int_value();
+                let count_value = this
+                    .builder
+                    .build_int_add(count_value, i32_type.const_int(1, false), "count")
+                    .unwrap();
+                this.builder.build_store(count, count_value).unwrap();
+            });
+            let count = this.load_variable(count, i32_type).into_int_value();
+            let has_strings = this
+                .builder
+                .build_int_compare(
+                    IntPredicate::UGT,
+                    count,
+                    i32_type.const_zero(),
+                    "has strings",
+                )
+                .unwrap();
+            let separators = this
+                .builder
+                .build_int_sub(count, i32_type.const_int(1, false), "separators")
+                .unwrap();
+            let separators = this
+                .builder
+                .build_select(has_strings, separators, i32_type.const_zero(), "separators")
+                .unwrap()
+                .into_int_value();
+            let separators_len = this
+                .builder
+                .build_int_mul(separators, separator_len, "separators length")
+                .unwrap();
+            let total = this.load_variable(total, i32_type).into_int_value();
+            let total = this
+                .builder
+                .build_int_add(total, separators_len, "total")
+                .unwrap();
+            let buffer = this
+                .builder
+                .build_array_malloc(i8_type, total, "buffer")
+                .unwrap();
+            let position = this.build_variable("position", i32_type.const_zero());
+            let first = this.build_variable("first", this.context.bool_type().const_all_ones());
+            this.build_for_each("string-join:copy", strings, |this, s| {
+                let (len, data) = this.string_parts(this.unchecked_get_string(s));
+                let first_value = this
+                    .load_variable(first, this.context.bool_type())
+                    .into_int_value();
+                let separator_copied = this
+                    .builder
+                    .build_select(
+                        first_value,
+                        i32_type.const_zero(),
+                        separator_len,
+                        "separator",
+                    )
+                    .unwrap()
+                    .into_int_value();
+                let at = this.load_variable(position, i32_type).into_int_value();
+                this.builder
+                    .build_memcpy(
+                        this.byte_pointer(buffer, at),
+                        1,
+                        separator,
+                        1,
+                        separator_copied,
+                    )
+                    .unwrap();
+                let at = this
+                    .builder
+                    .build_int_add(at, separator_copied, "position")
+                    .unwrap();
+                this.builder
+                    .build_memcpy(this.byte_pointer(buffer, at), 1, data, 1, len)
+                    .unwrap();
+                let at = this.builder.build_int_add(at, len, "position").unwrap();
+                this.builder.build_store(position, at).unwrap();
+                this.builder
+                    .build_store(first, this.context.bool_type().const_zero())
+                    .unwrap();
+            });
+            Values::Single(this.make_string(total, buffer, TypeIndex::string))
+        });
+        vec![
+            ("string-length", string_length),
+            ("string-append", string_append),
+            ("substring", substring),
+            ("string-ref", string_ref),
+            ("string=?", string_equal),
+            ("string<?", string_less),
+            ("string->symbol", string_to_symbol),
+            ("symbol->string", symbol_to_string),
+            ("number->string", number_to_string),
+            ("string->number", string_to_number),
+            ("string-split", string_split),
+            ("string-join", string_join),
+        ]
+    }
+
+    fn hash_object(&self, obj: StructValue<'ctx>) -> IntValue<'ctx> {
+        let hash = self.module.get_function("hash-obj").unwrap();
+        self.builder
+            .build_call(hash, &[obj.into()], "hash object")
+            .unwrap()
+            .try_as_basic_value()
+            .unwrap_left()
+            .into_int_value()
+    }
+
+    // the table behind a hash object, which is changed in place so that each copy of the object
+    // sees the change
+    fn get_hash_table(&self, obj: StructValue<'ctx>) -> PointerValue<'ctx> {
+        self.get_hash(obj);
+        self.builder
+            .build_extract_value(obj, 1, "table")
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    // a pointer to the count (0), the bucket count (1) or the buckets (2) of a table
+    fn hash_field(&self, table: PointerValue<'ctx>, field: u32) -> PointerValue<'ctx> {
+        self.builder
+            .build_struct_gep(self.types.types.hash, table, field, "hash field")
+            .unwrap()
+    }
+
+    fn bucket_pointer(
+        &self,
+        buckets: PointerValue<'ctx>,
+        index: IntValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        unsafe {
+            self.builder
+                .build_in_bounds_gep(self.types.object, buckets, &[index], "bucket")
+        }
+        .unwrap()
+    }
+
+    // the bucket for `key`, out of `bucket_count` buckets
+    fn key_bucket(
+        &self,
+        buckets: PointerValue<'ctx>,
+        bucket_count: IntValue<'ctx>,
+        key: StructValue<'ctx>,
+    ) -> PointerValue<'ctx> {
+        let index = self
+            .builder
+            .build_int_unsigned_rem(self.hash_object(key), bucket_count, "index")
+            .unwrap();
+        self.bucket_pointer(buckets, index)
+    }
+
+    // `count` buckets, each an empty list
+    fn new_buckets(&self, count: IntValue<'ctx>) -> PointerValue<'ctx> {
+        let buckets = self
+            .builder
+            .build_array_malloc(self.types.object, count, "buckets")
+            .unwrap();
+        let count = self
+            .builder
+            .build_int_z_extend(count, self.context.i64_type(), "count")
+            .unwrap();
+        let size = self
+            .builder
+            .build_int_mul(count, self.types.object.size_of().unwrap(), "size")
+            .unwrap();
+        // the empty list is all zeros
+        self.builder
+            .build_memset(buckets, 8, self.context.i8_type().const_zero(), size)
+            .unwrap();
+        buckets
+    }
+
+    // whether there is a (key . value) pair for `key` in `bucket`, and the pair if there is one
+    fn find_entry(
+        &self,
+        bucket: StructValue<'ctx>,
+        key: StructValue<'ctx>,
+    ) -> (IntValue<'ctx>, StructValue<'ctx>) {
+        let bool_type = self.context.bool_type();
+        let rest = self.build_variable("rest", bucket);
+        let found = self.build_variable("found", bool_type.const_zero());
+        let entry = self.build_variable("entry", self.empty());
+        self.build_while(
+            "hash:find",
+            |this| {
+                let rest = this
+                    .load_variable(rest, this.types.object)
+                    .into_struct_value();
+                let found = this.load_variable(found, bool_type).into_int_value();
+                let more = this
+                    .builder
+                    .build_not(this.is_hempty(rest), "not empty")
+                    .unwrap();
+                let not_found = this.builder.build_not(found, "not found").unwrap();
+                this.builder.build_and(more, not_found, "search").unwrap()
+            },
+            |this| {
+                let cell = this
+                    .load_variable(rest, this.types.object)
+                    .into_struct_value();
+                let current = this.make_unchecked_car(cell);
+                let same = this.compare_objects(this.make_unchecked_car(current), key);
+                let same = this
+                    .builder
+                    .build_int_compare(IntPredicate::NE, same, same.get_type().const_zero(), "same")
+                    .unwrap();
+                this.builder.build_store(found, same).unwrap();
+                this.builder.build_store(entry, current).unwrap();
+                this.builder
+                    .build_store(rest, this.make_unchecked_cdr(cell))
+                    .unwrap();
+            },
+        );
+        (
+            self.load_variable(found, bool_type).into_int_value(),
+            self.load_variable(entry, self.types.object)
+                .into_struct_value(),
+        )
+    }
+
+    // doubles the buckets once there are more than two keys per bucket, each (key . value) cell is
+    // moved to its new bucket
+    fn grow_hash_table(&self, table: PointerValue<'ctx>) {
+        let i32_type = self.context.i32_type();
+        let count = self
+            .load_variable(self.hash_field(table, 0), i32_type)
+            .into_int_value();
+        let bucket_count = self
+            .load_variable(self.hash_field(table, 1), i32_type)
+            .into_int_value();
+        let new_count = self
+            .builder
+            .build_int_mul(bucket_count, i32_type.const_int(2, false), "new count")
+            .unwrap();
+        let too_full = self
+            .builder
+            .build_int_compare(IntPredicate::UGT, count, new_count, "too full")
+            .unwrap();
+        let grow = self.context.append_basic_block(self.current, "hash:grow");
+        let done = self.context.append_basic_block(self.current, "hash:grown");
+        self.builder
+            .build_conditional_branch(too_full, grow, done)
+            .unwrap();
+
+        self.builder.position_at_end(grow);
+        let new_buckets = self.new_buckets(new_count);
+        let old_buckets = self
+            .load_variable(self.hash_field(table, 2), self.types.pointer)
+            .into_pointer_value();
+        let rest = self.build_variable("rest", self.empty());
+        self.build_for(
+            "hash:rehash",
+            i32_type.const_zero(),
+            bucket_count,
+            |this, i| {
+                let bucket =
+                    this.load_variable(this.bucket_pointer(old_buckets, i), this.types.object);
+                this.builder.build_store(rest, bucket).unwrap();
+                this.build_while(
+                    "hash:move",
+                    |this| {
+                        let rest = this
+                            .load_variable(rest, this.types.object)
+                            .into_struct_value();
+                        this.builder
+                            .build_not(this.is_hempty(rest), "not empty")
+                            .unwrap()
+                    },
+                    |this| {
+                        let cell = this
+                            .load_variable(rest, this.types.object)
+                            .into_struct_value();
+                        let next = this.make_unchecked_cdr(cell);
+                        let key = this.make_unchecked_car(this.make_unchecked_car(cell));
+                        let slot = this.key_bucket(new_buckets, new_count, key);
+                        let slot_value = this
+                            .load_variable(slot, this.types.object)
+                            .into_struct_value();
+                        this.make_unchecked_set_cdr(cell, slot_value);
+                        this.builder.build_store(slot, cell).unwrap();
+                        this.builder.build_store(rest, next).unwrap();
+                    },
+                );
+            },
+        );
+        self.builder
+            .build_store(self.hash_field(table, 1), new_count)
+            .unwrap();
+        self.builder
+            .build_store(self.hash_field(table, 2), new_buckets)
+            .unwrap();
+        self.builder.build_unconditional_branch(done).unwrap();
+
+        self.builder.position_at_end(done);
+    }
+
+    // the buckets of a table and how many there are
+    fn hash_buckets(&self, table: PointerValue<'ctx>) -> (PointerValue<'ctx>, IntValue<'ctx>) {
+        let buckets = self
+            .load_variable(self.hash_field(table, 2), self.types.pointer)
+            .into_pointer_value();
+        let bucket_count = self
+            .load_variable(self.hash_field(table, 1), self.context.i32_type())
+            .into_int_value();
+        (buckets, bucket_count)
+    }
+
+    fn init_hash_primitives(&mut self) -> Vec<(&'static str, BasicBlock<'ctx>)> {
+        let i32_type = self.context.i32_type();
+        let make_hash = self.create_simple_primitive("make-hash", |this, _| {
+            let bucket_count = i32_type.const_int(8, false);
+            let table = this.types.types.hash.into_struct_type().const_zero();
+            let table = this
+                .builder
+                .build_insert_value(table, bucket_count, 1, "insert bucket count")
+                .unwrap();
+            let table = this
+                .builder
+                .build_insert_value(table, this.new_buckets(bucket_count), 2, "insert buckets")
+                .unwrap();
+            Values::Single(this.make_object(&table.into_struct_value(), TypeIndex::hash))
+        });
+        // (hash-ref table key [default]) without a default it is an error for the key to be missing
+        let hash_ref = self.create_simple_primitive("hash-ref", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let table = this.get_hash_table(this.make_car(argl));
+            let key = this.make_cadr(argl);
+            let rest = this.make_cddr(argl);
+            let (buckets, bucket_count) = this.hash_buckets(table);
+            let bucket = this
+                .load_variable(
+                    this.key_bucket(buckets, bucket_count, key),
+                    this.types.object,
+                )
+                .into_struct_value();
+            let (found, entry) = this.find_entry(bucket, key);
+            let result = this.build_variable("result", this.empty());
+            let block = |end| {
+                this.context
+                    .append_basic_block(this.current, &format!("hash-ref:{end}"))
+            };
+            let found_bb = block("found");
+            let missing_bb = block("missing");
+            let done = block("done");
+            this.builder
+                .build_conditional_branch(found, found_bb, missing_bb)
+                .unwrap();
+
+            this.builder.position_at_end(found_bb);
+            this.builder
+                .build_store(result, this.make_unchecked_cdr(entry))
+                .unwrap();
+            this.builder.build_unconditional_branch(done).unwrap();
+
+            this.builder.position_at_end(missing_bb);
+            let has_default = this
+                .builder
+                .build_not(this.is_hempty(rest), "has default")
+                .unwrap();
+            this.check("hash-ref", has_default, "hash-ref: no value for the key\n");
+            this.builder
+                .build_store(result, this.make_car(rest))
+                .unwrap();
+            this.builder.build_unconditional_branch(done).unwrap();
+
+            this.builder.position_at_end(done);
+            Values::Single(
+                this.load_variable(result, this.types.object)
+                    .into_struct_value(),
+            )
+        });
+        let hash_set = self.create_simple_primitive("hash-set!", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let table = this.get_hash_table(this.make_car(argl));
+            let key = this.make_cadr(argl);
+            let value = this.make_caddr(argl);
+            let (buckets, bucket_count) = this.hash_buckets(table);
+            let slot = this.key_bucket(buckets, bucket_count, key);
+            let bucket = this
+                .load_variable(slot, this.types.object)
+                .into_struct_value();
+            let (found, entry) = this.find_entry(bucket, key);
+            let block = |end| {
+                this.context
+                    .append_basic_block(this.current, &format!("hash-set!:{end}"))
+            };
+            let replace = block("replace");
+            let add = block("add");
+            let done = block("done");
+            this.builder
+                .build_conditional_branch(found, replace, add)
+                .unwrap();
+
+            this.builder.position_at_end(replace);
+            this.make_unchecked_set_cdr(entry, value);
+            this.builder.build_unconditional_branch(done).unwrap();
+
+            this.builder.position_at_end(add);
+            let entry = this.make_cons(key, value);
+            this.builder
+                .build_store(slot, this.make_cons(entry, bucket))
+                .unwrap();
+            let count = this.hash_field(table, 0);
+            let count_value = this.load_variable(count, i32_type).into_int_value();
+            let count_value = this
+                .builder
+                .build_int_add(count_value, i32_type.const_int(1, false), "count")
+                .unwrap();
+            this.builder.build_store(count, count_value).unwrap();
+            this.grow_hash_table(table);
+            this.builder.build_unconditional_branch(done).unwrap();
+
+            this.builder.position_at_end(done);
+            Values::Single(this.empty())
+        });
+        // removing a key that is not in the table does nothing
+        let hash_remove = self.create_simple_primitive("hash-remove!", |this, _| {
+            let bool_type = this.context.bool_type();
+            let argl = this.load_register(Register::Argl);
+            let table = this.get_hash_table(this.make_car(argl));
+            let key = this.make_cadr(argl);
+            let (buckets, bucket_count) = this.hash_buckets(table);
+            let slot = this.key_bucket(buckets, bucket_count, key);
+            let previous = this.build_variable("previous", this.empty());
+            let rest = this.build_variable("rest", this.load_variable(slot, this.types.object));
+            let removed = this.build_variable("removed", bool_type.const_zero());
+            this.build_while(
+                "hash-remove!",
+                |this| {
+                    let rest = this
+                        .load_variable(rest, this.types.object)
+                        .into_struct_value();
+                    let removed = this.load_variable(removed, bool_type).into_int_value();
+                    let more = this
+                        .builder
+                        .build_not(this.is_hempty(rest), "not empty")
+                        .unwrap();
+                    let not_removed = this.builder.build_not(removed, "not removed").unwrap();
+                    this.builder.build_and(more, not_removed, "search").unwrap()
+                },
+                |this| {
+                    let cell = this
+                        .load_variable(rest, this.types.object)
+                        .into_struct_value();
+                    let next = this.make_unchecked_cdr(cell);
+                    let same = this.compare_objects(
+                        this.make_unchecked_car(this.make_unchecked_car(cell)),
+                        key,
+                    );
+                    let same = this
+                        .builder
+                        .build_int_compare(
+                            IntPredicate::NE,
+                            same,
+                            same.get_type().const_zero(),
+                            "same",
+                        )
+                        .unwrap();
+                    let block = |end| {
+                        this.context
+                            .append_basic_block(this.current, &format!("hash-remove!:{end}"))
+                    };
+                    let unlink = block("unlink");
+                    let unlink_first = block("unlink-first");
+                    let unlink_after = block("unlink-after");
+                    let keep = block("keep");
+                    let next_bb = block("next");
+                    this.builder
+                        .build_conditional_branch(same, unlink, keep)
+                        .unwrap();
+
+                    this.builder.position_at_end(unlink);
+                    let previous_value = this
+                        .load_variable(previous, this.types.object)
+                        .into_struct_value();
+                    this.builder
+                        .build_store(removed, bool_type.const_all_ones())
+                        .unwrap();
+                    let count = this.hash_field(table, 0);
+                    let count_value = this.load_variable(count, i32_type).into_int_value();
+                    let count_value = this
+                        .builder
+                        .build_int_sub(count_value, i32_type.const_int(1, false), "count")
+                        .unwrap();
+                    this.builder.build_store(count, count_value).unwrap();
+                    this.builder
+                        .build_conditional_branch(
+                            this.is_hempty(previous_value),
+                            unlink_first,
+                            unlink_after,
+                        )
+                        .unwrap();
+
+                    this.builder.position_at_end(unlink_first);
+                    this.builder.build_store(slot, next).unwrap();
+                    this.builder.build_unconditional_branch(next_bb).unwrap();
+
+                    this.builder.position_at_end(unlink_after);
+                    this.make_unchecked_set_cdr(previous_value, next);
+                    this.builder.build_unconditional_branch(next_bb).unwrap();
+
+                    this.builder.position_at_end(keep);
+                    this.builder.build_store(previous, cell).unwrap();
+                    this.builder.build_unconditional_branch(next_bb).unwrap();
+
+                    this.builder.position_at_end(next_bb);
+                    this.builder.build_store(rest, next).unwrap();
+                },
+            );
+            Values::Single(this.empty())
+        });
+        let hash_keys = self.create_simple_primitive("hash-keys", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let table = this.get_hash_table(this.make_car(argl));
+            let (buckets, bucket_count) = this.hash_buckets(table);
+            let keys = this.build_variable("keys", this.empty());
+            let rest = this.build_variable("rest", this.empty());
+            this.build_for(
+                "hash-keys",
+                i32_type.const_zero(),
+                bucket_count,
+                |this, i| {
+                    let bucket =
+                        this.load_variable(this.bucket_pointer(buckets, i), this.types.object);
+                    this.builder.build_store(rest, bucket).unwrap();
+                    this.build_while(
+                        "hash-keys:bucket",
+                        |this| {
+                            let rest = this
+                                .load_variable(rest, this.types.object)
+                                .into_struct_value();
+                            this.builder
+                                .build_not(this.is_hempty(rest), "not empty")
+                                .unwrap()
+                        },
+                        |this| {
+                            let cell = this
+                                .load_variable(rest, this.types.object)
+                                .into_struct_value();
+                            let key = this.make_unchecked_car(this.make_unchecked_car(cell));
+                            let keys_value = this
+                                .load_variable(keys, this.types.object)
+                                .into_struct_value();
+                            this.builder
+                                .build_store(keys, this.make_cons(key, keys_value))
+                                .unwrap();
+                            this.builder
+                                .build_store(rest, this.make_unchecked_cdr(cell))
+                                .unwrap();
+                        },
+                    );
+                },
+            );
+            Values::Single(
+                this.load_variable(keys, this.types.object)
+                    .into_struct_value(),
+            )
+        });
+        let hash_count = self.create_simple_primitive("hash-count", |this, _| {
+            let argl = this.load_register(Register::Argl);
+            let table = this.get_hash_table(this.make_car(argl));
+            let count = this
+                .load_variable(this.hash_field(table, 0), i32_type)
+                .into_int_value();
+            let count = this
+                .builder
+                .build_unsigned_int_to_float(
+                    count,
+                    this.types.types.number.into_float_type(),
+                    "count",
+                )
+                .unwrap();
+            Values::Single(this.make_object(&count, TypeIndex::number))
+        });
+        vec![
+            ("make-hash", make_hash),
+            ("hash-ref", hash_ref),
+            ("hash-set!", hash_set),
+            ("hash-remove!", hash_remove),
+            ("hash-keys", hash_keys),
+            ("hash-count", hash_count),
+        ]
+    }
+
+    fn compile_instructions(&mut self, instruction: Instruction) {
+        match instruction {
+            Instruction::Assign(r, e) => {
+                let expr = self.compile_expr(e);
+                self.assign_register(r, expr);
+            }
+            Instruction::Test(p) => {
+                self.builder
+                    .build_store(self.flag, self.compile_perform(p))
+                    .unwrap();
+            }
+            Instruction::Branch(l) => {
+                let flag = self
+                    .builder
+                    .build_load(self.types.object, self.flag, "load flag")
+                    .unwrap();
+                // TODO: hack technically (in most cases) if we have a branch, the next instruction after it will be a label so we dont need a new block for each branch but rather we should either "peek" ahead to the next instruction to obtain the label
+                // or encode the label as part of the branch variant
+                let next_label = self.context.append_basic_block(self.current, "next-block");
                 // eprintln!("{:?}", self.labels.keys());
                 self.builder.build_conditional_branch(
                     self.truthy(flag.into_struct_value()),
@@ -1553,6 +4428,15 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                 self.set_error(e, 3);
                 self.builder.build_store(register, expr).unwrap();
             }
+            Instruction::Error(e) => {
+                self.set_error(&e, 1);
+                self.builder
+                    .build_unconditional_branch(self.error_block)
+                    .unwrap();
+                // like after a goto anything after this is in its own block
+                let next_label = self.context.append_basic_block(self.current, "next-block");
+                self.builder.position_at_end(next_label);
+            }
         }
     }
 
@@ -1896,6 +4780,36 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                 let cdr = *args.get(1).unwrap();
                 self.make_cons(car, cdr)
             }
+            Operation::Cdr => self.make_unchecked_cdr(args[0]),
+            Operation::SetMark => {
+                let set_mark = self.module.get_function("set-mark").unwrap();
+                self.builder
+                    .build_call(
+                        set_mark,
+                        &[args[0].into(), args[1].into(), args[2].into()],
+                        "set mark",
+                    )
+                    .unwrap()
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_struct_value()
+            }
+            Operation::ContinuationMarks => {
+                let continuation_marks = self.module.get_function("continuation-marks").unwrap();
+                self.builder
+                    .build_call(continuation_marks, &[args[0].into()], "continuation marks")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .unwrap_left()
+                    .into_struct_value()
+            }
+            Operation::NotEmpty => {
+                let not_empty = self
+                    .builder
+                    .build_not(self.is_hempty(args[0]), "not empty")
+                    .unwrap();
+                self.make_object(&not_empty, TypeIndex::bool)
+            }
             Operation::SetVariableValue => {
                 let var = args[0];
                 let new_val = args[1];
@@ -2307,7 +5221,13 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
                 self.make_object(&number, TypeIndex::number)
             }
             Ast::Boolean(b) => {
-                let boolean = self.context.bool_type().const_int(u64::from(b), false);
+                // not an i1 as `?` is 2
+                let boolean = self
+                    .types
+                    .types
+                    .get(TypeIndex::bool)
+                    .into_int_type()
+                    .const_int(u64::from(b), false);
                 self.make_object(&boolean, TypeIndex::bool)
             }
             Ast::Pair(pair) => {
@@ -2327,6 +5247,10 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
             }
             Ast::Syntax(syntax) => todo!(),
             Ast::Function(function) => unreachable!(),
+            // only made while interpreting
+            Ast::Thunk(_) => unreachable!(),
+            // a hash table in the program is a compile error when it is transformed to `Ast2`
+            Ast::Hash(_) => unreachable!("cannot embed a hash table in compiled code"),
             // maybe unreachable
             Ast::Label(_) => todo!(),
         }
@@ -2340,7 +5264,8 @@ impl<'a, 'ctx> CodeGen<'a, 'ctx> {
     }
 
     fn create_string_part(&self, s: &str) -> StructValue<'ctx> {
-        let strlen = s.chars().count();
+        // the length is in bytes, as that is what printf and strncmp use
+        let strlen = s.len();
         let global_str = self
             .builder
             .build_global_string_ptr(s, s)
@@ -2395,6 +5320,7 @@ fn init_error_handler<'ctx>(
     error: StructType<'ctx>,
     printf: FunctionValue<'ctx>,
     exit: FunctionValue<'ctx>,
+    seed: PointerValue<'ctx>,
 ) -> (BasicBlock<'ctx>, PhiValue<'ctx>) {
     let error_block = context.append_basic_block(function, "error");
     builder.position_at_end(error_block);
@@ -2418,6 +5344,17 @@ fn init_error_handler<'ctx>(
         builder
             .build_call(printf, &[error_msg.into()], "print")
             .unwrap();
+        // so that the failing run can be replayed with EVERYTHING_SEED
+        let seed_format = builder
+            .build_global_string_ptr("\nseed: %u\n", "seed-format")
+            .unwrap()
+            .as_pointer_value();
+        let seed = builder
+            .build_load(context.i32_type(), seed, "seed")
+            .unwrap();
+        builder
+            .build_call(printf, &[seed_format.into(), seed.into()], "print")
+            .unwrap();
         builder
             .build_call(exit, &[error_code.into()], "print")
             .unwrap();
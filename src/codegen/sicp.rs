@@ -1,13 +1,17 @@
 use std::{
     collections::HashSet,
     fmt::{self, Formatter},
+    iter,
     rc::Rc,
 };
 
 use itertools::Itertools;
 use log::info;
 
-use crate::ast::{ast2::Ast2, Ast, Param, Symbol};
+use crate::{
+    ast::{ast2::Ast2, Arity, Ast, Boolean, Param, Symbol, Varidiac},
+    UniqueNumberManager,
+};
 
 type Label = String;
 
@@ -61,6 +65,10 @@ pub enum Register {
     ContinueMulti,
     Values,
     Thunk,
+    // the continuation marks, a list of frames with the innermost frame first, each frame is a
+    // list of (key . value) pairs with the innermost mark first, a call that is not in tail
+    // position has its own frame
+    Marks,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -85,6 +93,13 @@ pub enum Operation {
     NotStop,
     ResetStop,
     SetStop,
+    Cdr,
+    NotEmpty,
+    // the marks with a mark from key to value in the innermost frame, replacing the mark in that
+    // frame with the same key
+    SetMark,
+    // all the marks (from each frame) as one list of (key . value) pairs
+    ContinuationMarks,
     // maybe combine with define variables - rn only place it is used
     SetSingleMultiValueHandler,
     // maybe combine with extendenv
@@ -102,6 +117,7 @@ impl fmt::Display for Register {
             Self::ContinueMulti => write!(f, "continue-multi"),
             Self::Thunk => write!(f, "thunk"),
             Self::Values => write!(f, "values"),
+            Self::Marks => write!(f, "marks"),
         }
     }
 }
@@ -171,7 +187,11 @@ impl fmt::Display for Operation {
         };
         let kebabified = decamel(format!("{self:?}"));
         match self {
-            Self::False | Self::PrimitiveProcedure | Self::NotThunk | Self::NotStop => {
+            Self::False
+            | Self::PrimitiveProcedure
+            | Self::NotThunk
+            | Self::NotStop
+            | Self::NotEmpty => {
                 write!(f, "{kebabified}?")
             }
             _ => write!(f, "{kebabified}"),
@@ -204,6 +224,8 @@ pub enum Instruction {
     Perform(Perform),
     Label(Label),
     AssignError(Register, &'static str),
+    // ends the program with this message
+    Error(String),
 }
 
 impl fmt::Display for Instruction {
@@ -218,6 +240,7 @@ impl fmt::Display for Instruction {
             Self::Restore(r) => write!(f, " (restore {r})",),
             Self::Perform(r) => write!(f, " (perform {r})",),
             Self::Label(l) => write!(f, "{l}",),
+            Self::Error(e) => write!(f, " (error {e})"),
         }
     }
 }
@@ -257,8 +280,26 @@ pub fn compile(exp: Ast2, target: Register, linkage: Linkage) -> InstructionSequ
         Ast2::Begin(b) => compile_seq(b, target, linkage),
         Ast2::DefineValues(s, exp) => compile_defeninition(s, *exp, target, linkage),
         Ast2::Set(s, exp) => compile_assignment((s, *exp), target, linkage),
-        Ast2::Lambda(arg, body) => compile_lambda((arg, *body), target, linkage),
+        Ast2::Lambda(arg, body, name) => compile_lambda((arg, *body, name), target, linkage),
+        Ast2::CaseLambda(clauses) => compile_case_lambda(clauses, target, linkage),
         Ast2::If(cond, cons, alt) => compile_if((*cond, *cons, *alt), target, linkage),
+        Ast2::WithContinuationMark(key, val, body) => {
+            compile_with_continuation_mark((*key, *val, *body), target, linkage)
+        }
+        Ast2::CurrentContinuationMarks => end_with_linkage(
+            linkage,
+            make_intsruction_sequnce(
+                hashset!(Register::Marks),
+                hashset!(target),
+                vec![Instruction::Assign(
+                    target,
+                    Expr::Op(Perform {
+                        op: Operation::ContinuationMarks,
+                        args: vec![Expr::Register(Register::Marks)],
+                    }),
+                )],
+            ),
+        ),
         Ast2::Quote(q) => compile_quoted(q, target, linkage),
         Ast2::Basic(Ast::Label(l)) => {
             end_with_linkage(linkage, make_label_instruction(l.to_string()))
@@ -303,7 +344,10 @@ pub fn compile(exp: Ast2, target: Register, linkage: Linkage) -> InstructionSequ
                         )
                     },
                 ),
-                compile_linkage(Linkage::Return),
+                append_instruction_sequnce(
+                    remove_marks(mark_depth()),
+                    compile_linkage(Linkage::Return),
+                ),
             ),
         ),
         Ast2::Loop(loop_function) => compile_loop(loop_function, target, linkage),
@@ -314,7 +358,19 @@ pub fn compile(exp: Ast2, target: Register, linkage: Linkage) -> InstructionSequ
 
         Ast2::Expression(ast2) => compile(*ast2, target, linkage),
         Ast2::Begin0(ast2s) => compile_seq0(ast2s, target, linkage),
-        Ast2::Skip => todo!(),
+        // skip returns from the function the loop calls (without setting stop), so the loop just
+        // goes on to the next iteration
+        Ast2::Skip => append_instruction_sequnce(
+            make_intsruction_sequnce(
+                hashset!(),
+                hashset!(target),
+                vec![Instruction::Assign(target, Expr::Const(Ast::TheEmptyList))],
+            ),
+            append_instruction_sequnce(
+                remove_marks(mark_depth()),
+                compile_linkage(Linkage::Return),
+            ),
+        ),
     }
 }
 
@@ -363,6 +419,14 @@ fn compile_let(
     body: Box<Ast2>,
     target: Register,
     linkage: Linkage,
+) -> InstructionSequnce {
+    compile_let_body(variables, compile(*body, target, linkage))
+}
+
+// binds the variables in a new envoirnment, and runs the already compiled body in it
+fn compile_let_body(
+    variables: Vec<(Vec<Rc<str>>, Ast2)>,
+    body: InstructionSequnce,
 ) -> InstructionSequnce {
     // make new envoirment (save it to proc, because let doesn't remember values from env until
     // body).
@@ -400,7 +464,7 @@ fn compile_let(
         variables,
         // eval body in new env
         // go back to original env
-        append_instruction_sequnce(set_env, compile(*body, target, linkage)),
+        append_instruction_sequnce(set_env, body),
     )
 }
 
@@ -616,6 +680,64 @@ fn make_label_name(label: String) -> Label {
     format!("{}{}", label, label_counter())
 }
 
+// how many frames of marks the code being compiled is in (since the start of the function it is
+// in), one for each with-continuation-mark body that is not in tail position, a stop or skip leaves
+// all of them at once, so it has to remove that many frames
+static mut MARK_DEPTH: usize = 0;
+
+fn mark_depth() -> usize {
+    unsafe { MARK_DEPTH }
+}
+
+fn with_mark_depth<T>(depth: usize, f: impl FnOnce() -> T) -> T {
+    let outer = mark_depth();
+    unsafe { MARK_DEPTH = depth };
+    let res = f();
+    unsafe { MARK_DEPTH = outer };
+    res
+}
+
+// starts a new innermost frame of marks
+fn push_mark_frame() -> InstructionSequnce {
+    make_intsruction_sequnce(
+        hashset!(Register::Marks),
+        hashset!(Register::Marks),
+        vec![Instruction::Assign(
+            Register::Marks,
+            Expr::Op(Perform {
+                op: Operation::Cons,
+                args: vec![
+                    Expr::Const(Ast::TheEmptyList),
+                    Expr::Register(Register::Marks),
+                ],
+            }),
+        )],
+    )
+}
+
+// removes the innermost `count` frames of marks, the marks are not saved on the stack, as anything
+// that leaves a with-continuation-mark body early would leave them there
+fn remove_marks(count: usize) -> InstructionSequnce {
+    if count == 0 {
+        return empty_instruction_seqeunce();
+    }
+    make_intsruction_sequnce(
+        hashset!(Register::Marks),
+        hashset!(Register::Marks),
+        iter::repeat_with(|| {
+            Instruction::Assign(
+                Register::Marks,
+                Expr::Op(Perform {
+                    op: Operation::Cdr,
+                    args: vec![Expr::Register(Register::Marks)],
+                }),
+            )
+        })
+        .take(count)
+        .collect(),
+    )
+}
+
 fn compile_variable(exp: Symbol, target: Register, linkage: Linkage) -> InstructionSequnce {
     info!("generating ir for looking up variable {exp:?}, with register {target}, with linkage {linkage:?}");
     end_with_linkage(
@@ -705,7 +827,14 @@ fn compile_defeninition_let(
                 ],
             ),
             // TODO: might need to presereve single or multi continue register
-            append_instruction_sequnce(val, make_label_instruction(label)),
+            // the value is returned like from a call, so it has its own frame of marks
+            append_instruction_sequnce(
+                push_mark_frame(),
+                append_instruction_sequnce(
+                    val,
+                    append_instruction_sequnce(make_label_instruction(label), remove_marks(1)),
+                ),
+            ),
         ),
         InstructionSequnce::new(
             hashset![
@@ -752,13 +881,21 @@ fn compile_defeninition(
                         args: vec![Expr::Label(label.clone())],
                     })],
                 ),
-                val,
+                // the value is returned like from a call, so it has its own frame of marks
+                append_instruction_sequnce(push_mark_frame(), val),
             ),
             InstructionSequnce::new(
-                hashset![Register::Env, Register::Val],
-                hashset![target],
+                hashset![Register::Env, Register::Val, Register::Marks],
+                hashset![target, Register::Marks],
                 vec![
                     Instruction::Label(label),
+                    Instruction::Assign(
+                        Register::Marks,
+                        Expr::Op(Perform {
+                            op: Operation::Cdr,
+                            args: vec![Expr::Register(Register::Marks)],
+                        }),
+                    ),
                     Instruction::Perform(Perform {
                         op: Operation::DefineVariable(variables),
                         args: vec![Expr::Register(Register::Val), Expr::Register(Register::Env)],
@@ -772,6 +909,22 @@ fn compile_defeninition(
 
 fn compile_if(exp: (Ast2, Ast2, Ast2), target: Register, linkage: Linkage) -> InstructionSequnce {
     info!("generating ir for if expresion (condition) {:?} (consequent) {:?} (alternative) {:?}, with register {target}, with linkage {linkage:?}", exp.0, exp.1, exp.2);
+    compile_conditional(
+        exp.0,
+        |linkage| compile(exp.1, target, linkage),
+        |linkage| compile(exp.2, target, linkage),
+        linkage,
+    )
+}
+
+// an if where the branches are already compiled (given the linkage they should use), so that they
+// do not have to come from an expression
+fn compile_conditional(
+    predicate: Ast2,
+    consequent: impl FnOnce(Linkage) -> InstructionSequnce,
+    alternative: impl FnOnce(Linkage) -> InstructionSequnce,
+    linkage: Linkage,
+) -> InstructionSequnce {
     let t_branch = make_label_name("true-branch".to_string());
     let f_branch = make_label_name("false-branch".to_string());
     let after_if = make_label_name("after-if".to_string());
@@ -786,7 +939,7 @@ fn compile_if(exp: (Ast2, Ast2, Ast2), target: Register, linkage: Linkage) -> In
 
     #[cfg(feature = "lazy")]
     let p_code = force_it(
-        exp.0,
+        predicate,
         Register::Val,
         Linkage::Next {
             expect_single: true,
@@ -794,15 +947,16 @@ fn compile_if(exp: (Ast2, Ast2, Ast2), target: Register, linkage: Linkage) -> In
     );
     #[cfg(not(feature = "lazy"))]
     let p_code = compile(
-        exp.0,
+        predicate,
         Register::Val,
         Linkage::Next {
             expect_single: true,
         },
     );
 
-    let c_code = compile(exp.1, target, linkage);
-    let a_code = compile(exp.2, target, consequent_linkage);
+    // the consequent comes first so it has to jump over the alternative
+    let c_code = consequent(consequent_linkage);
+    let a_code = alternative(linkage);
 
     preserving(
         hashset!(Register::Env, Register::Continue, Register::ContinueMulti),
@@ -829,6 +983,107 @@ fn compile_if(exp: (Ast2, Ast2, Ast2), target: Register, linkage: Linkage) -> In
         ),
     )
 }
+// a mark is set in the innermost frame of marks, replacing the mark there with the same key, in tail
+// position (of a function) that is the frame of the function (which a tail call keeps), so the body
+// is in tail position as well, anywhere else the mark (along with the marks directly in its body)
+// gets its own frame, which is removed once the body is done
+fn compile_with_continuation_mark(
+    exp: (Ast2, Ast2, Ast2),
+    target: Register,
+    linkage: Linkage,
+) -> InstructionSequnce {
+    info!("generating ir for with-continuation-mark (key) {:?} (value) {:?} (body) {:?}, with register {target}, with linkage {linkage:?}", exp.0, exp.1, exp.2);
+    let (key, val, mut body) = exp;
+    let expect_single = match linkage {
+        Linkage::Next { expect_single } | Linkage::Label { expect_single, .. } => expect_single,
+        Linkage::Return => {
+            return preserving(
+                hashset!(Register::Env, Register::Continue, Register::ContinueMulti),
+                compile_set_mark(key, val),
+                compile(body, target, linkage),
+            );
+        }
+    };
+    let mut set_marks = preserving(
+        hashset!(Register::Env),
+        push_mark_frame(),
+        compile_set_mark(key, val),
+    );
+    while let Ast2::WithContinuationMark(key, val, inner) = body {
+        set_marks = preserving(
+            hashset!(Register::Env),
+            set_marks,
+            compile_set_mark(*key, *val),
+        );
+        body = *inner;
+    }
+    let body_code = with_mark_depth(mark_depth() + 1, || {
+        compile(body, target, Linkage::Next { expect_single })
+    });
+    // the body leaves the marks as they were after its frame was added (a stop or skip in it
+    // removes the frames it is in itself), so removing the innermost frame gives back the old marks
+    end_with_linkage(
+        linkage,
+        append_instruction_sequnce(
+            preserving(
+                hashset!(Register::Env, Register::Continue, Register::ContinueMulti),
+                set_marks,
+                body_code,
+            ),
+            remove_marks(1),
+        ),
+    )
+}
+
+fn compile_set_mark(key: Ast2, val: Ast2) -> InstructionSequnce {
+    let key_code = compile(
+        key,
+        Register::Val,
+        Linkage::Next {
+            expect_single: true,
+        },
+    );
+    let val_code = compile(
+        val,
+        Register::Val,
+        Linkage::Next {
+            expect_single: true,
+        },
+    );
+    let key_code = preserving(
+        hashset!(Register::Env),
+        key_code,
+        make_intsruction_sequnce(
+            hashset!(Register::Val),
+            hashset!(Register::Argl),
+            vec![Instruction::Assign(
+                Register::Argl,
+                Expr::Register(Register::Val),
+            )],
+        ),
+    );
+    let val_code = preserving(
+        hashset!(Register::Argl),
+        val_code,
+        make_intsruction_sequnce(
+            hashset!(Register::Val, Register::Argl, Register::Marks),
+            hashset!(Register::Marks),
+            vec![Instruction::Assign(
+                Register::Marks,
+                Expr::Op(Perform {
+                    op: Operation::SetMark,
+                    args: vec![
+                        Expr::Register(Register::Argl),
+                        Expr::Register(Register::Val),
+                        Expr::Register(Register::Marks),
+                    ],
+                }),
+            )],
+        ),
+    );
+    preserving(hashset!(Register::Env), key_code, val_code)
+}
+
 fn compile_seq0(mut seq: Vec<Ast2>, target: Register, linkage: Linkage) -> InstructionSequnce {
     info!(
         "generating ir for begin0 with expressions {:?}, with register {target}, with linkage {linkage:?}",
@@ -925,11 +1180,28 @@ fn tack_on_instruction_seq(
     )
 }
 
-fn compile_lambda(lambda: (Param, Ast2), target: Register, linkage: Linkage) -> InstructionSequnce {
+fn compile_lambda(
+    lambda: (Param, Ast2, Option<Symbol>),
+    target: Register,
+    linkage: Linkage,
+) -> InstructionSequnce {
     info!(
         "generating ir for lambda with parameter {} and body {:?}, with register {target}, with linkage {linkage:?}",
         lambda.0, lambda.1
     );
+    let body = with_mark_depth(0, || compile(lambda.1, Register::Val, Linkage::Return));
+    compile_procedure(lambda.0, body, lambda.2, target, linkage)
+}
+
+// makes a procedure whose body is already compiled (and returns), its name (from the inferred-name
+// syntax property) is in its runtime errors
+fn compile_procedure(
+    param: Param,
+    body: InstructionSequnce,
+    name: Option<Symbol>,
+    target: Register,
+    linkage: Linkage,
+) -> InstructionSequnce {
     let proc_entry = make_label_name("entry".to_string());
     let after_lambda = make_label_name("after-lambda".to_string());
     let lambda_linkage = if let Linkage::Next { expect_single } = linkage {
@@ -957,23 +1229,63 @@ fn compile_lambda(lambda: (Param, Ast2), target: Register, linkage: Linkage) ->
                                 // TODO: update llvm generation code to use symbols as opposed to
                                 // numbers
                                 Expr::Const(Ast::Number(
-                                    <&Param as Into<usize>>::into(&lambda.0) as f64
+                                    <&Param as Into<usize>>::into(&param) as f64
                                 )),
                             ],
                         }),
                     )],
                 ),
             ),
-            compile_lambda_body(lambda, proc_entry),
+            compile_lambda_body(param, body, name, proc_entry),
         ),
         make_label_instruction(after_lambda),
     )
 }
 
-fn compile_lambda_body(lambda: (Param, Ast2), proc_entry: String) -> InstructionSequnce {
+fn compile_lambda_body(
+    param: Param,
+    body: InstructionSequnce,
+    name: Option<Symbol>,
+    proc_entry: String,
+) -> InstructionSequnce {
+    // a function with no parameters is only called with no arguments by loop, any other call
+    // gives it an argument
+    let body = if param == Param::Zero {
+        let arity_error = make_label_name("arity-error".to_string());
+        let message = "empty lambda must be applied to no arguements";
+        append_instruction_sequnce(
+            make_intsruction_sequnce(
+                hashset!(Register::Argl),
+                hashset!(),
+                vec![
+                    Instruction::Test(Perform {
+                        op: Operation::NotEmpty,
+                        args: vec![Expr::Register(Register::Argl)],
+                    }),
+                    Instruction::Branch(arity_error.clone()),
+                ],
+            ),
+            tack_on_instruction_seq(
+                body,
+                make_intsruction_sequnce(
+                    hashset!(),
+                    hashset!(),
+                    vec![
+                        Instruction::Label(arity_error),
+                        Instruction::Error(name.map_or_else(
+                            || message.to_string(),
+                            |name| format!("{name}: {message}"),
+                        )),
+                    ],
+                ),
+            ),
+        )
+    } else {
+        body
+    };
     // TODO: do aritty checks by either going through argl and getting the length, having a register that contains the length of the arguments, or combine the 2 together and argl could be a pair of the length of the arguements and the arguements
     append_instruction_sequnce(
-        if let Param::One(i) | Param::AtLeast0(i) | Param::AtLeast1(i) = lambda.0 {
+        if let Param::One(i) | Param::AtLeast0(i) | Param::AtLeast1(i) = param {
             InstructionSequnce::new(
                 hashset!(Register::Env, Register::Proc, Register::Argl),
                 hashset!(Register::Env),
@@ -1022,9 +1334,189 @@ fn compile_lambda_body(lambda: (Param, Ast2), proc_entry: String) -> Instruction
                 ],
             )
         },
-        compile(lambda.1, Register::Val, Linkage::Return),
+        body,
     )
 }
+
+fn primitive_application(primitive: &str, args: Vec<Ast2>) -> Ast2 {
+    Ast2::Application(Box::new(Ast2::Basic(Ast::Symbol(primitive.into()))), args)
+}
+
+fn nth_cdr(n: usize, list: Ast2) -> Ast2 {
+    (0..n).fold(list, |list, _| primitive_application("cdr", vec![list]))
+}
+
+// whether list has at least n elements (or exactly n elements if exact is set)
+fn list_length_test(n: usize, exact: bool, list: Ast2) -> Ast2 {
+    if n == 0 {
+        if exact {
+            primitive_application("null?", vec![list])
+        } else {
+            Ast2::Basic(Ast::Boolean(Boolean::True))
+        }
+    } else {
+        Ast2::If(
+            Box::new(primitive_application("pair?", vec![list.clone()])),
+            Box::new(list_length_test(
+                n - 1,
+                exact,
+                primitive_application("cdr", vec![list]),
+            )),
+            Box::new(Ast2::Basic(Ast::Boolean(Boolean::False))),
+        )
+    }
+}
+
+// the dispatcher gives a variadic clause its extra arguments as one list, so the clause's
+// variadic parameter becomes a regular parameter
+fn variadic_as_list(clause: Ast2) -> Ast2 {
+    match clause {
+        Ast2::Lambda(Param::AtLeast0(i) | Param::AtLeast1(i), body, name) => {
+            Ast2::Lambda(Param::One(i), body, name)
+        }
+        Ast2::Lambda(param, body, name) => {
+            Ast2::Lambda(param, Box::new(variadic_as_list(*body)), name)
+        }
+        clause => clause,
+    }
+}
+
+// a variadic clause takes its extra arguments as one list (see `variadic_as_list`), so when it is
+// given only some of its arguments it is wrapped in a function that takes the rest of them the way
+// the clause would have
+fn curry_variadic(clause: Ast2, arity: Arity, given: Vec<Ast2>) -> Ast2 {
+    let variable = |name: &Rc<str>| Ast2::Basic(Ast::Symbol(Symbol(name.clone())));
+    let params = (given.len()..arity.0)
+        .map(|_| UniqueNumberManager::gen_sym("case-lambda-argument").0)
+        .collect_vec();
+    let rest = UniqueNumberManager::gen_sym("case-lambda-rest").0;
+    let args = given
+        .into_iter()
+        .chain(params.iter().map(variable))
+        .chain(iter::once(variable(&rest)))
+        .collect();
+    let rest = match arity.1 {
+        Some(Varidiac::AtLeast1) => Param::AtLeast1(rest),
+        _ => Param::AtLeast0(rest),
+    };
+    let body = Ast2::Lambda(
+        rest,
+        Box::new(Ast2::Application(Box::new(clause), args)),
+        None,
+    );
+    params.into_iter().rfold(body, |body, param| {
+        Ast2::Lambda(Param::One(param), Box::new(body), None)
+    })
+}
+
+// a case-lambda becomes a variadic procedure (so it gets all of the arguments of an application
+// at once), that picks a clause based on how many arguments there are (see `Arity::select`), and
+// applies it to the arguments
+// as the number of arguments is only known at runtime, there is a branch for every clause, and a
+// branch for every number of arguments that has to be curried into a clause
+// each clause is only compiled once, it is bound (when the case-lambda is made) to a variable that
+// the branches apply, the variables are gensymed (like the expander's locals) so they cannot clash
+// with or capture any other variable
+fn compile_case_lambda(
+    clauses: Vec<(Arity, Ast2)>,
+    target: Register,
+    linkage: Linkage,
+) -> InstructionSequnce {
+    info!("generating ir for case-lambda with clauses {clauses:?}, with register {target}, with linkage {linkage:?}");
+    let variable = |name: &Rc<str>| Ast2::Basic(Ast::Symbol(Symbol(name.clone())));
+    let arguments_name = UniqueNumberManager::gen_sym("case-lambda-arguments").0;
+    let arguments = || variable(&arguments_name);
+    let names = clauses
+        .iter()
+        .map(|_| UniqueNumberManager::gen_sym("case-lambda-clause").0)
+        .collect_vec();
+    let arities = clauses
+        .iter()
+        .enumerate()
+        .map(|(i, (arity, _))| (*arity, i))
+        .collect_vec();
+    let nth_argument = |i| primitive_application("car", vec![nth_cdr(i, arguments())]);
+    let accepting = arities.iter().map(|(arity, i)| {
+        let test = list_length_test(arity.min(), arity.1.is_none(), arguments());
+        let args = if arity.1.is_some() {
+            (0..arity.0)
+                .map(&nth_argument)
+                .chain(iter::once(nth_cdr(arity.0, arguments())))
+                .collect()
+        } else {
+            (0..arity.0).map(&nth_argument).collect()
+        };
+        (
+            test,
+            Ast2::Application(Box::new(variable(&names[*i])), args),
+        )
+    });
+    let most_needed = arities
+        .iter()
+        .map(|(arity, _)| arity.min())
+        .max()
+        .unwrap_or(0);
+    let currying = (0..most_needed)
+        .filter(|argc| !arities.iter().any(|(arity, _)| arity.accepts(*argc)))
+        .filter_map(|argc| {
+            let (arity, i) = Arity::select(&arities, argc)?;
+            let clause = variable(&names[*i]);
+            let given = (0..argc).map(&nth_argument).collect_vec();
+            let curried = if arity.1.is_some() {
+                curry_variadic(clause, *arity, given)
+            } else if argc == 0 {
+                // with no arguments there is nothing to curry so we just give back the clause
+                clause
+            } else {
+                Ast2::Application(Box::new(clause), given)
+            };
+            Some((list_length_test(argc, true, arguments()), curried))
+        });
+    let branches = accepting.chain(currying).collect_vec();
+    let body = with_mark_depth(0, || {
+        compile_case_lambda_branches(branches.into_iter(), Linkage::Return)
+    });
+    let dispatcher = compile_procedure(
+        Param::AtLeast0(arguments_name.clone()),
+        body,
+        None,
+        target,
+        linkage,
+    );
+    compile_let_body(
+        names
+            .into_iter()
+            .zip(clauses)
+            .map(|(name, (_, clause))| (vec![name], variadic_as_list(clause)))
+            .collect(),
+        dispatcher,
+    )
+}
+
+fn compile_case_lambda_branches(
+    mut branches: impl Iterator<Item = (Ast2, Ast2)>,
+    linkage: Linkage,
+) -> InstructionSequnce {
+    match branches.next() {
+        Some((test, application)) => compile_conditional(
+            test,
+            |linkage| compile(application, Register::Val, linkage),
+            |linkage| compile_case_lambda_branches(branches, linkage),
+            linkage,
+        ),
+        None => make_intsruction_sequnce(
+            hashset!(),
+            hashset!(Register::Val),
+            vec![
+                Instruction::AssignError(
+                    Register::Val,
+                    "case-lambda: no clause accepts this many arguments",
+                ),
+                Instruction::Goto(Goto::Register(Register::Val)),
+            ],
+        ),
+    }
+}
 // map with self is used compiling applications:
 // because when we compiling applications we do not know if we have to fully apply
 // or not if we have to fully apply then we want whatever is left (the self part) (this is for
@@ -1641,65 +2133,65 @@ fn compile_proc_appl_end<T: Application>(
             ),
             application_code,
         ),
-        Linkage::Label {
-            place: l,
-            expect_single,
-        } if target == T::return_register() => append_instruction_sequnce(
-            make_intsruction_sequnce(
-                hashset!(),
-                hashset!(Register::ContinueMulti, Register::Continue),
-                vec![
-                    Instruction::Assign(Register::Continue, Expr::Label(l.clone())),
-                    if expect_single {
-                        Instruction::AssignError(
-                            Register::ContinueMulti,
-                            "not expecting multiple values",
-                        )
-                    } else {
-                        Instruction::Assign(Register::ContinueMulti, Expr::Label(l))
-                    },
-                ],
-            ),
-            application_code,
-        ),
         Linkage::Return => panic!(
             "return linkage, target not {} -- COMPILE {target}",
             T::return_register()
         ),
+        // a call that is not in tail position has its own frame of marks, which is removed once it
+        // returns
         Linkage::Label {
             place: l,
             expect_single,
         } => {
             let proc_return = make_label_name(format!("{}-return", T::name()));
+            let mut returned = vec![
+                Instruction::Label(proc_return.clone()),
+                Instruction::Assign(
+                    Register::Marks,
+                    Expr::Op(Perform {
+                        op: Operation::Cdr,
+                        args: vec![Expr::Register(Register::Marks)],
+                    }),
+                ),
+            ];
+            if target != T::return_register() {
+                returned.push(Instruction::Assign(
+                    target,
+                    Expr::Register(T::return_register()),
+                ));
+            }
+            returned.push(Instruction::Goto(Goto::Label(l)));
             append_instruction_sequnce(
-                make_intsruction_sequnce(
-                    hashset!(),
-                    hashset!(Register::ContinueMulti, Register::Continue),
-                    vec![
-                        Instruction::Assign(Register::Continue, Expr::Label(proc_return.clone())),
-                        if expect_single {
-                            Instruction::AssignError(
-                                Register::ContinueMulti,
-                                "not expecting multiple values",
-                            )
-                        } else {
+                append_instruction_sequnce(
+                    make_intsruction_sequnce(
+                        hashset!(),
+                        hashset!(Register::ContinueMulti, Register::Continue),
+                        vec![
                             Instruction::Assign(
-                                Register::ContinueMulti,
+                                Register::Continue,
                                 Expr::Label(proc_return.clone()),
-                            )
-                        },
-                    ],
+                            ),
+                            if expect_single {
+                                Instruction::AssignError(
+                                    Register::ContinueMulti,
+                                    "not expecting multiple values",
+                                )
+                            } else {
+                                Instruction::Assign(
+                                    Register::ContinueMulti,
+                                    Expr::Label(proc_return),
+                                )
+                            },
+                        ],
+                    ),
+                    push_mark_frame(),
                 ),
                 append_instruction_sequnce(
                     application_code,
                     make_intsruction_sequnce(
-                        hashset!(),
-                        hashset!(target),
-                        vec![
-                            Instruction::Label(proc_return),
-                            Instruction::Assign(target, Expr::Register(T::return_register())),
-                            Instruction::Goto(Goto::Label(l)),
-                        ],
+                        hashset!(Register::Marks),
+                        hashset!(target, Register::Marks),
+                        returned,
                     ),
                 ),
             )
@@ -1884,7 +2376,7 @@ fn compile_thunk_body(thunk: Ast2, thunk_entry: Label) -> InstructionSequnce {
                 ),
             ],
         ),
-        compile(thunk, Register::Thunk, Linkage::Return),
+        with_mark_depth(0, || compile(thunk, Register::Thunk, Linkage::Return)),
     )
 }
 #[cfg(not(feature = "lazy"))]
@@ -1921,3 +2413,205 @@ impl From<Ast> for Expr {
         Self::Const(value)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ast::{ast1::Ast1, AstTransformFrom, HashTable, Pair},
+        multimap::MultiMap,
+    };
+
+    #[test]
+    fn sicp_test_hash_table_is_not_embedded() {
+        // a macro can put a table it made into the program, which has nothing to be compiled to
+        for program in [
+            Ast1::Basic(Ast::Hash(HashTable::default())),
+            Ast1::Quote(Ast::Pair(Box::new(Pair(
+                Ast::Hash(HashTable::default()),
+                Ast::TheEmptyList,
+            )))),
+        ] {
+            assert!(Ast2::transform(program, MultiMap::new())
+                .is_err_and(|e| e == "cannot embed a hash table in compiled code"));
+        }
+    }
+
+    #[test]
+    fn sicp_test_stop_out_of_continuation_mark() {
+        // (lambda () (begin (with-continuation-mark 1 2 (stop 3)) 4)), the mark is not in tail
+        // position so it has its own frame
+        let body = Ast2::Begin(vec![
+            Ast2::WithContinuationMark(
+                Box::new(Ast2::Basic(Ast::Number(1.))),
+                Box::new(Ast2::Basic(Ast::Number(2.))),
+                Box::new(Ast2::Stop(Some(Box::new(Ast2::Basic(Ast::Number(3.)))))),
+            ),
+            Ast2::Basic(Ast::Number(4.)),
+        ]);
+        let code = compile(
+            Ast2::Lambda(Param::Zero, Box::new(body), None),
+            Register::Val,
+            Linkage::Next {
+                expect_single: true,
+            },
+        );
+        let instructions = code.instructions();
+        // the old marks are not on the stack, where the stop would leave them
+        assert!(!instructions.iter().any(|i| matches!(
+            i,
+            Instruction::Save(Register::Marks) | Instruction::Restore(Register::Marks)
+        )));
+        // the stop removes the frame of the mark before it returns
+        let stop = instructions
+            .iter()
+            .position(|i| {
+                matches!(
+                    i,
+                    Instruction::Perform(Perform {
+                        op: Operation::SetStop,
+                        ..
+                    })
+                )
+            })
+            .unwrap();
+        let stop = &instructions[stop..];
+        let ret = stop
+            .iter()
+            .position(|i| matches!(i, Instruction::Goto(Goto::Register(Register::Continue))))
+            .unwrap();
+        let removed = stop[..ret]
+            .iter()
+            .filter(|i| {
+                matches!(
+                    i,
+                    Instruction::Assign(
+                        Register::Marks,
+                        Expr::Op(Perform {
+                            op: Operation::Cdr,
+                            ..
+                        })
+                    )
+                )
+            })
+            .count();
+        assert_eq!(removed, 1);
+    }
+
+    #[test]
+    fn sicp_test_named_lambda_error() {
+        // a function is named in its runtime errors
+        let code = compile(
+            Ast2::Lambda(
+                Param::Zero,
+                Box::new(Ast2::Basic(Ast::Number(1.))),
+                Some(Symbol("f".into())),
+            ),
+            Register::Val,
+            Linkage::Next {
+                expect_single: true,
+            },
+        );
+        assert!(code.instructions().iter().any(|i| matches!(
+            i,
+            Instruction::Error(e) if e == "f: empty lambda must be applied to no arguements"
+        )));
+    }
+
+    #[test]
+    fn sicp_test_case_lambda_clauses_compiled_once() {
+        let labeled = |label: &str, body: Ast2| {
+            Ast2::Begin(vec![Ast2::Basic(Ast::Label(label.into())), body])
+        };
+        let one = |param: &str, body| Ast2::Lambda(Param::One(param.into()), Box::new(body), None);
+        // (case-lambda ((1) @a x) ((3 *) @b r)), 0 arguments are curried into the first clause and
+        // 2 into the second
+        let first = one("x", labeled("a", Ast2::Basic(Ast::Symbol("x".into()))));
+        let second = one(
+            "y",
+            one(
+                "z",
+                one(
+                    "w",
+                    Ast2::Lambda(
+                        Param::AtLeast0("r".into()),
+                        Box::new(labeled("b", Ast2::Basic(Ast::Symbol("r".into())))),
+                        None,
+                    ),
+                ),
+            ),
+        );
+        let code = compile(
+            Ast2::CaseLambda(vec![
+                (Arity(1, None), first),
+                (Arity(3, Some(Varidiac::AtLeast0)), second),
+            ]),
+            Register::Val,
+            Linkage::Next {
+                expect_single: true,
+            },
+        );
+        let instructions = code.instructions();
+        for label in ["a", "b"] {
+            let placed = instructions
+                .iter()
+                .filter(|i| matches!(i, Instruction::Label(l) if l == label))
+                .count();
+            assert_eq!(placed, 1, "@{label} is placed {placed} times");
+        }
+        // the dispatcher's own variables cannot be named by the program
+        assert!(!instructions.iter().any(|i| matches!(
+            i,
+            Instruction::Assign(_, Expr::Op(Perform { args, .. }))
+                if args.contains(&Expr::Const(Ast::Symbol("case-lambda-arguments".into())))
+        )));
+    }
+
+    #[test]
+    fn sicp_test_continuation_mark_body_in_tail_position() {
+        // (lambda () (with-continuation-mark 1 2 (f 3)))
+        let body = Ast2::WithContinuationMark(
+            Box::new(Ast2::Basic(Ast::Number(1.))),
+            Box::new(Ast2::Basic(Ast::Number(2.))),
+            Box::new(Ast2::Application(
+                Box::new(Ast2::Basic(Ast::Symbol("f".into()))),
+                vec![Ast2::Basic(Ast::Number(3.))],
+            )),
+        );
+        let code = compile(
+            Ast2::Lambda(Param::Zero, Box::new(body), None),
+            Register::Val,
+            Linkage::Next {
+                expect_single: true,
+            },
+        );
+        let instructions = code.instructions();
+        let set_mark = instructions
+            .iter()
+            .position(|i| {
+                matches!(
+                    i,
+                    Instruction::Assign(
+                        Register::Marks,
+                        Expr::Op(Perform {
+                            op: Operation::SetMark,
+                            ..
+                        })
+                    )
+                )
+            })
+            .unwrap();
+        // the mark is set in the frame of the function, and the call to f is a tail call, so
+        // nothing is left to do after it (like removing the mark or the frame of the call)
+        assert!(!instructions[set_mark..].iter().any(|i| matches!(
+            i,
+            Instruction::Assign(
+                Register::Marks,
+                Expr::Op(Perform {
+                    op: Operation::Cons | Operation::Cdr,
+                    ..
+                })
+            ) | Instruction::Assign(Register::Continue, _)
+        )));
+    }
+}
@@ -1,4 +1,10 @@
-use crate::ast::{syntax::Syntax, Ast, Symbol};
+use std::fmt;
+
+use crate::{
+    ast::{ast1::Label, syntax::Syntax, Ast, Symbol},
+    evaluator::{EnvRef, Values},
+    expander::label::LabelDiagnostic,
+};
 
 #[derive(Debug)]
 pub enum Error {
@@ -13,6 +19,41 @@ pub enum Error {
     BindInEmptyScopeSet(BindInEmptyScopeSet),
     IllegalUseOfSyntax(IllegalUseOfSyntax),
     BeginNonExpression(BeginNonExpression),
+    ImportConflict(ImportConflict),
+    SkipOutsideLoop(SkipOutsideLoop),
+    EarlyReturn(EarlyReturn),
+    Goto(Goto),
+    InvalidLabels(InvalidLabels),
+}
+
+impl From<Goto> for Error {
+    fn from(v: Goto) -> Self {
+        Self::Goto(v)
+    }
+}
+
+impl From<InvalidLabels> for Error {
+    fn from(v: InvalidLabels) -> Self {
+        Self::InvalidLabels(v)
+    }
+}
+
+impl From<EarlyReturn> for Error {
+    fn from(v: EarlyReturn) -> Self {
+        Self::EarlyReturn(v)
+    }
+}
+
+impl From<SkipOutsideLoop> for Error {
+    fn from(v: SkipOutsideLoop) -> Self {
+        Self::SkipOutsideLoop(v)
+    }
+}
+
+impl From<ImportConflict> for Error {
+    fn from(v: ImportConflict) -> Self {
+        Self::ImportConflict(v)
+    }
 }
 
 impl From<BeginNonExpression> for Error {
@@ -94,3 +135,24 @@ pub struct DuplicateBinding(pub Syntax<Symbol>);
 pub struct ExpectedSingleValue();
 #[derive(Debug)]
 pub struct BindInEmptyScopeSet(pub Symbol);
+#[derive(Debug)]
+pub struct ImportConflict(pub Syntax<Symbol>);
+#[derive(Debug)]
+pub struct SkipOutsideLoop(pub Ast);
+/// Not really an error, `stop` and `skip` unwind the evaluator up to the function they are in.
+#[derive(Debug)]
+pub struct EarlyReturn(pub Values);
+/// Not really an error either, reaching a label that is linked unwinds the evaluator up to the
+/// function (or program) it is in, which carries on from the label it jumps to, in the
+/// envoirnment of the jump.
+pub struct Goto(pub Label, pub EnvRef);
+
+impl fmt::Debug for Goto {
+    // the envoirnment has every definition in it, so it is left out
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Goto({})", self.0)
+    }
+}
+/// Every problem found when checking the labels of a program, at least one of which is an error.
+#[derive(Debug)]
+pub struct InvalidLabels(pub Vec<LabelDiagnostic>);
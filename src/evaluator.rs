@@ -1,16 +1,62 @@
 use crate::{
-    ast::{ast1::Ast1, Ast, Boolean, Function, Lambda, Pair, Param, Symbol},
-    error::{Error, ExpectedSingleValue},
+    ast::{
+        ast1::{Ast1, Label},
+        Applied, Ast, Boolean, CaseLambda, Function, Lambda, ModuleType, Pair, Param, Symbol,
+        Thunk,
+    },
+    error::{EarlyReturn, Error, ExpectedSingleValue, Goto},
     expander::expand_expr::list_to_cons,
     matches_to,
     primitives::new_primitive_env,
 };
 
-use clap::error::Result;
+use clap::{error::Result, ValueEnum};
 use itertools::Itertools;
-use rand::random;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+
+/// How the arguments of user defined functions are evaluated.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Strategy {
+    /// Before the function is called.
+    Strict,
+    /// Only once their values are needed, by a primitive, the test of an `if` or to be printed.
+    Lazy,
+}
+
+impl Default for Strategy {
+    // like the compiled code, which is only lazy with the `lazy` feature
+    fn default() -> Self {
+        if cfg!(feature = "lazy") {
+            Self::Lazy
+        } else {
+            Self::Strict
+        }
+    }
+}
+
+thread_local! {
+    // the evaluators version of the stop register, set by `stop` and checked (and reset) by `loop`
+    static STOP: Cell<bool> = const { Cell::new(false) };
+    // the evaluators version of the marks register, a list of (key . value) pairs with the
+    // innermost mark first
+    static MARKS: RefCell<Ast> = const { RefCell::new(Ast::TheEmptyList) };
+    // the evaluators version of the links, from each label that is linked to the label it jumps to,
+    // only the links of the program being evaluated (see `Evaluator::with_links`)
+    static LINKS: RefCell<HashMap<Label, Label>> = RefCell::new(HashMap::new());
+    // only lazy while running a program that is evaluated lazily (see `Evaluator::with_strategy`)
+    static STRATEGY: Cell<Strategy> = const { Cell::new(Strategy::Strict) };
+    // whether a `?` keeps the outcome of its first test (see `Evaluator::observe_once`)
+    static OBSERVE_ONCE: Cell<bool> = const { Cell::new(false) };
+    // what `?` is resolved with, it is only reproducible once seeded (see `Evaluator::seed`)
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_os_rng());
+}
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Env {
@@ -194,19 +240,109 @@ impl Evaluator {
         })
     }
     pub(crate) fn eval(expr: Ast1, env: EnvRef) -> Result<Values, Error> {
-        match expr {
-            Ast1::Lambda(param, body) => {
-                Ok(Values::Single(Ast::Function(Function::Lambda(Lambda {
-                    body,
-                    env,
-                    param,
-                }))))
-            }
-            Ast1::Quote(datum) => Ok(Values::Single(datum)),
-            Ast1::Begin(b) => Self::eval_sequence(b, env),
-            Ast1::Begin0(b) => todo!(),
-            Ast1::LetRecValues(v, b) => {
-                v.into_iter().try_for_each(|(mut ids, value)| -> Result<_, Error> {
+        let mut called = None;
+        let result = Self::eval_tail(expr, env, &mut called);
+        // after a call in tail position the rest of the evaluation is the body of the function
+        // called, so a goto from it is in that function, and a stop or skip from it returns from
+        // that function with its value
+        match called {
+            Some(body) => match Self::resume_gotos(&body, result) {
+                Err(Error::EarlyReturn(EarlyReturn(values))) => Ok(values),
+                result => result,
+            },
+            None => result,
+        }
+    }
+
+    // an expression in tail position (the branches of an if, the last expression of a begin, the
+    // body of a let, the body of a with-continuation-mark, and the body of a function applied in
+    // tail position) replaces the expression being evaluated instead of being evaluated
+    // recursively, so tail calls run in constant Rust stack, `called` is set to the body of the
+    // last function whose body replaced it
+    fn eval_tail(expr: Ast1, env: EnvRef, called: &mut Option<Rc<Ast1>>) -> Result<Values, Error> {
+        // the marks from before the first mark of this frame, they are put back however the frame
+        // is left (even with stop or skip)
+        let mut outer_marks = None;
+        let result = Self::eval_tail_marked(expr, env, called, &mut outer_marks);
+        if let Some(marks) = outer_marks {
+            MARKS.set(marks);
+        }
+        result
+    }
+
+    fn eval_tail_marked(
+        mut expr: Ast1,
+        mut env: EnvRef,
+        called: &mut Option<Rc<Ast1>>,
+        outer_marks: &mut Option<Ast>,
+    ) -> Result<Values, Error> {
+        // the marks of this frame (everything evaluated in tail position), with the innermost
+        // first, like in racket a mark replaces the mark of this frame with the same key
+        let mut frame_marks: Vec<(Ast, Ast)> = vec![];
+        loop {
+            expr = match expr {
+                Ast1::Lambda(param, body, name) => {
+                    return Ok(Values::Single(Ast::Function(Function::Lambda(Lambda {
+                        body: Rc::new(*body),
+                        env,
+                        param,
+                        name,
+                    }))))
+                }
+                Ast1::CaseLambda(clauses) => {
+                    return clauses
+                        .into_iter()
+                        .map(|(arity, clause)| {
+                            let clause = Self::eval_single_value(clause, Rc::clone(&env))?;
+                            let clause = matches_to!(clause => Ast::Function)
+                                .ok_or("case-lambda: clause is not a function")?;
+                            Ok((arity, clause))
+                        })
+                        .collect::<Result<_, Error>>()
+                        .map(|clauses| {
+                            Values::Single(Ast::Function(Function::CaseLambda(CaseLambda {
+                                clauses,
+                            })))
+                        })
+                }
+                Ast1::WithContinuationMark(key, val, body) => {
+                    let key = Self::force(Self::eval_single_value(*key, env.clone())?)?;
+                    let val = Self::force(Self::eval_single_value(*val, env.clone())?)?;
+                    let outer = outer_marks.get_or_insert_with(|| MARKS.with_borrow(Clone::clone));
+                    match frame_marks.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, v)) => *v = val,
+                        None => frame_marks.insert(0, (key, val)),
+                    }
+                    MARKS.set(
+                        frame_marks
+                            .iter()
+                            .rev()
+                            .fold(outer.clone(), |marks, (k, v)| {
+                                let mark = Ast::Pair(Box::new(Pair(k.clone(), v.clone())));
+                                Ast::Pair(Box::new(Pair(mark, marks)))
+                            }),
+                    );
+                    *body
+                }
+                Ast1::CurrentContinuationMarks => {
+                    return Ok(Values::Single(MARKS.with_borrow(Clone::clone)))
+                }
+                Ast1::Quote(datum) => return Ok(Values::Single(Self::observable_datum(datum))),
+                Ast1::Begin(mut b) => {
+                    let last = b.pop().ok_or("empty begin")?;
+                    b.into_iter()
+                        .try_for_each(|e| Self::eval(e, env.clone()).map(|_| ()))?;
+                    last
+                }
+                Ast1::Begin0(mut b) => {
+                    // begins are veirfied to have at least one expression
+                    let first = Self::eval(b.remove(0), env.clone())?;
+                    b.into_iter()
+                        .try_for_each(|e| Self::eval(e, env.clone()).map(|_| ()))?;
+                    return Ok(first);
+                }
+                Ast1::LetRecValues(v, b) => {
+                    v.into_iter().try_for_each(|(mut ids, value)| -> Result<_, Error> {
                                         let value = Self::eval(value, Rc::clone(&env))?;
                                         match value {
                                             Values::Many(vec) if vec.len() == ids.len() => {
@@ -220,10 +356,10 @@ impl Evaluator {
                                             _ => Err("let-values error: number of values is not the same as the number of ids".into()),
                                         }
                                     })?;
-                Self::eval(*b, env)
-            }
-            Ast1::LetValues(v, b) => {
-                let values = v.into_iter().map(|(mut ids, value)|-> Result<_, Error> {
+                    *b
+                }
+                Ast1::LetValues(v, b) => {
+                    let values = v.into_iter().map(|(mut ids, value)|-> Result<_, Error> {
                                         let value = Self::eval(value, Rc::clone(&env))?;
                                         match value {
                                             Values::Many(vec) if vec.len() == ids.len() => {
@@ -233,66 +369,363 @@ impl Evaluator {
                                             _ => Err("let-values error: number of values is not the same as the number of ids".into()),
                                         }
                                     }).try_collect::<_, Vec<_>, _>()?.concat();
-                let env = Rc::new(RefCell::new(Env {
-                    scope: HashMap::from_iter(values),
-                    parent: Some(env),
-                }));
-                Self::eval(*b, env)
+                    env = Rc::new(RefCell::new(Env {
+                        scope: HashMap::from_iter(values),
+                        parent: Some(env),
+                    }));
+                    *b
+                }
+                Ast1::Application(f, args) => {
+                    let f: Ast = Self::force(Self::eval_single_value(*f, env.clone())?)?;
+                    // when evaluating lazily only primitives are given the values of their
+                    // arguments
+                    let delay = STRATEGY.get() == Strategy::Lazy
+                        && !matches!(f, Ast::Function(Function::Primitive(_)));
+                    let rest = list_to_cons(
+                        args.into_iter()
+                            .map(|arg| {
+                                if delay {
+                                    Self::delay(arg, &env)
+                                } else {
+                                    Self::eval_single_value(arg, env.clone())
+                                }
+                            })
+                            .collect::<Result<Vec<_>, _>>()?
+                            .into_iter(),
+                        |x| x,
+                    );
+                    // like in the compiled code a stop in the function returns from it, but stop
+                    // stays set, so the loop it was called from ends after this iteration
+                    match Self::execute_application_tail(f, rest)? {
+                        Applied::Values(values) => return Ok(values),
+                        Applied::Body(body, body_env) => {
+                            env = body_env;
+                            let expr = (*body).clone();
+                            *called = Some(body);
+                            expr
+                        }
+                    }
+                }
+                Ast1::Basic(Ast::Symbol(s)) => {
+                    return env
+                        .borrow()
+                        .lookup(&s)
+                        .map(Values::Single)
+                        .ok_or(format!("free variable {s} eval").into())
+                }
+                Ast1::Basic(Ast::Label(l)) => {
+                    return match LINKS.with_borrow(|links| links.get(&Label(l.clone())).cloned()) {
+                        Some(dest) => Err(Goto(dest, env).into()),
+                        None => Ok(Values::Single(Ast::Label(l))),
+                    }
+                }
+                Ast1::Basic(expr) => return Ok(Values::Single(Self::observable(expr))),
+                Ast1::If(ast1, ast2, ast3) => {
+                    let cond = Self::eval_single_value(*ast1, env.clone())?;
+                    if Self::truthy(cond)? {
+                        *ast2
+                    } else {
+                        *ast3
+                    }
+                }
+                Ast1::DefineValues(mut ids, value) => {
+                    let value = Self::eval(*value, env.clone())?;
+                    return match value {
+                        Values::Many(vec) if vec.len() == ids.len() => {
+                            env.borrow_mut().define_values(ids.into_iter().map(Symbol), vec);
+                            Ok(Values::Many(vec![]))
+                        }
+                        Values::Single(ast) if ids.len() == 1 => {
+                            env.borrow_mut().define(Symbol(ids.remove(0)),  ast);
+                            Ok(Values::Many(vec![]))
+                        },
+                        _ => Err(
+                            "define-values error: number of values is not the same as the number of ids"
+                                .into(),
+                        ),
+                    };
+                }
+                Ast1::Set(s, ast1) => {
+                    let value = Self::eval_single_value(*ast1, env.clone())?;
+                    env.borrow_mut().set(Symbol(s), value);
+                    return Ok(Values::Many(vec![]));
+                }
+                Ast1::Stop(value) => {
+                    let value = value.map_or(Ok(Ast::TheEmptyList), |value| {
+                        Self::eval_single_value(*value, env)
+                    })?;
+                    STOP.set(true);
+                    return Err(EarlyReturn(Values::Single(value)).into());
+                }
+                // like in the compiled code skip just returns from the function the loop calls, but
+                // without setting stop
+                Ast1::Skip => return Err(EarlyReturn(Values::Single(Ast::TheEmptyList)).into()),
+                Ast1::Loop(producer) => {
+                    let producer = Self::force(Self::eval_single_value(*producer, env)?)?;
+                    return loop {
+                        let value = Self::execute_application(producer.clone(), Ast::TheEmptyList)?;
+                        if STOP.replace(false) {
+                            break Ok(value);
+                        }
+                    };
+                }
+                // a module is run where it is declared, so refering to it does nothing
+                Ast1::Module(_, ModuleType::Inline(_)) => return Ok(Values::Many(vec![])),
+                Ast1::Module(name, ModuleType::Path(path)) => {
+                    return Err(format!("module {name}: cannot load a module from {path}").into())
+                }
+                Ast1::Expression(ast1) => *ast1,
             }
-            Ast1::Application(f, args) => {
-                let f: Ast = Self::eval_single_value(*f, env.clone())?;
-                let rest = list_to_cons(
-                    args.into_iter()
-                        .map(|arg| Self::eval_single_value(arg, env.clone()))
-                        .collect::<Result<Vec<_>, _>>()?
-                        .into_iter(),
-                    |x| x,
-                );
-
-                Self::execute_application(f, rest)
+        }
+    }
+
+    // a `?` that is observed once is in a thunk shared by each copy of it, which is set to the
+    // outcome of its first test
+    fn truthy(mut cond: Ast) -> Result<bool, Error> {
+        let mut observed = None;
+        while let Ast::Thunk(thunk) = cond {
+            cond = thunk.force_once()?;
+            observed = Some(thunk);
+        }
+        Ok(match cond {
+            Ast::Boolean(Boolean::False) => false,
+            Ast::Boolean(Boolean::Maybe) => {
+                let outcome = Self::resolve_maybe();
+                if let Some(thunk) = observed.filter(|_| OBSERVE_ONCE.get()) {
+                    thunk.observe(outcome);
+                }
+                outcome
             }
-            Ast1::Basic(Ast::Symbol(s)) => env
-                .borrow()
-                .lookup(&s)
-                .map(Values::Single)
-                .ok_or(format!("free variable {s} eval").into()),
-            Ast1::Basic(expr) => Ok(Values::Single(expr)),
-            Ast1::If(ast1, ast2, ast3) => {
-                let cond = Self::eval_single_value(*ast1, env.clone())?;
-                let cond = matches!(cond, Ast::Boolean(Boolean::False | Boolean::Maybe) if random::<u8>() % 2 == 0);
-                if cond {
-                    Self::eval(*ast2, env)
-                } else {
-                    Self::eval(*ast3, env)
+            _ => true,
+        })
+    }
+
+    // when a `?` is observed once, every `?` value (not just a `?` literal) is in its own shared
+    // thunk, like in the compiled code where each `?` is its own object
+    pub(crate) fn observable(value: Ast) -> Ast {
+        match value {
+            Ast::Boolean(Boolean::Maybe) if OBSERVE_ONCE.get() => Ast::Thunk(Thunk::maybe()),
+            value => value,
+        }
+    }
+
+    pub(crate) fn observable_values(values: Values) -> Values {
+        match values {
+            Values::Single(value) => Values::Single(Self::observable(value)),
+            Values::Many(values) => {
+                Values::Many(values.into_iter().map(Self::observable).collect())
+            }
+        }
+    }
+
+    // each `?` in quoted data is its own value too
+    fn observable_datum(datum: Ast) -> Ast {
+        match datum {
+            Ast::Pair(pair) if OBSERVE_ONCE.get() => {
+                let Pair(car, cdr) = *pair;
+                Ast::Pair(Box::new(Pair(
+                    Self::observable_datum(car),
+                    Self::observable_datum(cdr),
+                )))
+            }
+            datum => Self::observable(datum),
+        }
+    }
+
+    /// Whether each `?` evaluated from now on keeps the outcome of its first test, instead of
+    /// being resolved again each time it is tested.
+    pub(crate) fn observe_once(observe_once: bool) {
+        OBSERVE_ONCE.set(observe_once);
+    }
+
+    /// Resolves each `?` from now on with a generator seeded with `seed`, so the same seed always
+    /// resolves them the same way.
+    pub(crate) fn seed(seed: u32) {
+        RNG.set(StdRng::seed_from_u64(seed.into()));
+    }
+
+    /// Whether a `?` is true this time.
+    pub(crate) fn resolve_maybe() -> bool {
+        RNG.with_borrow_mut(|rng| rng.random_bool(0.5))
+    }
+
+    /// Runs `f` with arguments evaluated according to `strategy`.
+    pub(crate) fn with_strategy<T>(strategy: Strategy, f: impl FnOnce() -> T) -> T {
+        let outer = STRATEGY.replace(strategy);
+        let res = f();
+        STRATEGY.set(outer);
+        res
+    }
+
+    // an argument that can be evaluated without any side effects is not delayed, a variable is
+    // just looked up (its value may already be a thunk), so that passing a variable along does not
+    // make a chain of thunks
+    fn delay(arg: Ast1, env: &EnvRef) -> Result<Ast, Error> {
+        match arg {
+            Ast1::Basic(_) | Ast1::Quote(_) | Ast1::Lambda(..) => {
+                Self::eval_single_value(arg, env.clone())
+            }
+            arg => Ok(Ast::Thunk(Thunk::new(arg, env.clone()))),
+        }
+    }
+
+    /// The value of `value`, which is only not `value` itself if it is a thunk.
+    ///
+    /// # Errors
+    /// If evaluating the thunk errors.
+    pub fn force(mut value: Ast) -> Result<Ast, Error> {
+        while let Ast::Thunk(thunk) = value {
+            value = thunk.force_once()?;
+        }
+        Ok(value)
+    }
+
+    /// `values` with each value forced.
+    ///
+    /// # Errors
+    /// If evaluating any of the thunks errors.
+    pub fn force_values(values: Values) -> Result<Values, Error> {
+        match values {
+            Values::Single(value) => Self::force(value).map(Values::Single),
+            Values::Many(values) => values
+                .into_iter()
+                .map(Self::force)
+                .collect::<Result<_, _>>()
+                .map(Values::Many),
+        }
+    }
+
+    // the arguments of a primitive, there can only be thunks in them when evaluating lazily (or
+    // when a `?` is observed once)
+    pub(crate) fn force_args(args: Ast) -> Result<Ast, Error> {
+        if STRATEGY.get() == Strategy::Strict && !OBSERVE_ONCE.get() {
+            return Ok(args);
+        }
+        Ok(list_to_cons(
+            args.to_list_checked::<Error>()?
+                .into_iter()
+                .map(Self::force)
+                .collect::<Result<Vec<_>, _>>()?
+                .into_iter(),
+            |x| x,
+        ))
+    }
+
+    // evaluates the body of a function, which is where any `stop` or `skip` in it returns to, and
+    // where any goto in it jumps within
+    pub(crate) fn eval_function_body(body: &Ast1, env: EnvRef) -> Result<Values, Error> {
+        match Self::resume_gotos(body, Self::eval(body.clone(), env)) {
+            Err(Error::EarlyReturn(EarlyReturn(values))) => Ok(values),
+            result => result,
+        }
+    }
+
+    /// Evaluates a whole program (or a single top level form), a goto in it can jump to any label
+    /// in it that is not in a function.
+    pub(crate) fn eval_program(program: Ast1, env: EnvRef) -> Result<Values, Error> {
+        Self::resume_gotos(&program, Self::eval(program.clone(), env))
+    }
+
+    /// Runs `f` with the links of `links` (keyed by the label that is jumped to, like
+    /// `Expander::links`).
+    pub(crate) fn with_links<T>(links: &HashMap<Label, Vec<Label>>, f: impl FnOnce() -> T) -> T {
+        let links = links
+            .iter()
+            .flat_map(|(dest, srcs)| srcs.iter().map(move |src| (src.clone(), dest.clone())))
+            .collect();
+        let outer = LINKS.replace(links);
+        let res = f();
+        LINKS.set(outer);
+        res
+    }
+
+    // a goto unwinds the evaluator up to the function (or program) it is in, which then carries on
+    // from the label it jumps to, as many times as it is jumped from
+    fn resume_gotos(body: &Ast1, mut result: Result<Values, Error>) -> Result<Values, Error> {
+        loop {
+            match result {
+                Err(Error::Goto(Goto(label, env))) => {
+                    result = if Self::places(body, &label) {
+                        Self::eval_from(body.clone(), &label, env)
+                    } else {
+                        Err(
+                            format!("cannot jump to {label}, it is not in the same function")
+                                .into(),
+                        )
+                    };
                 }
+                result => return result,
             }
-            Ast1::DefineValues(mut ids, value) => {
-                let value = Self::eval(*value, env.clone())?;
-                match value {
-                    Values::Many(vec) if vec.len() == ids.len() => {
-                        env.borrow_mut().define_values(ids.into_iter().map(Symbol), vec);
-                        Ok(Values::Many(vec![]))
-                    }
-                    Values::Single(ast) if ids.len() == 1 => {
-                        env.borrow_mut().define(Symbol(ids.remove(0)),  ast);
-                        Ok(Values::Many(vec![]))
-                    },
-                    _ => Err(
-                        "define-values error: number of values is not the same as the number of ids"
-                            .into(),
-                    ),
+        }
+    }
+
+    // whether `label` is placed in `expr`, but not in a function in it
+    fn places(expr: &Ast1, label: &Label) -> bool {
+        match expr {
+            Ast1::Basic(Ast::Label(l)) => *l == label.0,
+            Ast1::Basic(_)
+            | Ast1::Quote(_)
+            | Ast1::Lambda(..)
+            | Ast1::CaseLambda(_)
+            | Ast1::CurrentContinuationMarks
+            | Ast1::Skip
+            | Ast1::Module(..) => false,
+            Ast1::If(cond, then, alt) => {
+                Self::places(cond, label) || Self::places(then, label) || Self::places(alt, label)
+            }
+            Ast1::WithContinuationMark(key, val, body) => {
+                Self::places(key, label) || Self::places(val, label) || Self::places(body, label)
+            }
+            Ast1::Begin(b) | Ast1::Begin0(b) => b.iter().any(|e| Self::places(e, label)),
+            Ast1::Application(f, args) => {
+                Self::places(f, label) || args.iter().any(|e| Self::places(e, label))
+            }
+            Ast1::LetValues(v, b) | Ast1::LetRecValues(v, b) => {
+                v.iter().any(|(_, e)| Self::places(e, label)) || Self::places(b, label)
+            }
+            Ast1::DefineValues(_, e)
+            | Ast1::Set(_, e)
+            | Ast1::Loop(e)
+            | Ast1::Expression(e)
+            | Ast1::Stop(Some(e)) => Self::places(e, label),
+            Ast1::Stop(None) => false,
+        }
+    }
+
+    // evaluates what is left of `expr` after `label` (which is placed in it), like the compiled
+    // code the jump only skips over what is before the label, so the envoirnment is the one the
+    // jump is from, and a jump into a let body does not bind its variables
+    fn eval_from(expr: Ast1, label: &Label, env: EnvRef) -> Result<Values, Error> {
+        match expr {
+            Ast1::Basic(Ast::Label(l)) => Ok(Values::Single(Ast::Label(l))),
+            Ast1::Begin(b) => {
+                let mut rest = b.into_iter().skip_while(|e| !Self::places(e, label));
+                let first = rest.next().ok_or("empty begin")?;
+                rest.try_fold(Self::eval_from(first, label, env.clone())?, |_, e| {
+                    Self::eval(e, env.clone())
+                })
+            }
+            Ast1::If(cond, then, alt) => {
+                if Self::places(&cond, label) {
+                    let cond = Self::eval_from(*cond, label, env.clone())?
+                        .into_single()
+                        .map_err(|_| Error::ExpectedSingleValue(ExpectedSingleValue()))?;
+                    Self::eval(if Self::truthy(cond)? { *then } else { *alt }, env)
+                } else if Self::places(&then, label) {
+                    Self::eval_from(*then, label, env)
+                } else {
+                    Self::eval_from(*alt, label, env)
                 }
             }
-            Ast1::Set(s, ast1) => {
-                let value = Self::eval_single_value(*ast1, env.clone())?;
-                env.borrow_mut().set(Symbol(s), value);
-                Ok(Values::Many(vec![]))
+            Ast1::LetValues(_, b) | Ast1::LetRecValues(_, b) | Ast1::Expression(b)
+                if Self::places(&b, label) =>
+            {
+                Self::eval_from(*b, label, env)
             }
-            Ast1::Stop(ast1) => todo!(),
-            Ast1::Skip => todo!(),
-            Ast1::Loop(ast1) => todo!(),
-            Ast1::Module(_, module_type) => todo!(),
-            Ast1::Expression(ast1) => Self::eval(*ast1, env),
+            expr => Err(format!(
+                "cannot jump to {label}, only labels in a begin, if or let body can be jumped to: {expr}"
+            )
+            .into()),
         }
     }
 
@@ -304,12 +737,12 @@ impl Evaluator {
         }
     }
 
-    pub(crate) fn eval_sequence(body: Vec<Ast1>, env: Rc<RefCell<Env>>) -> Result<Values, Error> {
-        // this might not fail if anything but last one doesn't fail
-        body.into_iter()
-            .map(|v| Self::eval(v, env.clone()))
-            .next_back()
-            .ok_or("empty begin".to_string())?
+    fn execute_application_tail(f: Ast, args: Ast) -> Result<Applied, Error> {
+        if let Ast::Function(f) = f {
+            f.apply_tail(args)
+        } else {
+            Err(format!("cannot not apply {f} to {args:?}, because {f} is not a function").into())
+        }
     }
     pub fn to_id_list(ids: Ast) -> Result<Vec<Symbol>, Error> {
         let ids = ids.to_list_checked::<Error>()?;
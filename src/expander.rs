@@ -3,16 +3,24 @@ use std::{
     rc::Rc,
 };
 
-use binding::CoreForm;
+use binding::{CoreForm, Macro};
 use expand_context::ExpandContext;
+use label::LabelUses;
+use module::Module;
 use namespace::NameSpace;
+use stepper::Step;
 
 use crate::{
     ast::syntax::{Properties, SourceLocation},
-    evaluator::Values,
+    evaluator::{Strategy, Values},
 };
 use crate::{
-    ast::{ast1::Label, scope::Scope, syntax::Syntax, Ast, Symbol},
+    ast::{
+        ast1::Label,
+        scope::{Scope, ScopeSet},
+        syntax::Syntax,
+        Ast, Symbol,
+    },
     evaluator::{Env, EnvRef},
     primitives::new_primitive_env,
     UniqueNumberManager,
@@ -30,17 +38,39 @@ pub mod expand;
 pub mod expand_context;
 pub mod expand_expr;
 mod expand_top_level;
+pub mod hygiene;
+pub mod label;
+mod module;
 mod namespace;
+mod prelude;
+mod statement;
+pub mod stepper;
 // TODO: maybe combine a bit with expand context
 pub struct Expander {
     core_forms: HashMap<Rc<str>, CoreForm>,
+    core_macros: HashMap<Rc<str>, Macro>,
     core_primitives: HashMap<Rc<str>, Ast>,
     core_scope: Scope,
     expand_time_env: EnvRef,
     run_time_env: EnvRef,
     core_syntax: Syntax<Ast>,
     pub(crate) variable: Symbol,
+    // keyed by the resolved name of the label (see `Expander::resolve_label`)
     pub links: HashMap<Label, Vec<Label>>,
+    // every label has this scope so that it cannot be seen by an identifier
+    label_scope: Scope,
+    // the scopes introduced by applying macros, which are the only scopes that tell apart labels
+    introduction_scopes: ScopeSet,
+    labels: LabelUses,
+    // whether syntax marked as fully expanded is skipped, only turned off to compare with
+    pub(crate) expansion_cache: bool,
+    // how the program is evaluated, the code for syntax is always evaluated strictly
+    pub strategy: Strategy,
+    modules: HashMap<Symbol, Module>,
+    // only recorded while stepping
+    steps: Option<Vec<Step>>,
+    // called with every step as it is recorded
+    on_step: Option<Box<dyn FnMut(usize, &Step)>>,
 }
 
 impl Default for Expander {
@@ -64,12 +94,23 @@ impl Expander {
             core_scope,
             core_primitives: HashMap::new(),
             core_forms: HashMap::new(),
+            core_macros: HashMap::new(),
             run_time_env: Env::new_env(),
             expand_time_env: Env::new_env(),
             variable,
             links: HashMap::new(),
+            label_scope: UniqueNumberManager::new_scope(),
+            introduction_scopes: ScopeSet::new(),
+            labels: LabelUses::default(),
+            expansion_cache: true,
+            strategy: Strategy::default(),
+            modules: HashMap::new(),
+            steps: None,
+            on_step: None,
         };
         this.add_core_forms();
+        this.add_core_macros();
+        this.add_prelude_macros();
         new_primitive_env(|name, primitive| {
             this.add_core_primitive(name, primitive);
         });
@@ -87,10 +128,9 @@ impl Expander {
 
     // TODO: mutability of ns/ctx how should/are changes to envoirnments preserved over multiple
     // expansions maybe have a namespace as part of the expander object
-    // TODO: annonate fully expanded expressions so we don't have to reexpand them
     pub fn eval(&mut self, s: Ast, ns: NameSpace) -> Result<Values, Error> {
         let ctx = ExpandContext::new(ns.clone());
-        let expanded = self.expand(
+        let expanded = self.expand_top_level(
             self.namespace_syntax_introduce(s.datum_to_syntax(None, None, None)),
             ctx,
         )?;
@@ -103,13 +143,23 @@ impl Expander {
 #[cfg(test)]
 mod tests {
 
-    use crate::ast::ast1::Ast1;
-    use crate::ast::Ast;
-    use crate::error::Error;
-    use crate::evaluator::{Evaluator, Values};
+    use std::{cell::RefCell, rc::Rc};
 
+    use itertools::Itertools;
+
+    use crate::ast::ast1::{Ast1, Label};
+    use crate::ast::{Ast, Boolean, Pair};
+    use crate::error::{Error, InvalidLabels};
+    use crate::evaluator::{Evaluator, Strategy, Values};
+
+    use crate::ast::scope::Scope;
+    use crate::ast::syntax::{Properties, SourceLocation, Syntax};
+    use crate::ast::Symbol;
+    use crate::expander::binding::Binding;
+    use crate::expander::expand_expr::list_to_cons;
+    use crate::expander::label::LabelDiagnostic;
     use crate::expander::Expander;
-    use crate::{list, sexpr};
+    use crate::{list, sexpr, UniqueNumberManager};
 
     use super::expand_context::ExpandContext;
 
@@ -129,9 +179,7 @@ mod tests {
                     let namespace = self.namespace();
                     self.compile(e, &namespace)
                 })
-                .and_then(|e| {
-                    Evaluator::eval(e.clone(), self.run_time_env.clone()).map(|v| (e, v))
-                });
+                .and_then(|e| self.run_time_eval(e.clone()).map(|v| (e, v)));
             match c {
                 Ok(v) => v,
                 Err(e) => panic!("{e:?}"),
@@ -142,6 +190,33 @@ mod tests {
             check.inspect(|check| assert_eq!(&v, check));
             v
         }
+        // expands, compiles and evaluates each form in the same namespace, like a file would be
+        fn eval_top_level(&mut self, es: Vec<Ast>) -> Result<Values, Error> {
+            let ns = self.namespace();
+            let ctx = ExpandContext::new(ns.clone());
+            es.into_iter()
+                .map(|e| {
+                    let e = self.namespace_syntax_introduce(e.datum_to_syntax(None, None, None));
+                    self.expand_top_level(e, ctx.clone())
+                        .and_then(|e| self.compile(e, &ns))
+                        .and_then(|e| self.run_time_eval(e))
+                })
+                .try_fold(Values::Many(vec![]), |_, v| v)
+        }
+        // like eval_top_level, but every form is compiled before the program is run as a whole
+        fn run_program(&mut self, es: Vec<Ast>) -> Result<Values, Error> {
+            let ns = self.namespace();
+            let ctx = ExpandContext::new(ns.clone());
+            let program = es
+                .into_iter()
+                .map(|e| {
+                    let e = self.namespace_syntax_introduce(e.datum_to_syntax(None, None, None));
+                    self.expand_top_level(e, ctx.clone())
+                        .and_then(|e| self.compile(e, &ns))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            self.run_time_eval(Ast1::Begin(program))
+        }
     }
 
     fn add_let(e: Ast) -> Ast {
@@ -269,4 +344,1120 @@ mod tests {
             )
             .is_err_and(|e| matches!(e, Error::IllegalUseOfSyntax(_))));
     }
+
+    fn module_a() -> Ast {
+        sexpr!((module a ("define-values" (x) (quote 1)) ("define-values" (y) (quote 2)) (provide x)))
+    }
+
+    #[test]
+    fn expander_test_module_provide_require() {
+        let mut expander = Expander::new();
+        let v = expander.eval_top_level(vec![module_a(), sexpr!((require a)), sexpr!(x)]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::Number(1.))));
+        assert!(expander.eval_top_level(vec![sexpr!(y)]).is_err());
+    }
+
+    #[test]
+    fn expander_test_module_require_renaming() {
+        let mut expander = Expander::new();
+        let v = expander.eval_top_level(vec![
+            module_a(),
+            sexpr!((require ("prefix-in" "a:" a) ("rename-in" a (x z)))),
+            sexpr!((list "a:x" z)),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(list!(Ast::Number(1.), Ast::Number(1.)))));
+    }
+
+    #[test]
+    fn expander_test_unbound_identifier_at_compile_time() {
+        let mut expander = Expander::new();
+        // a definition can be used before its value is evaluated
+        let v = expander.eval_top_level(vec![
+            sexpr!(("define-values" (f) (lambda (1) (if ("null?" (param 0)) 0 (f (cdr (param 0))))))),
+            sexpr!((f (quote (1 2)))),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::Number(0.))));
+        // but a namespace that never saw the definition does not know the name
+        let namespace = expander.namespace();
+        let e = expander.expand_expression(sexpr!(f)).unwrap();
+        assert!(expander
+            .compile(e, &namespace)
+            .is_err_and(|e| matches!(e, Error::Other(e) if e.contains("unbound identifier f"))));
+    }
+
+    #[test]
+    fn expander_test_module_import_conflict() {
+        let mut expander = Expander::new();
+        let v = expander.eval_top_level(vec![
+            module_a(),
+            sexpr!((module b ("define-values" (x) (quote 3)) (provide x))),
+            sexpr!((require a)),
+            sexpr!((require b)),
+        ]);
+        assert!(v.is_err_and(|e| matches!(e, Error::ImportConflict(_))));
+    }
+
+    #[test]
+    fn expander_test_module_macro_keeps_definition_scope() {
+        let mut expander = Expander::new();
+        let v = expander.eval_top_level(vec![
+            sexpr!((module m
+                ("define-values" (secret) (quote 5))
+                ("define-syntaxes" (reveal) (lambda (1) ("quote-syntax" secret)))
+                (provide reveal))),
+            sexpr!((require m)),
+            sexpr!((reveal)),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::Number(5.))));
+        assert!(expander.eval_top_level(vec![sexpr!(secret)]).is_err());
+    }
+
+    #[test]
+    fn expander_test_skip_to_next_iteration() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            ("let-values" (((xs) (quote (1 2 3))) ((kept) (quote ())))
+                (loop (lambda (0)
+                    (if ("null?" xs) (stop kept) (quote ()))
+                    ("let-values" (((x) (car xs)))
+                        ("set-bang" xs (cdr xs))
+                        (if ("null?" xs) (skip) (quote ()))
+                        ("set-bang" kept (cons x kept))))))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(Ast::Number(2.), Ast::Number(1.)))),
+        );
+    }
+
+    #[test]
+    fn expander_test_stop_from_function() {
+        let mut expander = Expander::new();
+        // stop returns from early, and like in the compiled code the loop early is called from
+        // ends after that iteration
+        let expr = sexpr!(
+            ("let-values"
+                (((early) (lambda (1) (stop (param 0)) 6))
+                 ((seen) (quote ())))
+                (loop (lambda (0)
+                    ("set-bang" seen (cons (early 1) seen))
+                    seen)))
+        );
+        expander.eval_expression(expr, Some(Values::Single(list!(Ast::Number(1.)))));
+    }
+
+    #[test]
+    fn expander_test_tail_calls() {
+        let mut expander = Expander::new();
+        // counts through every number a list of 20 bits can hold (a bit is 0 when it is the empty
+        // list) by calling count once for each number, so it recurses 2^20 times in tail position
+        let bits = list_to_cons(vec![Ast::TheEmptyList; 20].into_iter(), |x| x);
+        let v = expander.eval_top_level(vec![
+            sexpr!(("define-values" (inc) (lambda (1)
+                (if ("null?" (param 0))
+                    (quote ())
+                    (if ("null?" (car (param 0)))
+                        (cons 1 (cdr (param 0)))
+                        ("let-values" (((rest) (inc (cdr (param 0)))))
+                            (if ("null?" rest) (quote ()) (cons (quote ()) rest)))))))),
+            sexpr!(("define-values" (count) (lambda (1)
+                (if ("null?" (param 0)) (quote done) (count (inc (param 0))))))),
+            sexpr!((count (quote #(bits)))),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::Symbol("done".into()))));
+    }
+
+    #[test]
+    fn expander_test_lazy_evaluation() {
+        let run = |strategy, expr| {
+            let mut expander = Expander::new();
+            expander.strategy = strategy;
+            expander.eval_expression(expr, None)
+        };
+        let number = Ast::Number;
+        // an argument that is never used is never evaluated
+        let unused = || {
+            sexpr!(
+                ("let-values" (((log) (quote ())))
+                    (cons ((lambda (1) 5) ("set-bang" log (cons 1 log))) log))
+            )
+        };
+        assert_eq!(
+            run(Strategy::Strict, unused()),
+            Values::Single(list!(number(5.), number(1.)))
+        );
+        assert_eq!(
+            run(Strategy::Lazy, unused()),
+            Values::Single(list!(number(5.)))
+        );
+        // and one that is used twice is only evaluated once
+        let twice = sexpr!(
+            ("let-values" (((log) (quote ())))
+                (cons
+                    ((lambda (1) (cons (param 0) (param 0)))
+                        (begin ("set-bang" log (cons 1 log)) 2))
+                    log))
+        );
+        assert_eq!(
+            run(Strategy::Lazy, twice),
+            Values::Single(list!(list!(number(2.); number(2.)), number(1.)))
+        );
+        // an if forces its test, and the value of the program is forced
+        let expr = sexpr!(((lambda (1) (if (param 0) (param 0) 3)) (car (quote (4)))));
+        assert_eq!(run(Strategy::Lazy, expr), Values::Single(number(4.)));
+    }
+
+    #[test]
+    fn expander_test_seeded_maybe() {
+        let run = |seed| {
+            Evaluator::seed(seed);
+            let maybe = || sexpr!((if #(Ast::Boolean(Boolean::Maybe)) 1 0));
+            let expr = sexpr!(
+                (list #(maybe()) #(maybe()) #(maybe()) #(maybe())
+                    #(maybe()) #(maybe()) #(maybe()) #(maybe()))
+            );
+            Expander::new().eval_expression(expr, None)
+        };
+        // the same seed resolves each `?` the same way
+        assert_eq!(run(42), run(42));
+        assert_eq!(run(7), run(7));
+    }
+
+    #[test]
+    fn expander_test_kleene_logic() {
+        use Boolean::{False as F, Maybe as M, True as T};
+        let run = |expr| Expander::new().eval_expression(expr, None);
+        let boolean = Ast::Boolean;
+        // each table is indexed by the first argument then the second one, in the order | ? &
+        let tables = [
+            ("kleene-and", [[F, F, F], [F, M, M], [F, M, T]]),
+            ("kleene-or", [[F, M, T], [M, M, T], [T, T, T]]),
+            ("xor", [[F, M, T], [M, M, M], [T, M, F]]),
+            ("implies", [[T, T, T], [M, M, T], [F, M, T]]),
+        ];
+        for (op, table) in tables {
+            for (a, row) in [F, M, T].into_iter().zip(table) {
+                for (b, expected) in [F, M, T].into_iter().zip(row) {
+                    let expr = sexpr!((#(op.into()) #(boolean(a)) #(boolean(b))));
+                    assert_eq!(run(expr), Values::Single(boolean(expected)), "{op} {a} {b}");
+                }
+            }
+        }
+        // anything that is not a boolean is true
+        let expr = sexpr!(("kleene-and" 1 #(boolean(M))));
+        assert_eq!(run(expr), Values::Single(boolean(M)));
+        let expr = sexpr!(
+            (list ("maybe?" #(boolean(M))) ("definitely?" #(boolean(M))) ("maybe?" 1))
+        );
+        assert_eq!(
+            run(expr),
+            Values::Single(list!(boolean(T), boolean(F), boolean(F)))
+        );
+        let expr = sexpr!(("maybe?" (collapse #(boolean(M)))));
+        assert_eq!(run(expr), Values::Single(boolean(F)));
+        let expr = sexpr!((collapse 1));
+        assert_eq!(run(expr), Values::Single(Ast::Number(1.)));
+    }
+
+    #[test]
+    fn expander_test_observe_maybe_once() {
+        Evaluator::observe_once(true);
+        let run = |expr| Expander::new().eval_expression(expr, None);
+        let maybe = || Ast::Boolean(Boolean::Maybe);
+        // each copy of x keeps the outcome of the first test of x
+        let expr = sexpr!(
+            ("let-values" (((x) #(maybe())))
+                ("let-values" (((first) (if x 1 0)) ((y) x))
+                    (list ("maybe?" x) first (if x 1 0) (if y 1 0) (if x 1 0))))
+        );
+        let Values::Single(Ast::Pair(p)) = run(expr) else {
+            panic!("expected a list")
+        };
+        let Pair(was_maybe, outcomes) = *p;
+        assert_eq!(was_maybe, Ast::Boolean(Boolean::False));
+        let outcomes = outcomes.to_list_checked::<Error>().unwrap();
+        assert!(outcomes.iter().all_equal(), "{outcomes:?}");
+        // an untested `?` is still a `?`
+        let expr = sexpr!(("let-values" (((x) #(maybe()))) ("maybe?" x)));
+        assert_eq!(run(expr), Values::Single(Ast::Boolean(Boolean::True)));
+        // so does a `?` from a primitive or from quoted data
+        let outcomes = |expr| match run(expr) {
+            Values::Single(outcomes) => outcomes.to_list_checked::<Error>().unwrap(),
+            values => panic!("expected a list, got {values}"),
+        };
+        let expr = sexpr!(
+            ("let-values" (((x) ("kleene-and" #(Ast::Boolean(Boolean::True)) #(maybe()))))
+                (list (if x 1 0) (if x 1 0) (if x 1 0) (if x 1 0) (if x 1 0) (if x 1 0)))
+        );
+        let v = outcomes(expr);
+        assert!(v.iter().all_equal(), "{v:?}");
+        let expr = sexpr!(
+            ("let-values" (((x) (car (quote (#(maybe()))))))
+                (list (if x 1 0) (if x 1 0) (if x 1 0) (if x 1 0) (if x 1 0) (if x 1 0)))
+        );
+        let v = outcomes(expr);
+        assert!(v.iter().all_equal(), "{v:?}");
+        Evaluator::observe_once(false);
+    }
+
+    #[test]
+    fn expander_test_arithmetic() {
+        let run = |expr| Expander::new().eval_expression(expr, None);
+        let number = |n| Values::Single(Ast::Number(n));
+        let boolean = |b: bool| Values::Single(Ast::Boolean(b.into()));
+        assert_eq!(run(sexpr!(("+"))), number(0.));
+        assert_eq!(run(sexpr!(("+" 1 2 3))), number(6.));
+        assert_eq!(run(sexpr!(("-" 5))), number(-5.));
+        assert_eq!(run(sexpr!(("-" 10 1 2))), number(7.));
+        assert_eq!(run(sexpr!(("*" 2 3 4))), number(24.));
+        assert_eq!(run(sexpr!(("/" 4))), number(0.25));
+        assert_eq!(run(sexpr!(("/" 12 2 3))), number(2.));
+        assert_eq!(run(sexpr!((quotient #(Ast::Number(-7.)) 2))), number(-3.));
+        assert_eq!(run(sexpr!((remainder #(Ast::Number(-7.)) 2))), number(-1.));
+        assert_eq!(run(sexpr!((modulo #(Ast::Number(-7.)) 2))), number(1.));
+        assert_eq!(run(sexpr!((modulo 7 #(Ast::Number(-2.))))), number(-1.));
+        assert_eq!(run(sexpr!(("<" 1 2 3))), boolean(true));
+        assert_eq!(run(sexpr!(("<" 1 3 2))), boolean(false));
+        assert_eq!(run(sexpr!((">=" 3 3 1))), boolean(true));
+        assert_eq!(run(sexpr!(("=" 2 2 2))), boolean(true));
+        assert_eq!(run(sexpr!((abs #(Ast::Number(-2.5))))), number(2.5));
+        assert_eq!(run(sexpr!((floor 2.5))), number(2.));
+        assert_eq!(run(sexpr!((ceiling 2.5))), number(3.));
+        assert_eq!(run(sexpr!((round 2.5))), number(2.));
+        assert_eq!(run(sexpr!((round 3.5))), number(4.));
+        assert_eq!(run(sexpr!((sqrt 16))), number(4.));
+        assert_eq!(run(sexpr!((expt 2 10))), number(1024.));
+        let mut expander = Expander::new();
+        assert!(expander.run_program(vec![sexpr!(("/" 1 0))]).is_err());
+        assert!(expander
+            .run_program(vec![sexpr!(("+" 1 (quote a)))])
+            .is_err());
+        assert!(expander.run_program(vec![sexpr!(("-"))]).is_err());
+        assert!(expander.run_program(vec![sexpr!(("/"))]).is_err());
+        // = used to compare anything, so its error points at equal?
+        assert!(matches!(
+            expander.run_program(vec![sexpr!(("=" 1 (quote a)))]),
+            Err(Error::Other(e)) if e.contains("equal?")
+        ));
+    }
+
+    #[test]
+    fn expander_test_strings() {
+        let run = |expr| Expander::new().eval_expression(expr, None);
+        let string = |s: &str| Ast::String(s.into());
+        let result = |s: &str| Values::Single(string(s));
+        let number = |n| Values::Single(Ast::Number(n));
+        let boolean = |b: bool| Values::Single(Ast::Boolean(b.into()));
+        // lengths and indices count characters, not bytes
+        assert_eq!(
+            run(sexpr!(("string-length" #(string("héllo"))))),
+            number(5.)
+        );
+        assert_eq!(
+            run(sexpr!(("string-ref" #(string("héllo")) 1))),
+            result("é")
+        );
+        assert_eq!(
+            run(sexpr!((substring #(string("héllo")) 1 3))),
+            result("él")
+        );
+        assert_eq!(run(sexpr!((substring #(string("héllo")) 2))), result("llo"));
+        assert_eq!(
+            run(sexpr!(("string-append" #(string("ab")) #(string("")) #(string("ç"))))),
+            result("abç")
+        );
+        assert_eq!(run(sexpr!(("string-append"))), result(""));
+        assert_eq!(
+            run(sexpr!(("string=?" #(string("ab")) #(string("ab"))))),
+            boolean(true)
+        );
+        assert_eq!(
+            run(sexpr!(("string<?" #(string("ab")) #(string("abc"))))),
+            boolean(true)
+        );
+        assert_eq!(
+            run(sexpr!(("string<?" #(string("b")) #(string("abc"))))),
+            boolean(false)
+        );
+        assert_eq!(
+            run(sexpr!(("string->symbol" #(string("a b"))))),
+            Values::Single(Ast::Symbol("a b".into()))
+        );
+        assert_eq!(run(sexpr!(("symbol->string" (quote abc)))), result("abc"));
+        assert_eq!(run(sexpr!(("number->string" 15.5625))), result("0xf%9"));
+        assert_eq!(
+            run(sexpr!(("number->string" #(Ast::Number(-256.))))),
+            result("-0x100")
+        );
+        assert_eq!(
+            run(sexpr!(("string->number" #(string("0xf%9"))))),
+            number(15.5625)
+        );
+        assert_eq!(
+            run(sexpr!(("string->number" #(string("-10"))))),
+            number(-16.)
+        );
+        assert_eq!(run(sexpr!(("string->number" #(string("%8"))))), number(0.5));
+        for not_number in ["", "-", "0x", "1%", "0xF", "1a", "0x1%%1"] {
+            assert_eq!(
+                run(sexpr!(("string->number" #(string(not_number))))),
+                boolean(false),
+                "{not_number}"
+            );
+        }
+        assert_eq!(
+            run(sexpr!(("string-split" #(string("a, b,, ç")) #(string(","))))),
+            Values::Single(list!(string("a"), string(" b"), string(""), string(" ç")))
+        );
+        assert_eq!(
+            run(sexpr!(("string-join" (list #(string("a")) #(string("é"))) #(string(", "))))),
+            result("a, é")
+        );
+        assert_eq!(
+            run(sexpr!(("string-join" (list) #(string(","))))),
+            result("")
+        );
+        let mut expander = Expander::new();
+        assert!(expander
+            .run_program(vec![sexpr!((substring #(string("abc")) 2 1))])
+            .is_err());
+        assert!(expander
+            .run_program(vec![sexpr!(("string-ref" #(string("abc")) 3))])
+            .is_err());
+        assert!(expander
+            .run_program(vec![
+                sexpr!(("string-split" #(string("abc")) #(string(""))))
+            ])
+            .is_err());
+    }
+
+    #[test]
+    fn expander_test_hash_tables() {
+        let run = |expr| Expander::new().eval_expression(expr, None);
+        let number = |n| Values::Single(Ast::Number(n));
+        let string = |s: &str| Ast::String(s.into());
+        // keys are the same key when their contents are
+        let expr = sexpr!(
+            ("let-values" (((h) ("make-hash")))
+                (begin
+                    ("hash-set!" h (list 1 2) 1)
+                    ("hash-set!" h #(string("a")) 2)
+                    ("hash-set!" h (list 1 2) 3)
+                    (list ("hash-ref" h (list 1 2)) ("hash-ref" h #(string("a"))) ("hash-count" h))))
+        );
+        assert_eq!(
+            run(expr),
+            Values::Single(list!(Ast::Number(3.), Ast::Number(2.), Ast::Number(2.)))
+        );
+        let expr = sexpr!(
+            ("let-values" (((h) ("make-hash")))
+                (begin
+                    ("hash-set!" h 1 2)
+                    ("hash-remove!" h 1)
+                    ("hash-remove!" h 5)
+                    ("hash-ref" h 1 0)))
+        );
+        assert_eq!(run(expr), number(0.));
+        // copies of a table share it
+        let expr = sexpr!(
+            ("let-values" (((h) ("make-hash")))
+                ("let-values" (((g) h))
+                    (begin ("hash-set!" g (quote a) 1) ("hash-keys" h))))
+        );
+        assert_eq!(run(expr), Values::Single(list!(Ast::Symbol("a".into()))));
+        let expr = sexpr!(
+            ("let-values" (((h) ("make-hash")))
+                (begin
+                    ("hash-set!" h 1 1)
+                    ("hash-set!" h 2 2)
+                    ("hash-keys" h)))
+        );
+        let Values::Single(keys) = run(expr) else {
+            panic!("expected a list")
+        };
+        let keys = keys.to_list_checked::<Error>().unwrap();
+        assert!(
+            keys.len() == 2 && keys.contains(&Ast::Number(1.)) && keys.contains(&Ast::Number(2.))
+        );
+        let mut expander = Expander::new();
+        assert!(expander
+            .run_program(vec![sexpr!(("hash-ref" ("make-hash") 1))])
+            .is_err());
+        assert!(expander
+            .run_program(vec![sexpr!(("hash-set!" 1 1 1))])
+            .is_err());
+    }
+
+    #[test]
+    fn expander_test_begin0() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            ("let-values" (((x) 1)) (cons (begin0 x ("set-bang" x 2)) x))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(Ast::Number(1.); Ast::Number(2.)))),
+        );
+    }
+
+    #[test]
+    fn expander_test_skip_outside_loop() {
+        let mut expander = Expander::new();
+        assert!(expander
+            .expand_expression(sexpr!((skip)))
+            .is_err_and(|e| matches!(e, Error::SkipOutsideLoop(_))));
+        // a function in the loop body is not the loop body
+        assert!(expander
+            .expand_expression(sexpr!((loop (lambda (0) (lambda (0) (skip))))))
+            .is_err_and(|e| matches!(e, Error::SkipOutsideLoop(_))));
+        // neither is a function made while making the producer
+        assert!(expander
+            .expand_expression(sexpr!((loop ((lambda (1) (param 0)) (lambda (0) (skip))))))
+            .is_err_and(|e| matches!(e, Error::SkipOutsideLoop(_))));
+        assert!(expander
+            .expand_expression(sexpr!(
+                (loop ("let-values" (((f) (lambda (0) (skip)))) (lambda (0) (f))))
+            ))
+            .is_err_and(|e| matches!(e, Error::SkipOutsideLoop(_))));
+        assert!(expander
+            .expand_expression(sexpr!((loop ("case-lambda" ((0) (skip))))))
+            .is_ok());
+    }
+
+    #[test]
+    fn expander_test_while() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            ("let-values" (((xs) (quote (1 2 3))) ((seen) (quote ())))
+                (begin
+                    (while ("pair?" xs) ("set-bang" seen (cons (car xs) seen)) ("set-bang" xs (cdr xs)))
+                    seen))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                Ast::Number(3.),
+                Ast::Number(2.),
+                Ast::Number(1.)
+            ))),
+        );
+    }
+
+    #[test]
+    fn expander_test_for_goes_through_trees() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            ("let-values" (((seen) (quote ())))
+                (begin (for x (quote (1 (2 3) 4)) ("set-bang" seen (cons x seen))) seen))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                Ast::Number(4.),
+                Ast::Number(3.),
+                Ast::Number(2.),
+                Ast::Number(1.)
+            ))),
+        );
+    }
+
+    #[test]
+    fn expander_test_class() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            ("let-values" ()
+                (class point (x) (y) (getY (lambda (0) y)))
+                ((getY (point 1 2))))
+        );
+        expander.eval_expression(expr, Some(Values::Single(Ast::Number(2.))));
+    }
+
+    #[test]
+    fn expander_test_define_function_shorthand() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            ("let-values" () (define (first 2) (param 0)) (define x 5) (first x 6))
+        );
+        expander.eval_expression(expr, Some(Values::Single(Ast::Number(5.))));
+    }
+
+    #[test]
+    fn expander_test_let_star() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(("let*" ((x (quote (1))) (y (cons 2 x))) y));
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(Ast::Number(2.), Ast::Number(1.)))),
+        );
+    }
+
+    #[test]
+    fn expander_test_cond_and_case() {
+        let mut expander = Expander::new();
+        let expr = sexpr!((cond (#(Ast::Boolean(Boolean::False)) 1) ((quote 2)) (else 3)));
+        expander.eval_expression(expr, Some(Values::Single(Ast::Number(2.))));
+        let expr = sexpr!((case (quote b) ((a) 1) ((b c) 2) (else 3)));
+        expander.eval_expression(expr, Some(Values::Single(Ast::Number(2.))));
+        let expr = sexpr!((case (quote d) ((a) 1) (else 3)));
+        expander.eval_expression(expr, Some(Values::Single(Ast::Number(3.))));
+        // case compares with equal?, so a list datum matches a list made at run time
+        let expr = sexpr!((case (cons 1 (quote (2))) (((1 2)) 1) (else 3)));
+        expander.eval_expression(expr, Some(Values::Single(Ast::Number(1.))));
+    }
+
+    #[test]
+    fn expander_test_or_does_not_capture() {
+        let mut expander = Expander::new();
+        // the temporary or binds for its first expression is not the t of the use site
+        let expr = sexpr!((let ((t 5)) (or #(Ast::Boolean(Boolean::False)) t)));
+        expander.eval_expression(expr, Some(Values::Single(Ast::Number(5.))));
+        // and a local else is not an else clause
+        let expr = sexpr!((let ((else #(Ast::Boolean(Boolean::False)))) (cond (else 1))));
+        expander.eval_expression(expr, Some(Values::Single(Ast::TheEmptyList)));
+    }
+
+    #[test]
+    fn expander_test_case_lambda_dispatches_on_argument_count() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            (let ((f ("case-lambda" ((1) (quote one)) ((3) (quote three)))))
+                (cons (f 1) (cons (f 1 2 3) (cons ((f 1 2) 3) (quote ())))))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                Ast::from("one"),
+                Ast::from("three"),
+                Ast::from("three")
+            ))),
+        );
+        let expr = sexpr!((("case-lambda" ((1) (quote one)) ((1 *) (param 1))) 5 6 7));
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(Ast::Number(6.), Ast::Number(7.)))),
+        );
+    }
+
+    #[test]
+    fn expander_test_continuation_marks() {
+        let mut expander = Expander::new();
+        // the body is in tail position, so a mark in it replaces the mark with the same key
+        let expr = sexpr!(
+            ("with-continuation-mark" (quote k) 1
+                ("with-continuation-mark" (quote j) 2
+                    ("with-continuation-mark" (quote k) 3 ("current-continuation-marks"))))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                list!(Ast::from("j"); Ast::Number(2.)),
+                list!(Ast::from("k"); Ast::Number(3.))
+            ))),
+        );
+        // but a mark that is not in tail position is kept
+        let expr = sexpr!(
+            ("with-continuation-mark" (quote k) 1
+                (car (cons ("with-continuation-mark" (quote k) 2 ("current-continuation-marks")) 3)))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                list!(Ast::from("k"); Ast::Number(2.)),
+                list!(Ast::from("k"); Ast::Number(1.))
+            ))),
+        );
+        // so a function that calls itself in the body of a mark only keeps the last mark
+        let expr = sexpr!(
+            ("letrec-values" (((f) (lambda (1)
+                    (if ("=" (param 0) 0)
+                        ("current-continuation-marks")
+                        ("with-continuation-mark" (quote k) (param 0) (f ("-" (param 0) 1)))))))
+                (f 3))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                list!(Ast::from("k"); Ast::Number(1.))
+            ))),
+        );
+        // marks are seen by called functions, and are gone once the body is done
+        let expr = sexpr!(
+            (let ((f (lambda (0) ("current-continuation-marks"))))
+                (begin
+                    ("with-continuation-mark" 1 2 (f))
+                    (cons ("with-continuation-mark" 1 2 (f)) (f))))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                list!(list!(Ast::Number(1.); Ast::Number(2.)));
+                Ast::TheEmptyList
+            ))),
+        );
+        // stopping out of the body still removes the mark
+        let expr = sexpr!(
+            (cons
+                (loop (lambda (0) ("with-continuation-mark" 1 2 (stop ("current-continuation-marks")))))
+                ("current-continuation-marks"))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(
+                list!(list!(Ast::Number(1.); Ast::Number(2.)));
+                Ast::TheEmptyList
+            ))),
+        );
+    }
+
+    #[test]
+    fn expander_test_top_level_definitions() {
+        let mut expander = Expander::new();
+        let v = expander.eval_top_level(vec![
+            sexpr!((define (last 1)
+                (if ("null?" (cdr (param 0))) (car (param 0)) (last (cdr (param 0)))))),
+            sexpr!(("define-syntaxes" (five) (lambda (1) ("quote-syntax" (quote 5))))),
+            sexpr!((cons (five) (last (quote (1 2 3))))),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(list!(Ast::Number(5.); Ast::Number(3.)))));
+        // a macro can be redefined as a variable, and a variable can be defined again
+        let v = expander.eval_top_level(vec![
+            sexpr!(("define-syntaxes" (five) (lambda (1) ("quote-syntax" (quote 5))))),
+            sexpr!((define five 6)),
+            sexpr!((define five (cons five (quote ())))),
+            sexpr!(five),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(list!(Ast::Number(6.)))));
+        assert!(expander
+            .eval_top_level(vec![sexpr!((define car 1))])
+            .is_err());
+        assert!(expander
+            .eval_top_level(vec![sexpr!((if 1 (define x 1) 2))])
+            .is_err());
+    }
+
+    #[test]
+    fn expander_test_top_level_macro_definitions_are_hygienic() {
+        let mut expander = Expander::new();
+        let v = expander.eval_top_level(vec![
+            sexpr!((define tmp 2)),
+            sexpr!(("define-syntaxes" (deftmp)
+                (lambda (1) ("quote-syntax" (begin (define tmp 1) tmp))))),
+            sexpr!((deftmp)),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::Number(1.))));
+        // the tmp defined by the macro does not replace the one defined outside of it
+        let v = expander.eval_top_level(vec![sexpr!(tmp)]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::Number(2.))));
+    }
+
+    #[test]
+    fn expander_test_begin_for_syntax() {
+        let mut expander = Expander::new();
+        let helpers = || {
+            sexpr!(("define-for-syntax" (quoted 1)
+                ("datum-to-syntax" ("quote-syntax" here) (list ("quote-syntax" quote) (param 0)))))
+        };
+        let macro_using_helper = || {
+            sexpr!(("define-syntaxes" (quote_second)
+                (lambda (1) (quoted (car (cdr ("syntax-e" (param 0))))))))
+        };
+        let v = expander.eval_top_level(vec![
+            helpers(),
+            macro_using_helper(),
+            sexpr!((quote_second x)),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::from("x"))));
+        // run time code cannot use what is defined for syntax
+        assert!(expander
+            .eval_top_level(vec![helpers(), sexpr!((quoted 1))])
+            .is_err());
+        // and transformers cannot use run time definitions
+        assert!(expander
+            .eval_top_level(vec![
+                sexpr!((define y 1)),
+                sexpr!(("define-syntaxes" (m) (lambda (1) y))),
+            ])
+            .is_err());
+        let v = expander.eval_top_level(vec![
+            sexpr!((module m #(helpers()) #(macro_using_helper())
+                (provide ("rename-out" (quote_second quote_it))))),
+            sexpr!((require m)),
+            sexpr!((quote_it y)),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::from("y"))));
+    }
+
+    #[test]
+    fn expander_test_macro_stepper() {
+        let mut expander = Expander::new();
+        expander.start_stepping();
+        expander.eval_expression(
+            sexpr!((and 1 (or 2))),
+            Some(Values::Single(Ast::Number(2.))),
+        );
+        let steps = expander.take_steps();
+        assert_eq!(
+            steps
+                .iter()
+                .map(|step| step.name.to_string())
+                .collect::<Vec<_>>(),
+            vec!["and", "and", "or"]
+        );
+        assert_eq!(steps[2].output.clone().syntax_to_datum(), Ast::Number(2.));
+        assert!(steps.iter().all(|step| step.use_site_scope.is_none()));
+        // nothing is recorded once the steps are taken
+        expander.eval_expression(sexpr!((and 1)), None);
+        assert!(expander.take_steps().is_empty());
+        // steps are named by the binding the macro resolved to, not by the name it was used with,
+        // and are given to `on_step` as they are recorded
+        let seen = Rc::new(RefCell::new(vec![]));
+        expander.on_step({
+            let seen = seen.clone();
+            move |i, step| seen.borrow_mut().push((i, step.name.to_string()))
+        });
+        expander.start_stepping();
+        let v = expander.eval_top_level(vec![
+            sexpr!((module m ("define-syntaxes" (second) (lambda (1) (car (cdr ("syntax-e" (param 0))))))
+                (provide second))),
+            sexpr!((require ("prefix-in" "m:" m))),
+            sexpr!(("m:second" 1 2)),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(Ast::Number(1.))));
+        let steps = expander.take_steps();
+        assert_eq!(steps.last().unwrap().name.to_string(), "m#second");
+        assert_eq!(
+            *seen.borrow(),
+            steps
+                .iter()
+                .enumerate()
+                .map(|(i, step)| (i + 1, step.name.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn expander_test_resolution_shows_candidates() {
+        let mut expander = Expander::new();
+        let e = expander.expand_expression(sexpr!(nope)).unwrap_err();
+        let resolution = Expander::error_resolution(&e).unwrap();
+        assert!(matches!(resolution.binding, Err(Error::FreeVariable(_))));
+        assert!(resolution.candidates.is_empty());
+
+        let a = UniqueNumberManager::new_scope();
+        let b = UniqueNumberManager::new_scope();
+        let x = |scopes: &[&Scope]| {
+            Syntax(
+                Symbol::from("x"),
+                scopes.iter().copied().cloned().collect(),
+                SourceLocation::default(),
+                Properties::new(),
+            )
+        };
+        Expander::add_binding(x(&[&a]), Binding::Local("x1".into())).unwrap();
+        Expander::add_binding(x(&[&b]), Binding::Local("x2".into())).unwrap();
+        let resolution = Expander::resolution(x(&[&a, &b]));
+        assert!(matches!(
+            resolution.binding,
+            Err(Error::AmbiguousBinding(_))
+        ));
+        assert_eq!(resolution.candidates.len(), 2);
+        let resolution = Expander::resolution(x(&[&a]));
+        assert_eq!(resolution.binding.ok(), Some(Binding::Local("x1".into())));
+    }
+
+    #[test]
+    fn expander_test_syntax_properties() {
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            (cons
+                ("syntax-property"
+                    ("syntax-property" ("quote-syntax" x) (quote color) (quote red))
+                    (quote color))
+                ("syntax-property" ("quote-syntax" x) (quote color)))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(
+                list!(Ast::from("red"); Ast::Boolean(Boolean::False)),
+            )),
+        );
+        let named_by = |e: Result<Values, Error>, name: &str| {
+            e.is_err_and(|e| matches!(e, Error::Other(e) if e.starts_with(&format!("{name}:"))))
+        };
+        // functions are named after what they are defined as
+        let v = expander.eval_top_level(vec![sexpr!((define (f 1) (param 0))), sexpr!((f))]);
+        assert!(named_by(v, "f"));
+        // unless they are given a name with the inferred-name property
+        let v = expander.eval_top_level(vec![
+            sexpr!(("define-syntaxes" (named) (lambda (1)
+                ("syntax-property"
+                    ("quote-syntax" (lambda (1) (param 0)))
+                    (quote "inferred-name")
+                    (quote g))))),
+            sexpr!(("let-values"(((h)(named)))(h))),
+        ]);
+        assert!(named_by(v, "g"));
+    }
+
+    #[test]
+    fn expander_test_identifier_comparison_primitives() {
+        let mut expander = Expander::new();
+        let true_false = || {
+            Some(Values::Single(list!(
+                Ast::Boolean(Boolean::True);
+                Ast::Boolean(Boolean::False)
+            )))
+        };
+        // else is only else when it is not shadowed
+        let expr = sexpr!(
+            ("letrec-syntaxes+values"
+                (((is_else) (lambda (1)
+                    ("datum-to-syntax"
+                        ("quote-syntax" here)
+                        ("free-identifier=?"
+                            (car (cdr ("syntax-e" (param 0))))
+                            ("quote-syntax" else))))))
+                ()
+                (cons (is_else else) ("let-values" (((else) 1)) (is_else else))))
+        );
+        expander.eval_expression(expr, true_false());
+        // an identifier from the use site has a scope that the macro's own x does not have, so they
+        // are not bound-identifier=?, but as they are both unbound and named x they are
+        // free-identifier=?
+        let expr = sexpr!(
+            ("letrec-syntaxes+values"
+                (((same_x) (lambda (1)
+                    ("let-values" (((arg) (car (cdr ("syntax-e" (param 0))))))
+                        ("datum-to-syntax"
+                            ("quote-syntax" here)
+                            (list
+                                ("quote-syntax" cons)
+                                ("free-identifier=?" arg ("quote-syntax" x))
+                                ("bound-identifier=?" arg ("quote-syntax" x))))))))
+                ()
+                (same_x x))
+        );
+        expander.eval_expression(expr, true_false());
+        // temporaries are fresh, so they can be bound in the output of a macro without capturing
+        let expr = sexpr!(
+            ("letrec-syntaxes+values"
+                (((five) (lambda (1)
+                    ("let-values" (((t) (car ("generate-temporaries" (quote (x))))))
+                        ("datum-to-syntax"
+                            ("quote-syntax" here)
+                            (list
+                                ("quote-syntax" "let-values")
+                                (list (list (list t) ("quote-syntax" 5)))
+                                t))))))
+                ()
+                ("let-values" (((x) 4)) (cons (five) x)))
+        );
+        expander.eval_expression(
+            expr,
+            Some(Values::Single(list!(Ast::Number(5.); Ast::Number(4.)))),
+        );
+        let expr = sexpr!(
+            ("let-values" (((ts) ("generate-temporaries" (quote (x x)))))
+                ("bound-identifier=?" (car ts) (car (cdr ts))))
+        );
+        expander.eval_expression(expr, Some(Values::Single(Ast::Boolean(Boolean::False))));
+    }
+
+    #[test]
+    fn expander_test_hygienic_labels() {
+        let mut expander = Expander::new();
+        let label = |name: &str| Ast::Label(name.into());
+        // each use of mark introduces its own @loop, which is neither the user's @loop nor the
+        // @loop of the other use, but the user's @loop is the same label wherever it is
+        let expr = sexpr!(
+            ("letrec-syntaxes+values"
+                (((mark) (lambda (1) ("quote-syntax" #(label("loop"))))))
+                ()
+                (link #(label("loop")) #(label("again")))
+                (list #(label("loop")) (mark) (mark) ("let-values" () #(label("loop")))))
+        );
+        let (_, v) = expander.compile_eval_expression(expr);
+        let Values::Single(v) = v else {
+            panic!("expected a single value, got {v:?}")
+        };
+        let labels = v
+            .to_list_checked::<Error>()
+            .unwrap()
+            .into_iter()
+            .map(|l| match l {
+                Ast::Label(l) => Label(l),
+                l => panic!("expected a label, got {l}"),
+            })
+            .collect::<Vec<_>>();
+        let [user, first_mark, second_mark, nested_user] = &labels[..] else {
+            panic!("expected four labels, got {labels:?}")
+        };
+        assert_eq!(user, nested_user);
+        assert_ne!(user, first_mark);
+        assert_ne!(user, second_mark);
+        assert_ne!(first_mark, second_mark);
+        // the link is keyed by the resolved labels, @again is not in the list as reaching it would
+        // jump to @loop, but as it is the user's it resolves the same without any scopes
+        let again = expander
+            .resolve_label(&Syntax(
+                "again".into(),
+                Default::default(),
+                SourceLocation::default(),
+                Properties::new(),
+            ))
+            .unwrap();
+        assert_eq!(expander.links.get(user), Some(&vec![again]));
+    }
+
+    #[test]
+    fn expander_test_goto() {
+        let label = |name: &str| Ast::Label(name.into());
+        let numbers = |ns: &[f64]| list_to_cons(ns.iter().map(|n| Ast::Number(*n)), |x| x);
+        // @y jumps out of the if and past the next top level form to @x, and @enter jumps into the
+        // then branch of an if whose test was never evaluated
+        let mut expander = Expander::new();
+        let v = expander.run_program(vec![
+            sexpr!(("define-values"(out)(quote()))),
+            sexpr!((link #(label("x")) #(label("y")))),
+            sexpr!((link #(label("inside")) #(label("enter")))),
+            sexpr!((if #(Ast::Boolean(Boolean::True))
+                (begin #(label("y")) ("set-bang" out (cons 1 out)))
+                ("set-bang" out (cons 2 out)))),
+            sexpr!(("set-bang" out (cons 3 out))),
+            sexpr!(#(label("x"))),
+            sexpr!(("set-bang" out (cons 4 out))),
+            sexpr!(#(label("enter"))),
+            sexpr!((if #(Ast::Boolean(Boolean::False))
+                (begin #(label("inside")) ("set-bang" out (cons 5 out)))
+                ("set-bang" out (cons 6 out)))),
+            sexpr!(out),
+        ]);
+        assert!(v.is_ok_and(|v| v == Values::Single(numbers(&[5., 4.]))));
+        // jumping back to the start of a function body is a loop, and the jump keeps the
+        // envoirnment of the let it is in
+        let mut expander = Expander::new();
+        let expr = sexpr!(
+            ((lambda (1)
+                ("let-values" (((xs) (param 0)) ((acc) (quote ())))
+                    (link #(label("top")) #(label("again")))
+                    #(label("top"))
+                    (if ("null?" xs)
+                        (quote ())
+                        (begin
+                            ("set-bang" acc (cons (car xs) acc))
+                            ("set-bang" xs (cdr xs))
+                            #(label("again"))))
+                    acc))
+             (quote (1 2 3)))
+        );
+        expander.eval_expression(expr, Some(Values::Single(numbers(&[3., 2., 1.]))));
+    }
+
+    #[test]
+    fn expander_test_links_per_expander() {
+        let label = |name: &str| Ast::Label(name.into());
+        let mut expander = Expander::new();
+        expander.compile_eval_expression(sexpr!(
+            (begin (link #(label("end")) #(label("skip"))) #(label("end")))
+        ));
+        let [skip] = &expander.links.values().flatten().collect::<Vec<_>>()[..] else {
+            panic!("expected one linked label, got {:?}", expander.links)
+        };
+        let skip = Ast1::Basic(Ast::Label(skip.0.clone()));
+        // the links of one expander are not jumped by another
+        let v = Expander::new().run_time_eval(skip.clone());
+        assert!(v.is_ok_and(|v| matches!(v, Values::Single(Ast::Label(_)))));
+        // and here @end is not in the program, so it cannot be jumped to
+        assert!(expander.run_time_eval(skip).is_err());
+    }
+
+    #[test]
+    fn expander_test_label_diagnostics() {
+        let label = |name: &str| Ast::Label(name.into());
+        let check = |e: Ast| {
+            let mut expander = Expander::new();
+            expander.compile_eval_expression(e);
+            expander.check_labels()
+        };
+        let errors = |e: Ast| match check(e) {
+            Err(Error::InvalidLabels(InvalidLabels(diagnostics))) => diagnostics,
+            v => panic!("expected invalid labels, got {v:?}"),
+        };
+        let v = check(
+            sexpr!((begin (link #(label("end")) #(label("skip"))) #(label("skip")) 1 #(label("end")))),
+        );
+        assert!(v.is_ok_and(|warnings| warnings.is_empty()));
+        let v = check(sexpr!((begin #(label("lonely")) 1)));
+        assert!(v.is_ok_and(|warnings| matches!(&warnings[..], [LabelDiagnostic::NeverLinked(_)])));
+        let v = errors(sexpr!((begin (link #(label("end")) #(label("skip"))) #(label("end")))));
+        assert!(matches!(&v[..], [LabelDiagnostic::NeverPlaced(site)] if &*site.name == "skip"));
+        let v = errors(sexpr!(
+            (begin (link #(label("end")) #(label("skip"))) #(label("skip")) #(label("end")) #(label("end")))
+        ));
+        assert!(matches!(&v[..], [LabelDiagnostic::PlacedTwice(..)]));
+        let v = errors(sexpr!(
+            (begin
+                (link #(label("end")) #(label("skip")))
+                (link #(label("end")) #(label("other")))
+                #(label("skip"))
+                #(label("other"))
+                #(label("end")))
+        ));
+        assert!(matches!(&v[..], [LabelDiagnostic::LinkedTwice(..)]));
+        // a goto cannot jump out of a function
+        let v = errors(sexpr!(
+            (begin (link #(label("end")) #(label("skip"))) (lambda (1) #(label("skip"))) #(label("end")))
+        ));
+        assert!(matches!(&v[..], [LabelDiagnostic::Unreachable { .. }]));
+        // nor into or out of a with-continuation-mark body
+        let v = errors(sexpr!(
+            (begin (link #(label("end")) #(label("skip"))) ("with-continuation-mark" 1 2 #(label("skip"))) #(label("end")))
+        ));
+        assert!(matches!(&v[..], [LabelDiagnostic::AcrossMark { .. }]));
+    }
+
+    #[test]
+    fn expander_test_label_source_location() {
+        let mut expander = Expander::new();
+        let namespace = expander.namespace();
+        let program =
+            crate::lexer::everything_parse_file("test.everything", "ln @end @skip\n@end").unwrap();
+        for e in program {
+            let e = expander.expand_expression(e).unwrap();
+            expander.compile(e, &namespace).unwrap();
+        }
+        let v = match expander.check_labels() {
+            Err(Error::InvalidLabels(InvalidLabels(diagnostics))) => diagnostics,
+            v => panic!("expected invalid labels, got {v:?}"),
+        };
+        let srcloc = SourceLocation::new("test.everything".to_string(), 1, 9);
+        assert!(matches!(&v[..], [LabelDiagnostic::NeverPlaced(site)] if site.srcloc == srcloc));
+        assert!(v[0].to_string().contains("at test.everything:1:9"));
+    }
+
+    #[test]
+    fn expander_test_expansion_cache() {
+        let program =
+            crate::lexer::everything_parse(include_str!("../examples/prelude-macros.everything"))
+                .unwrap();
+        // expanded like `run` does, so the bodies of definition contexts are finished with the core
+        // forms they were partially expanded to
+        let expand = |expansion_cache| {
+            let mut expander = Expander::new();
+            expander.expansion_cache = expansion_cache;
+            let ctx = ExpandContext::new(expander.namespace());
+            program
+                .iter()
+                .map(|e| {
+                    let e = expander
+                        .namespace_syntax_introduce(e.clone().datum_to_syntax(None, None, None));
+                    expander
+                        .expand_top_level(e, ctx.clone())
+                        .unwrap()
+                        .syntax_to_datum()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(expand(true), expand(false));
+    }
 }
@@ -40,8 +40,11 @@ pub enum CompileTimeBinding {
     // maybe this should just be Function
     // as we need to capture expander state
     CoreForm(CoreForm),
+    // a macro implemented by the expander, which is applied just like a transformer
+    Macro(Macro),
 }
 pub type CoreForm = fn(&mut Expander, Ast, ExpandContext) -> Result<Ast, Error>;
+pub type Macro = fn(&Expander, Ast) -> Result<Ast, Error>;
 #[derive(Clone, Debug)]
 pub struct CompileTimeEnvoirnment(pub(crate) HashMap<Symbol, Ast>);
 
@@ -79,6 +82,7 @@ impl CompileTimeEnvoirnment {
                 .ok_or(OutOfContext(id.to_string())),
             Binding::TopLevel(core) => Ok(ns
                 .transformers
+                .borrow()
                 .get(&core.clone().into())
                 .cloned()
                 .unwrap_or(CompileTimeBinding::Regular(Ast::Symbol(variable)))),
@@ -86,10 +90,17 @@ impl CompileTimeEnvoirnment {
     }
 }
 impl Expander {
+    // unbound identifiers are only free-identifier=? if they have the same name
     pub fn free_identifier(a: Syntax<Symbol>, b: Syntax<Symbol>) -> Result<bool, Error> {
-        let ab = Self::resolve(&a, false)?;
-        let bb = Self::resolve(&b, false)?;
-        Ok(ab == bb)
+        let binding = |id: &Syntax<Symbol>| match Self::resolve(id, false) {
+            Ok(binding) => Ok(Some(binding)),
+            Err(Error::FreeVariable(_)) => Ok(None),
+            Err(e) => Err(e),
+        };
+        Ok(match (binding(&a)?, binding(&b)?) {
+            (None, None) => a.0 == b.0,
+            (ab, bb) => ab == bb,
+        })
     }
     pub fn add_local_binding(id: Syntax<Symbol>) -> Symbol {
         let symbol = UniqueNumberManager::gen_sym(&id.0 .0);
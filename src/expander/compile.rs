@@ -1,10 +1,6 @@
 use crate::{
-    ast::{
-        ast1::{Ast1, Label},
-        Param,
-    },
+    ast::{ast1::Ast1, Arity, Param},
     error::Error,
-    matches_to,
 };
 use std::{mem, rc::Rc};
 
@@ -15,7 +11,11 @@ use crate::{
     evaluator::{Evaluator, Values},
 };
 
-use super::{binding::Binding, namespace::NameSpace, Expander};
+use super::{
+    binding::Binding,
+    namespace::{NameSpace, Phase},
+    Expander,
+};
 
 impl Expander {
     // self is only used for envoirment
@@ -32,20 +32,37 @@ impl Expander {
                 match core_sym.to_string().as_str() {
                     "lambda" => {
                         // TODO: 0 arg lambda is currently (lambda expr) after expander
+                        let name = inferred_name(&s);
                         let m = match_syntax!(
                             (lambda formals body)
                         )(s)?;
                         let formals = Self::process_formals(m.formals)?;
-
-                        Ok(Ast1::Lambda(formals, Box::new(compile(m.body)?)))
+                        // the curried lambdas of a function all have its name
+                        let body = match &name {
+                            Some(name)
+                                if Self::core_form_symbol(m.body.clone())
+                                    .is_ok_and(|form| &*form == "lambda") =>
+                            {
+                                with_inferred_name(m.body, name)
+                            }
+                            _ => m.body,
+                        };
+                        let body = self.with_label_function(|this| this.compile(body, ns))?;
+                        Ok(Ast1::Lambda(formals, Box::new(body), name))
+                    }
+                    "case-lambda" => {
+                        let name = inferred_name(&s);
+                        let m = match_syntax!((case_lambda (formals lambda) ...))(s)?;
+                        Ast::map2_to_list(m.formals, m.lambda, |formals, lambda| {
+                            let (argc, variadic) = Self::lambda_formals(&formals)?;
+                            let lambda = match &name {
+                                Some(name) => with_inferred_name(lambda, name),
+                                None => lambda,
+                            };
+                            compile(lambda).map(|lambda| (Arity(argc.0, variadic), lambda))
+                        })
+                        .map(Ast1::CaseLambda)
                     }
-                    // "case-lambda" => {
-                    //     let m = match_syntax!( (case_lambda (formals body) ...))(s)?;
-                    //     Ast::map2(m.formals, m.body, |formals, body| {
-                    //         self.compile_lambda(formals, body, ns)
-                    //     })
-                    //     .map(|cases| sexpr!(("case-lambda". #(cases))))
-                    // }
                     "#%app" => {
                         let m = match_syntax!((app.rest))(s)?;
                         if let Ast::Pair(p) = m.rest {
@@ -69,18 +86,20 @@ impl Expander {
                         ))
                     }
 
-                    // "with-continuation-mark" => {
-                    //     let m = match_syntax!(
-                    //         // TODO: should this match with-continuation-mark as opposed to if?
-                    //     (with_continuation_mark key val body)
-                    //     )(s)?;
-                    //     Ok(list!(
-                    //         "with-continuation-mark".into(),
-                    //         compile(m.key)?,
-                    //         compile(m.val)?,
-                    //         compile(m.body)?,
-                    //     ))
-                    // }
+                    "with-continuation-mark" => {
+                        let m = match_syntax!(
+                            (with_continuation_mark key val body)
+                        )(s)?;
+                        let key = compile(m.key)?;
+                        let val = compile(m.val)?;
+                        let body = self.with_label_mark_body(|this| this.compile(m.body, ns))?;
+                        Ok(Ast1::WithContinuationMark(
+                            Box::new(key),
+                            Box::new(val),
+                            Box::new(body),
+                        ))
+                    }
+                    "current-continuation-marks" => Ok(Ast1::CurrentContinuationMarks),
                     // maybe begin0 is if its gen-symed (at a sybmol level)
                     "begin" => {
                         let m = match_syntax!( (begin e ..+))(s)?;
@@ -136,23 +155,55 @@ impl Expander {
                                 ...
                             )
                         )(s)?;
-                        let filter_label = |l: Ast| {
-                            let l = l.unsyntax();
-                            matches_to!(l => Ast::Label |format!("not a label: {l}") ).map(Label)
+                        let filter_label = |l: Ast| -> Result<Syntax<Rc<str>>, Error> {
+                            let Ast::Syntax(l) = l else {
+                                Err(format!("not a label: {l}"))?
+                            };
+                            let Ast::Label(ref label) = l.0 else {
+                                Err(format!("not a label: {l}"))?
+                            };
+                            Ok(l.with_ref(label.clone()))
                         };
                         let var_name = format!("not a list of labels {}", m.src_labels);
-                        let dest = m
+                        let srcs = m
                             .src_labels
                             .map_to_list_checked(filter_label)
-                            .map_err(|e| e.unwrap_or(var_name))?;
-                        let src = filter_label(m.dest_label)?;
-                        self.links.insert(src, dest);
+                            .map_err(|e| e.unwrap_or_else(|| var_name.into()))?;
+                        let dest = filter_label(m.dest_label)?;
+                        self.link_labels(&dest, &srcs)?;
                         Ok(Ast1::Application(
                             Box::new(Ast1::Basic(Ast::Symbol("values".into()))),
                             vec![],
                         ))
                     }
-                    "module" => todo!(),
+                    "module" => {
+                        let m = match_syntax!((module name body ...))(s)?;
+                        let mut body = m
+                            .body
+                            .map_to_list_checked(compile)
+                            .map_err(|e| e.unwrap_or("not a list".into()))?;
+                        // a module only runs its definitions and expressions, it has no value
+                        body.push(Ast1::Application(
+                            Box::new(Ast1::Basic(Ast::Symbol("values".into()))),
+                            vec![],
+                        ));
+                        Ok(Ast1::Begin(body))
+                    }
+                    "define-values" => {
+                        let m = match_syntax!((define_values (id ...) rhs))(s)?;
+                        let ids =
+                            m.id.map_to_list_checked(|id| {
+                                Self::resolve(&id.try_into()?, false).map(|b| Symbol::from(b).0)
+                            })
+                            .map_err(|e| e.unwrap_or("not a list".into()))?;
+                        let rhs = infer_name(m.rhs, &m.id);
+                        Ok(Ast1::DefineValues(ids, Box::new(compile(rhs)?)))
+                    }
+                    // these only matter at expansion time
+                    "require" | "define-syntaxes" | "begin-for-syntax" => Ok(Ast1::Application(
+                        Box::new(Ast1::Basic(Ast::Symbol("values".into()))),
+                        vec![],
+                    )),
                     "stop" => match_syntax!((stop))(s.clone())
                         .map(|_| Ast1::Stop(None))
                         .map_err(std::convert::Into::into)
@@ -163,7 +214,7 @@ impl Expander {
                                     compile(m.expr).map(Box::new).map(Some).map(Ast1::Stop)
                                 })
                         }),
-                    "skip" => todo!(),
+                    "skip" => Ok(Ast1::Skip),
                     "loop" => {
                         let m = match_syntax!((r#loop producer))(s)?;
                         Ok(Ast1::Loop(Box::new(compile(m.producer)?)))
@@ -172,6 +223,9 @@ impl Expander {
                 }
             }
             Ast::Symbol(ref s1) => Self::compile_identifier(&syntax.with_ref(s1.clone()), ns),
+            Ast::Label(ref l) => self
+                .place_label(&syntax.with_ref(l.clone()))
+                .map(|l| Ast1::Basic(Ast::Label(l.0))),
             _ => Err(format!("bad syntax after expansion {s} compile").into()),
         }
     }
@@ -241,6 +295,7 @@ impl Expander {
             Ast1::LetValues
         };
         Ast::map2_to_list(idss, m.rhs, |ids, rhs| {
+            let rhs = infer_name(rhs, &ids);
             ids.map_to_list_checked(|id| Self::local_symbol(&id.try_into()?).map(|i| i.0))
                 .map_err(|e| e.unwrap_or("not a list".into()))
                 .and_then(|ids| self.compile(rhs.clone(), ns).map(|rhs| (ids, rhs)))
@@ -258,33 +313,103 @@ impl Expander {
         Ok(key_to_symbol(s))
     }
 
+    // only the links made by this expander are jumped by what it evaluates
     pub fn expand_time_eval(&self, compiled: Ast1) -> Result<Values, Error> {
-        Evaluator::eval(compiled, self.expand_time_env.clone())
+        Evaluator::with_links(&self.links, || {
+            Evaluator::eval(compiled, self.expand_time_env.clone())
+        })
     }
+    // the values of the program are printed, so they are forced
     pub fn run_time_eval(&self, compiled: Ast1) -> Result<Values, Error> {
-        Evaluator::eval(compiled, self.run_time_env.clone())
+        Evaluator::with_links(&self.links, || {
+            Evaluator::with_strategy(self.strategy, || {
+                Evaluator::eval_program(compiled, self.run_time_env.clone())
+                    .and_then(Evaluator::force_values)
+            })
+        })
     }
     pub fn expand_time_eval_single(&self, compiled: Ast1) -> Result<Ast, Error> {
-        Evaluator::eval_single_value(compiled, self.expand_time_env.clone())
+        Evaluator::with_links(&self.links, || {
+            Evaluator::eval_single_value(compiled, self.expand_time_env.clone())
+        })
     }
     pub fn run_time_eval_single(&self, compiled: Ast1) -> Result<Ast, Error> {
-        Evaluator::eval_single_value(compiled, self.run_time_env.clone())
+        Evaluator::with_links(&self.links, || {
+            Evaluator::with_strategy(self.strategy, || {
+                Evaluator::eval_single_value(compiled, self.run_time_env.clone())
+                    .and_then(Evaluator::force)
+            })
+        })
     }
     fn compile_identifier(s: &Syntax<Symbol>, ns: &NameSpace) -> Result<Ast1, Error> {
         let with = s;
         let b = Self::resolve(with, false)?;
         match b {
             Binding::Local(b) => Ok(Ast1::Basic(Ast::Symbol(key_to_symbol(b)))),
-            Binding::TopLevel(s) => ns
-                .variables
-                .get(&s.clone().into())
-                .ok_or(format!("missing core bindig for primitive {s}").into())
-                .cloned()
-                .map(Ast1::Basic),
+            Binding::TopLevel(s) => {
+                if let Some(primitive) = ns.variables.borrow().get(&s.clone().into()) {
+                    Ok(Ast1::Basic(primitive.clone()))
+                } else if ns.transformers.borrow().contains_key(&s.clone().into()) {
+                    Err(format!("missing core bindig for primitive {s}").into())
+                } else if ns
+                    .other_phase_variables
+                    .borrow()
+                    .contains_key(&s.clone().into())
+                {
+                    Err(match ns.phase {
+                        Phase::RunTime => format!("{s} is only defined for syntax"),
+                        Phase::ForSyntax => format!("{s} is only defined at run time"),
+                    }
+                    .into())
+                } else {
+                    // a definition (even one whose value is not evaluated yet) is recorded in the
+                    // namespace as soon as its name is bound, so anything else was never defined
+                    // in this namespace
+                    Err(format!("unbound identifier {s}").into())
+                }
+            }
         }
     }
 }
 
+/// The syntax property that names a function, used in error messages.
+pub const INFERRED_NAME: &str = "inferred-name";
+
+fn inferred_name(s: &Ast) -> Option<Symbol> {
+    match s {
+        Ast::Syntax(syntax) => match syntax.3.get(&INFERRED_NAME.into()) {
+            Some(Ast::Symbol(name)) => Some(name.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// gives s the inferred-name property, unless it already has one
+fn with_inferred_name(s: Ast, name: &Symbol) -> Ast {
+    match s {
+        Ast::Syntax(mut syntax) => {
+            syntax
+                .3
+                .entry(INFERRED_NAME.into())
+                .or_insert_with(|| Ast::Symbol(name.clone()));
+            Ast::Syntax(syntax)
+        }
+        s => s,
+    }
+}
+
+// the right hand side of a definition of a single identifier is named after it
+fn infer_name(rhs: Ast, ids: &Ast) -> Ast {
+    match ids.clone().unsyntax() {
+        Ast::Pair(p) if p.1 == Ast::TheEmptyList => match Syntax::<Symbol>::try_from(p.0) {
+            Ok(id) => with_inferred_name(rhs, &id.0),
+            Err(_) => rhs,
+        },
+        _ => rhs,
+    }
+}
+
 const fn key_to_symbol(key: Symbol) -> Symbol {
     key
 }
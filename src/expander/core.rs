@@ -10,7 +10,7 @@ use crate::{
 };
 
 use super::{
-    binding::{Binding, CompileTimeBinding, CoreForm},
+    binding::{Binding, CompileTimeBinding, CoreForm, Macro},
     namespace::NameSpace,
     Expander,
 };
@@ -32,24 +32,37 @@ impl Expander {
         self.add_core_binding(sym.clone().into());
         self.core_forms.insert(sym, proc);
     }
+    pub fn add_core_macro(&mut self, sym: Rc<str>, proc: Macro) {
+        self.add_core_binding(sym.clone().into());
+        self.core_macros.insert(sym, proc);
+    }
     pub fn add_core_primitive(&mut self, sym: Rc<str>, proc: Ast) {
         self.add_core_binding(sym.clone().into());
         self.core_primitives.insert(sym, proc);
     }
 
     pub fn declare_core_top_level(&self, ns: &mut NameSpace) {
-        ns.transformers.extend(
+        ns.transformers.borrow_mut().extend(
             self.core_forms
                 .clone()
                 .into_iter()
                 .map(|(key, value)| (key.into(), CompileTimeBinding::CoreForm(value))),
         );
-        ns.variables.extend(
-            self.core_primitives
+        ns.transformers.borrow_mut().extend(
+            self.core_macros
                 .clone()
                 .into_iter()
-                .map(|(key, value)| (key.into(), value)),
+                .map(|(key, value)| (key.into(), CompileTimeBinding::Macro(value))),
         );
+        // the primitives can be used both at run time and for syntax
+        for variables in [&ns.variables, &ns.other_phase_variables] {
+            variables.borrow_mut().extend(
+                self.core_primitives
+                    .clone()
+                    .into_iter()
+                    .map(|(key, value)| (key.into(), value)),
+            );
+        }
     }
 
     pub fn core_form_symbol(s: Ast) -> Result<Rc<str>, Error> {
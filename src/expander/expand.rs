@@ -27,6 +27,7 @@ use super::{
     binding::{Binding, CompileTimeBinding, CompileTimeEnvoirnment},
     duplicate_check::{self, make_check_no_duplicate_table, DuplicateMap},
     expand_context::ExpandContext,
+    expand_expr::list_to_cons,
     namespace::NameSpace,
     Expander,
 };
@@ -36,6 +37,9 @@ impl Expander {
         s.add_scope(self.core_scope.clone())
     }
     pub fn expand(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
+        if self.expansion_cache && is_fully_expanded(&s) {
+            return Ok(s);
+        }
         match s.clone() {
             Ast::Syntax(syntax) => match syntax.0 {
                 Ast::Symbol(ref symbol) => {
@@ -43,6 +47,8 @@ impl Expander {
                 }
                 Ast::Pair(p) if p.0.identifier() => self.expand_id_application_form(*p, s, ctx),
                 Ast::Pair(_) | Ast::TheEmptyList => self.expand_implicit("#%app".into(), s, ctx),
+                // a label is resolved when it is compiled, as that is when every label is known
+                Ast::Label(_) => Ok(s),
                 _ => self.expand_implicit("#%datum".into(), s, ctx),
             },
             _ => self.expand_implicit("#%datum".into(), s, ctx),
@@ -62,29 +68,80 @@ impl Expander {
         let Ast::Symbol(ref id) = id_syntax.0 else {
             unreachable!();
         };
-        let binding = Self::resolve(&id_syntax.with_ref(id.clone()), false);
-        let binding = binding.and_then(|binding| self.lookup(&binding, &ctx, id));
+        let binding = Self::resolve(&id_syntax.with_ref(id.clone()), false).and_then(|binding| {
+            self.lookup(&binding, &ctx, id)
+                .map(|transformer| (binding, transformer))
+        });
         match binding {
-            Ok(binding) if !matches!(&binding, CompileTimeBinding::Regular(Ast::Symbol(sym)) if *sym == self.variable) => {
-                self.dispatch(binding, s, ctx)
+            Ok((Binding::TopLevel(form), CompileTimeBinding::CoreForm(_)))
+                if ctx.only_immediate && self.expansion_cache =>
+            {
+                Ok(mark_immediate_core_form(s, form))
+            }
+            Ok((binding, transformer)) if !matches!(&transformer, CompileTimeBinding::Regular(Ast::Symbol(sym)) if *sym == self.variable) => {
+                self.dispatch(&binding, transformer, s, ctx)
             }
             _ => self.expand_implicit("#%app".into(), s, ctx),
         }
     }
 
+    /// Partially expands `s` (until it is a core form), along with the name of the core form, so
+    /// that finishing its expansion (see [`Expander::finish_expanding`]) does not have to resolve
+    /// it again.
+    pub(crate) fn expand_immediate(
+        &mut self,
+        s: Ast,
+        ctx: ExpandContext,
+    ) -> Result<(Ast, Option<Rc<str>>), Error> {
+        let ctx = ExpandContext {
+            only_immediate: true,
+            ..ctx
+        };
+        let exp_s = self.expand(s, ctx)?;
+        // fully expanded syntax is skipped before it gets to be marked
+        if self.expansion_cache && !is_fully_expanded(&exp_s) {
+            Ok(take_immediate_core_form(exp_s))
+        } else {
+            let form = Self::core_form_symbol(exp_s.clone()).ok();
+            Ok((exp_s, form))
+        }
+    }
+
+    /// Finishes expanding what [`Expander::expand_immediate`] partially expanded to the core form
+    /// `form`.
+    pub(crate) fn finish_expanding(
+        &mut self,
+        s: Ast,
+        form: Option<&str>,
+        ctx: ExpandContext,
+    ) -> Result<Ast, Error> {
+        match form.and_then(|form| self.core_forms.get(form).copied()) {
+            Some(form) if self.expansion_cache => form(self, s, ctx).map(mark_fully_expanded),
+            _ => self.expand(s, ctx),
+        }
+    }
+
     fn expand_implicit(&mut self, sym: Symbol, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
         let scopes = s.scope_set();
         let id = sym.clone().datum_to_syntax(scopes, None, None);
-        let binding = Self::resolve(&id, false);
-        let transformer = binding.and_then(|binding| self.lookup(&binding, &ctx, &sym))?;
+        let binding = Self::resolve(&id, false)?;
+        let transformer = self.lookup(&binding, &ctx, &sym)?;
         match transformer {
             CompileTimeBinding::CoreForm(_) if ctx.only_immediate => Ok(s),
-            CompileTimeBinding::Regular(Ast::Function(_)) | CompileTimeBinding::CoreForm(_) => {
+            CompileTimeBinding::Regular(Ast::Function(_))
+            | CompileTimeBinding::CoreForm(_)
+            | CompileTimeBinding::Macro(_) => {
                 let scope_set = s.scope_set();
                 let syntax_src_loc = s.syntax_src_loc();
+                let properties = s.properties();
                 self.dispatch(
+                    &binding,
                     transformer,
-                    list!(Ast::Symbol(sym); s).datum_to_syntax(scope_set, syntax_src_loc, None),
+                    list!(Ast::Symbol(sym); s).datum_to_syntax(
+                        scope_set,
+                        syntax_src_loc,
+                        properties,
+                    ),
                     ctx,
                 )
             }
@@ -102,31 +159,48 @@ impl Expander {
             .lookup(binding, &ctx.namespace, id, self.variable.clone())
             .map_err(Error::OutOfContext)
     }
+    // the result of applying a macro, along with the introduction scope and use site scope (if
+    // any) that where added, so they can be shown by the stepper
     pub(crate) fn apply_transformer(
         m: Function,
         s: Ast,
         ctx: &ExpandContext,
-    ) -> Result<Ast, Error> {
+    ) -> Result<(Ast, Scope, Option<Scope>), Error> {
+        Self::apply_macro(
+            |s| m.apply_single(Ast::Pair(Box::new(Pair(s, Ast::TheEmptyList)))),
+            s,
+            ctx,
+        )
+    }
+    fn apply_macro(
+        m: impl FnOnce(Ast) -> Result<Ast, Error>,
+        s: Ast,
+        ctx: &ExpandContext,
+    ) -> Result<(Ast, Scope, Option<Scope>), Error> {
         let intro_scope = UniqueNumberManager::new_scope();
         let intro_s = s.add_scope(intro_scope.clone());
-        let uses_s = Self::maybe_add_use_site_scope(intro_s, ctx);
-        let transformed_s = m.apply_single(Ast::Pair(Box::new(Pair(uses_s, Ast::TheEmptyList))))?;
+        let (uses_s, use_site_scope) = Self::maybe_add_use_site_scope(intro_s, ctx);
+        let transformed_s = m(uses_s)?;
         if !matches!(transformed_s, Ast::Syntax(_)) {
             return Err(NonSyntaxTransformer(transformed_s).into());
         }
-        let result_s = transformed_s.flip_scope(intro_scope);
-        Ok(Self::maybe_add_post_site_scope(result_s, ctx))
+        let result_s = transformed_s.flip_scope(intro_scope.clone());
+        Ok((
+            Self::maybe_add_post_site_scope(result_s, ctx),
+            intro_scope,
+            use_site_scope,
+        ))
     }
 
-    fn maybe_add_use_site_scope(s: Ast, ctx: &ExpandContext) -> Ast {
+    fn maybe_add_use_site_scope(s: Ast, ctx: &ExpandContext) -> (Ast, Option<Scope>) {
         match &ctx.use_site_scopes {
             Some(scopes) => {
                 let sc = UniqueNumberManager::new_scope();
 
                 scopes.borrow_mut().insert(sc.clone());
-                s.add_scope(sc)
+                (s.add_scope(sc.clone()), Some(sc))
             }
-            None => s,
+            None => (s, None),
         }
     }
     fn maybe_add_post_site_scope(s: Ast, ctx: &ExpandContext) -> Ast {
@@ -137,8 +211,10 @@ impl Expander {
             }
         }
     }
+    // `binding` is what `t` was resolved from, which names the step when stepping
     fn dispatch(
         &mut self,
+        binding: &Binding,
         t: CompileTimeBinding,
         s: Ast,
         ctx: ExpandContext,
@@ -146,17 +222,41 @@ impl Expander {
         match t {
             CompileTimeBinding::Regular(t) => match t {
                 Ast::Function(transfromer) => {
-                    let apply_transformer = Self::apply_transformer(transfromer, s, &ctx)?;
+                    let input = self.stepping().then(|| s.clone());
+                    let (apply_transformer, intro_scope, use_site_scope) =
+                        Self::apply_transformer(transfromer, s, &ctx)?;
+                    self.introduction_scopes.insert(intro_scope.clone());
+                    self.record_step(
+                        binding.clone().into(),
+                        input,
+                        &apply_transformer,
+                        intro_scope,
+                        use_site_scope,
+                    );
                     self.expand(apply_transformer, ctx)
                 }
                 Ast::Symbol(variable) if variable == self.variable => Ok(s),
                 _ => Err(Error::IllegalUseOfSyntax(IllegalUseOfSyntax(t))),
             },
+            CompileTimeBinding::Macro(m) => {
+                let input = self.stepping().then(|| s.clone());
+                let (apply_macro, intro_scope, use_site_scope) =
+                    Self::apply_macro(|s| m(self, s), s, &ctx)?;
+                self.introduction_scopes.insert(intro_scope.clone());
+                self.record_step(
+                    binding.clone().into(),
+                    input,
+                    &apply_macro,
+                    intro_scope,
+                    use_site_scope,
+                );
+                self.expand(apply_macro, ctx)
+            }
             CompileTimeBinding::CoreForm(form) => {
                 if ctx.only_immediate {
                     Ok(s)
                 } else {
-                    form(self, s, ctx)
+                    form(self, s, ctx).map(mark_fully_expanded)
                 }
             }
         }
@@ -169,7 +269,7 @@ impl Expander {
         mut body_ctx: ExpandContext,
         ctx: ExpandContext,
         mut bodys: VecDeque<Ast>,
-        mut done_bodys: Vec<Ast>,
+        mut done_bodys: Vec<(Ast, Option<Rc<str>>)>,
         mut val_binds: Vec<(Vec<Syntax<Symbol>>, Ast)>,
         duplicate: DuplicateMap,
         original_syntax: Ast,
@@ -178,9 +278,9 @@ impl Expander {
         match bodys.pop_front() {
             None => self.finish_expanding_body(body_ctx, done_bodys, val_binds, original_syntax),
             Some(body) => {
-                let exp_body = self.expand(body, body_ctx.clone())?;
-                if let Ok(pat) = Self::core_form_symbol(exp_body.clone()) {
-                    match pat.to_string().as_str() {
+                let (exp_body, form) = self.expand_immediate(body, body_ctx.clone())?;
+                if let Some(pat) = form.as_deref() {
+                    match pat {
                         "begin" => {
                             let m = match_syntax!(
                                 (begin e ...)
@@ -271,7 +371,7 @@ impl Expander {
                             )
                         }
                         _ => {
-                            done_bodys.push(exp_body);
+                            done_bodys.push((exp_body, form));
                             self.expand_body_loop(
                                 body_ctx,
                                 ctx,
@@ -284,7 +384,7 @@ impl Expander {
                         }
                     }
                 } else {
-                    done_bodys.push(exp_body);
+                    done_bodys.push((exp_body, None));
                     self.expand_body_loop(
                         body_ctx,
                         ctx,
@@ -315,7 +415,7 @@ impl Expander {
     fn finish_expanding_body(
         &mut self,
         body_ctx: ExpandContext,
-        mut done_bodys: Vec<Ast>,
+        mut done_bodys: Vec<(Ast, Option<Rc<str>>)>,
         val_binds: Vec<(Vec<Syntax<Symbol>>, Ast)>,
         s: Ast,
     ) -> Result<Ast, Error> {
@@ -328,15 +428,17 @@ impl Expander {
             post_expansion_scope: None,
             ..body_ctx
         };
+        // the body forms were already partially expanded, so they are not resolved again
         let finish_bodys = if done_bodys.len() == 1 {
-            self.expand(done_bodys.remove(0), finish_ctx.clone())?
+            let (body, form) = done_bodys.remove(0);
+            self.finish_expanding(body, form.as_deref(), finish_ctx.clone())?
         } else {
             list!(
                 self.core_datum_to_syntax("begin".into());
                 done_bodys
                     .into_iter()
-                    .try_rfold(Ast::TheEmptyList, |exprs, expr| {
-                        self.expand(expr, finish_ctx.clone())
+                    .try_rfold(Ast::TheEmptyList, |exprs, (expr, form)| {
+                        self.finish_expanding(expr, form.as_deref(), finish_ctx.clone())
                             .map(|expr| list!(expr; exprs))
                 })?)
             .datum_to_syntax(None, None, None)
@@ -364,10 +466,10 @@ impl Expander {
             .datum_to_syntax(None, None, None))
         }
     }
-    fn no_binds(&self, done_bodys: Vec<Ast>) -> Vec<(Vec<Syntax<Symbol>>, Ast)> {
+    fn no_binds(&self, done_bodys: Vec<(Ast, Option<Rc<str>>)>) -> Vec<(Vec<Syntax<Symbol>>, Ast)> {
         done_bodys
             .into_iter()
-            .map(|body| {
+            .map(|(body, _)| {
                 (
                     vec![],
                     list!(
@@ -422,6 +524,10 @@ impl Expander {
         id_count: usize,
         ctx: ExpandContext,
     ) -> Result<(Vec<Ast>, Ast), Error> {
+        let ctx = ExpandContext {
+            namespace: ctx.namespace.for_syntax()?,
+            ..ctx
+        };
         let exp_rhs = self.expand_transformer(rhs, ctx.clone())?;
         Ok((
             self.eval_for_bindings(exp_rhs.clone(), id_count, ctx.namespace)?,
@@ -484,7 +590,10 @@ impl Expander {
         let id = s.0.clone();
         let s = Ast::Syntax(Box::new(s.with(Ast::Symbol(id.clone()))));
         match binding {
-            Ok(binding) => self.dispatch(self.lookup(&binding, &ctx, &id)?, s, ctx),
+            Ok(binding) => {
+                let transformer = self.lookup(&binding, &ctx, &id)?;
+                self.dispatch(&binding, transformer, s, ctx)
+            }
             _ => self.expand_implicit("#%top".into(), s, ctx),
         }
     }
@@ -499,6 +608,67 @@ pub fn to_id_list(ids: Ast) -> Result<Vec<Syntax<Symbol>>, Error> {
     Ok(ids)
 }
 
+/// The property that fully expanded syntax is marked with, its value is the scopes the syntax had
+/// when it was expanded.
+pub const FULLY_EXPANDED: &str = "fully-expanded";
+
+// a core form is only fully expanded once everything in it is, so its result can be marked, and
+// skipped if it is expanded again (like when a macro puts syntax that was already expanded in its
+// output)
+// if the syntax got more scopes since it was expanded, what its identifiers refer to could have
+// changed, so it has to be expanded again
+fn mark_fully_expanded(s: Ast) -> Ast {
+    match s {
+        Ast::Syntax(mut syntax) => {
+            let scopes = list_to_cons(syntax.1.iter(), |scope| Ast::from(scope.0));
+            syntax.3.insert(FULLY_EXPANDED.into(), scopes);
+            Ast::Syntax(syntax)
+        }
+        s => s,
+    }
+}
+
+// the name of the core form that partially expanded syntax is, only kept until
+// `Expander::expand_immediate` takes it off
+const IMMEDIATE_CORE_FORM: &str = "immediate-core-form";
+
+fn mark_immediate_core_form(s: Ast, form: Rc<str>) -> Ast {
+    match s {
+        Ast::Syntax(mut syntax) => {
+            syntax
+                .3
+                .insert(IMMEDIATE_CORE_FORM.into(), Ast::Symbol(form.into()));
+            Ast::Syntax(syntax)
+        }
+        s => s,
+    }
+}
+
+fn take_immediate_core_form(s: Ast) -> (Ast, Option<Rc<str>>) {
+    match s {
+        Ast::Syntax(mut syntax) => {
+            let form = match syntax.3.remove(&Symbol::from(IMMEDIATE_CORE_FORM)) {
+                Some(Ast::Symbol(form)) => Some(form.0),
+                _ => None,
+            };
+            (Ast::Syntax(syntax), form)
+        }
+        s => (s, None),
+    }
+}
+
+fn is_fully_expanded(s: &Ast) -> bool {
+    match s {
+        Ast::Syntax(syntax) => syntax
+            .3
+            .get(&Symbol::from(FULLY_EXPANDED))
+            .is_some_and(|scopes| {
+                *scopes == list_to_cons(syntax.1.iter(), |scope| Ast::from(scope.0))
+            }),
+        _ => false,
+    }
+}
+
 pub fn rebuild(s: Ast, rator: Ast) -> Ast {
     rator.datum_to_syntax(s.scope_set(), s.syntax_src_loc(), s.properties())
 }
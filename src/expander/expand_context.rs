@@ -11,6 +11,17 @@ pub struct ExpandContext {
     pub(crate) env: CompileTimeEnvoirnment,
     pub(crate) only_immediate: bool,
     pub(crate) post_expansion_scope: Option<Scope>,
+    pub(crate) loop_context: LoopContext,
+}
+
+/// Where an expression is relative to the innermost `loop`, `skip` is only allowed in the body of
+/// the function that the loop calls on each iteration.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LoopContext {
+    #[default]
+    Outside,
+    Producer,
+    Body,
 }
 
 impl ExpandContext {
@@ -21,6 +32,7 @@ impl ExpandContext {
             env: CompileTimeEnvoirnment::new(),
             only_immediate: false,
             post_expansion_scope: None,
+            loop_context: LoopContext::Outside,
         }
     }
 }
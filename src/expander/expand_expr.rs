@@ -6,19 +6,23 @@ use crate::{
         syntax::Syntax,
         Ast, Pair, Symbol, Varidiac,
     },
-    error::Error,
+    error::{Error, SkipOutsideLoop},
     expander::{
         duplicate_check::{check_no_duplicate_ids, make_check_no_duplicate_table},
         expand,
     },
-    list, matches_to,
+    list,
 };
 use crate::{sexpr, UniqueNumberManager};
 use itertools::Itertools;
 use matcher_proc_macro::{match_syntax, match_syntax_as};
 
 use super::binding::CompileTimeBinding;
-use super::{expand::rebuild, expand_context::ExpandContext, Expander};
+use super::{
+    expand::rebuild,
+    expand_context::{ExpandContext, LoopContext},
+    Expander,
+};
 match_syntax_as!(LetSyntaxMatcher as
 
     (
@@ -180,7 +184,7 @@ impl Expander {
     }
     pub fn add_core_forms(&mut self) {
         self.add_core_form("lambda".into(), Self::core_form_lambda);
-        // self.add_core_form("case-lambda".into(), Self::core_form_case_lambda);
+        self.add_core_form("case-lambda".into(), Self::core_form_case_lambda);
         self.add_core_form("let-values".into(), Self::core_form_let_values);
         self.add_core_form("letrec-values".into(), Self::core_form_letrec_values);
         self.add_core_form(
@@ -194,30 +198,54 @@ impl Expander {
         self.add_core_form("quote".into(), Self::core_form_quote);
         self.add_core_form("quote-syntax".into(), Self::core_form_quote_syntax);
         self.add_core_form("if".into(), Self::core_form_if);
-        // self.add_core_form(
-        //     "with-continuation-mark".into(),
-        //     Self::core_form_with_continuation_mark,
-        // );
+        self.add_core_form(
+            "with-continuation-mark".into(),
+            Self::core_form_with_continuation_mark,
+        );
+        self.add_core_form(
+            "current-continuation-marks".into(),
+            Self::core_form_current_continuation_marks,
+        );
         self.add_core_form("begin".into(), Self::core_form_begin);
         self.add_core_form("begin0".into(), Self::core_form_begin0);
         self.add_core_form("set-bang".into(), Self::core_form_set);
         // from expand_top_level
         self.add_core_form("define-values".into(), Self::core_form_define_values);
         self.add_core_form("define-syntaxes".into(), Self::core_form_define_syntaxes);
+        self.add_core_form("begin-for-syntax".into(), Self::core_form_begin_for_syntax);
         self.add_core_form("link".into(), Self::core_form_link);
+        // from module
+        self.add_core_form("module".into(), Self::core_form_module);
+        self.add_core_form("provide".into(), Self::core_form_provide);
+        self.add_core_form("require".into(), Self::core_form_require);
         self.add_core_form("if".into(), Self::core_form_if);
 
         self.add_core_form("loop".into(), Self::core_form_loop);
 
         // TODO: only availiabe in loop
         self.add_core_form("stop".into(), Self::core_form_stop);
-        self.add_core_form("skip".into(), |_, _, _| todo!());
+        self.add_core_form("skip".into(), Self::core_form_skip);
         // TODO: will we need begin
     }
     fn core_form_loop(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
         let m = match_syntax!((r#loop producer))(s.clone())?;
 
-        let body = self.expand(m.producer, ctx)?;
+        // only a function that is the producer itself can skip, not one that is somewhere in the
+        // expression that makes the producer
+        let (producer, form) = self.expand_immediate(m.producer, ctx.clone())?;
+        let loop_context = if matches!(form.as_deref(), Some("lambda" | "case-lambda")) {
+            LoopContext::Producer
+        } else {
+            LoopContext::Outside
+        };
+        let body = self.finish_expanding(
+            producer,
+            form.as_deref(),
+            ExpandContext {
+                loop_context,
+                ..ctx
+            },
+        )?;
         Ok(rebuild(s, sexpr!((#(m.r#loop) #(body)))))
     }
 
@@ -239,7 +267,9 @@ impl Expander {
                 }
             })
     }
-    fn lambda_formals(formals: &Ast) -> Result<(Syntax<usize>, Option<Varidiac>), Error> {
+    pub(crate) fn lambda_formals(
+        formals: &Ast,
+    ) -> Result<(Syntax<usize>, Option<Varidiac>), Error> {
         let check_variadic = |argc: Ast| -> Result<Varidiac, Error> {
             Self::get_syntax(argc)
                 .ok_or("formals must be number".into())
@@ -291,8 +321,21 @@ impl Expander {
         let m = match_syntax!(
             (lambda formals body..+)
         )(s.clone())?;
+        let new_lambda = self.make_lambda(m.lambda, &m.formals, m.body, &s, ctx)?;
+        Ok(rebuild(s, new_lambda))
+    }
+
+    // the curried lambdas for formals, with lambda as the lambda identifier for each one
+    fn make_lambda(
+        &mut self,
+        lambda: Ast,
+        formals: &Ast,
+        body: Ast,
+        s: &Ast,
+        ctx: ExpandContext,
+    ) -> Result<Ast, Error> {
         let sc = UniqueNumberManager::new_scope();
-        let (formals, variadiac) = Self::lambda_formals(&m.formals)?;
+        let (formals, variadiac) = Self::lambda_formals(formals)?;
 
         let arg_count = variadiac.map_or(formals.0, |_| formals.0 + 1);
         let args = (0..arg_count).map(|i| {
@@ -303,6 +346,13 @@ impl Expander {
         });
 
         let mut body_ctx = ctx;
+        // only the function a loop calls can skip to the next iteration, not any function in it,
+        // so every other lambda body starts outside of any loop
+        body_ctx.loop_context = if body_ctx.loop_context == LoopContext::Producer {
+            LoopContext::Body
+        } else {
+            LoopContext::Outside
+        };
         body_ctx.env.0.extend(args.clone().map(|i| {
             (
                 Self::add_local_binding(i),
@@ -314,9 +364,8 @@ impl Expander {
         // o(N) = octal of n
         // (lambda (N ) ..) becomes (lambda 0 (.. (lambda o(N)  ...)))
         // (lambda (N +) ..) becomes (lambda 0 (.. (lambda o(N) lambda o(N + 1 )*  ...)))
-        let exp_body = self.expand_body(m.body, sc.clone(), s.clone(), body_ctx)?;
-        let lambda = m.lambda;
-        let new_lambda = if formals.0 == 0 && variadiac.is_none() {
+        let exp_body = self.expand_body(body, sc.clone(), s.clone(), body_ctx)?;
+        Ok(if formals.0 == 0 && variadiac.is_none() {
             sexpr!( (#(lambda) () #(exp_body)))
         } else {
             // TODO: do we really need to have the lambdas curried at this point?
@@ -330,9 +379,31 @@ impl Expander {
                     sexpr!((#(lambda.clone()) (#(id)) #(body)))
                 }
             })
-        };
+        })
+    }
 
-        Ok(rebuild(s, new_lambda))
+    // (case-lambda (formals body ...+) ...) => (case-lambda (formals (lambda ...)) ...)
+    // each clause keeps its formals so that compilation knows its arity without having to look at
+    // how the lambda is curried
+    fn core_form_case_lambda(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
+        let m = match_syntax!((case_lambda clause ...))(s.clone())?;
+        let lambda = self.core_datum_to_syntax("lambda".into());
+        let clauses = m
+            .clause
+            .to_list_checked::<Error>()?
+            .into_iter()
+            .map(|clause| -> Result<_, Error> {
+                let m = match_syntax!((formals body..+))(clause.clone())?;
+                let new_lambda =
+                    self.make_lambda(lambda.clone(), &m.formals, m.body, &s, ctx.clone())?;
+                let new_lambda = rebuild(clause.clone(), new_lambda);
+                Ok(rebuild(clause, list!(m.formals, new_lambda)))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(rebuild(
+            s,
+            list!(m.case_lambda; list_to_cons(clauses.into_iter(), |clause| clause)),
+        ))
     }
 
     // fn make_lambda_expander(
@@ -367,28 +438,6 @@ impl Expander {
     //     let (formals, body) = self.make_lambda_expander(s.clone(), m.formal, m.body, ctx)?;
     //     Ok(rebuild(s, list!(m.lambda, formals, body)))
     // }
-    // fn core_form_case_lambda(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
-    //     let m = matcher::match_syntax!((
-    //         case_lambda
-    //         (formals body ..+)
-    //         ...
-    //     ))(s.clone())?;
-    //     let cm = matcher::match_syntax!(
-    //         (case_lambda clause ...)
-    //     )(s.clone())?;
-    //     let iter = m
-    //         .formals
-    //         .to_list_checked()?
-    //         .into_iter()
-    //         .zip(m.body.to_list_checked()?)
-    //         .zip(cm.clause.to_list_checked()?)
-    //         .try_rfold(Ast::TheEmptyList, |rest, ((formals, bodys), clause)| {
-    //             let (formals, body) =
-    //                 self.make_lambda_expander(s.clone(), formals, bodys, ctx.clone())?;
-    //             Ok::<Ast, String>(list!(rebuild(clause, list!(formals, body));rest))
-    //         })?;
-    //     Ok(rebuild(s, list!(m.case_lambda; iter)))
-    // }
     // fn parse_and_flatten_formals(
     //     &self,
     //     formals: Ast,
@@ -515,6 +564,14 @@ impl Expander {
             ),
         ))
     }
+    fn core_form_current_continuation_marks(
+        &mut self,
+        s: Ast,
+        _ctx: ExpandContext,
+    ) -> Result<Ast, Error> {
+        match_syntax!((current_continuation_marks))(s.clone())?;
+        Ok(s)
+    }
     fn make_begin(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
         let m = match_syntax!( (begin e ..+))(s.clone())?;
 
@@ -542,6 +599,14 @@ impl Expander {
                     })
             })
     }
+    fn core_form_skip(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
+        match_syntax!((skip))(s.clone())?;
+        if ctx.loop_context == LoopContext::Body {
+            Ok(s)
+        } else {
+            Err(SkipOutsideLoop(s).into())
+        }
+    }
     fn core_form_set(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
         // TODO: let m = matcher::match_syntax!( (set-bang id rhs))(s.clone(),)?;
         let (set, id, rhs) = match_syntax!( (set id rhs))(s.clone())
@@ -586,12 +651,14 @@ impl Expander {
                 ...
             )
         )(s.clone())?;
-        // little hacky: we have to unwrap any syntax object/quoting done as the labels might be
-        // result of expansion from macro
+        // the labels are kept as syntax objects, so that they can be resolved (with their scopes)
+        // when the link is compiled
         let filter_label = |label: Ast| {
-            let label = label.syntax_to_datum().unquote();
-            matches_to!(label => Ast::Label | format!("not a label: {label}").into())
-                .map(Ast::Label)
+            if matches!(&label, Ast::Syntax(l) if matches!(l.0, Ast::Label(_))) {
+                Ok(label)
+            } else {
+                Err(format!("not a label: {label}").into())
+            }
         };
 
         let dest = self.expand(m.dest_label, ctx.clone())?;
@@ -1,12 +1,178 @@
-use crate::{ast::Ast, error::Error};
+use std::collections::VecDeque;
 
-use super::{expand_context::ExpandContext, Expander};
+use matcher_proc_macro::match_syntax;
+
+use crate::{
+    ast::{syntax::Syntax, Ast, Symbol},
+    error::Error,
+    list, UniqueNumberManager,
+};
+
+use super::{
+    binding::{Binding, CompileTimeBinding},
+    duplicate_check::{check_no_duplicate_ids, make_check_no_duplicate_table},
+    expand::{rebuild, to_id_list},
+    expand_context::ExpandContext,
+    expand_expr::list_to_cons,
+    Expander,
+};
 
 impl Expander {
-    pub fn core_form_define_values(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
-        Err(format!("not allowed in an expression postion: {s} ").into())
+    // expands a form as a top level form, definitions bind their ids before the right hand side is
+    // expanded (so functions can be recursive) and are recorded in the namespace, so the forms
+    // expanded after this one can use them
+    pub fn expand_top_level(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
+        let (exp_s, form) = self.expand_immediate(s, ctx.clone())?;
+        match form.as_deref() {
+            // each form in a top level begin is also at the top level, so a definition is visible
+            // to the forms after it in the begin
+            Some("begin") => {
+                let m = match_syntax!((begin e ..+))(exp_s.clone())?;
+                let body =
+                    m.e.to_list_checked::<Error>()?
+                        .into_iter()
+                        .map(|e| self.expand_top_level(e, ctx.clone()))
+                        .collect::<Result<Vec<_>, Error>>()?;
+                Ok(rebuild(
+                    exp_s,
+                    list!(m.begin; list_to_cons(body.into_iter(), |e| e)),
+                ))
+            }
+            Some("define-values") => {
+                let m = match_syntax!((define_values (id ...) rhs))(exp_s.clone())?;
+                let ids = to_id_list(m.id.clone())?;
+                check_no_duplicate_ids(ids.clone(), &exp_s, make_check_no_duplicate_table())?;
+                for key in self.add_top_level_bindings(ids)? {
+                    ctx.namespace.transformers.borrow_mut().remove(&key);
+                    ctx.namespace
+                        .variables
+                        .borrow_mut()
+                        .insert(key.clone(), Ast::Symbol(key));
+                }
+                let rhs = self.expand(m.rhs, ctx)?;
+                Ok(rebuild(exp_s, list!(m.define_values, m.id, rhs)))
+            }
+            Some("define-syntaxes") => {
+                let m = match_syntax!((define_syntaxes (id ...) rhs))(exp_s.clone())?;
+                let ids = to_id_list(m.id)?;
+                check_no_duplicate_ids(ids.clone(), &exp_s, make_check_no_duplicate_table())?;
+                let id_count = ids.len();
+                let keys = self.add_top_level_bindings(ids)?;
+                let vals = self.eval_for_syntaxes_binding(m.rhs, id_count, ctx.clone())?;
+                for (key, val) in keys.into_iter().zip(vals) {
+                    ctx.namespace.variables.borrow_mut().remove(&key);
+                    ctx.namespace
+                        .transformers
+                        .borrow_mut()
+                        .insert(key, CompileTimeBinding::Regular(val));
+                }
+                Ok(exp_s)
+            }
+            Some("begin-for-syntax") => {
+                self.expand_begin_for_syntax(exp_s.clone(), ctx, None)?;
+                Ok(exp_s)
+            }
+            form => self.finish_expanding(exp_s, form, ctx),
+        }
+    }
+
+    // the forms of a begin-for-syntax are expanded, compiled and evaluated (in the expand time
+    // envoirnment) one at a time, so each form can use the definitions before it, and so can any
+    // transformer defined after the begin-for-syntax
+    // definitions are bound like the run time definitions of the module they are in (or of the top
+    // level), but are only recorded in the for syntax namespace
+    pub(crate) fn expand_begin_for_syntax(
+        &mut self,
+        s: Ast,
+        ctx: ExpandContext,
+        module: Option<&Symbol>,
+    ) -> Result<(), Error> {
+        let m = match_syntax!((begin_for_syntax e ...))(s)?;
+        let ctx = ExpandContext {
+            namespace: ctx.namespace.for_syntax()?,
+            ..ctx
+        };
+        let mut bodys: VecDeque<Ast> = m.e.to_list_checked::<Error>()?.into();
+        while let Some(body) = bodys.pop_front() {
+            let (exp_body, form) = self.expand_immediate(body, ctx.clone())?;
+            let exp_body = match form.as_deref() {
+                Some("begin") => {
+                    let m = match_syntax!((begin e ...))(exp_body)?;
+                    for e in m.e.to_list_checked::<Error>()?.into_iter().rev() {
+                        bodys.push_front(e);
+                    }
+                    continue;
+                }
+                Some("define-values") => {
+                    let m = match_syntax!((define_values (id ...) rhs))(exp_body.clone())?;
+                    let ids = to_id_list(m.id.clone())?;
+                    check_no_duplicate_ids(
+                        ids.clone(),
+                        &exp_body,
+                        make_check_no_duplicate_table(),
+                    )?;
+                    let keys = match module {
+                        Some(module) => self.add_module_bindings(module, ids)?,
+                        None => self.add_top_level_bindings(ids)?,
+                    };
+                    ctx.namespace
+                        .variables
+                        .borrow_mut()
+                        .extend(keys.into_iter().map(|key| (key.clone(), Ast::Symbol(key))));
+                    let rhs = self.expand(m.rhs, ctx.clone())?;
+                    rebuild(exp_body, list!(m.define_values, m.id, rhs))
+                }
+                Some("define-syntaxes" | "begin-for-syntax") => {
+                    Err(format!("not allowed in code for syntax: {exp_body}"))?
+                }
+                form => self.finish_expanding(exp_body, form, ctx.clone())?,
+            };
+            let compiled = self.compile(exp_body, &ctx.namespace)?;
+            self.expand_time_eval(compiled)?;
+        }
+        Ok(())
+    }
+
+    pub fn core_form_begin_for_syntax(
+        &mut self,
+        s: Ast,
+        _ctx: ExpandContext,
+    ) -> Result<Ast, Error> {
+        Err(format!("begin-for-syntax: only allowed at the top level or module level: {s}").into())
     }
-    pub fn core_form_define_syntaxes(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
-        Err(format!("not allowed in an expression postion: {s} ").into())
+
+    pub fn core_form_define_values(&mut self, s: Ast, _ctx: ExpandContext) -> Result<Ast, Error> {
+        Err(format!("not allowed in an expression position: {s} ").into())
+    }
+
+    pub fn core_form_define_syntaxes(&mut self, s: Ast, _ctx: ExpandContext) -> Result<Ast, Error> {
+        Err(format!("not allowed in an expression position: {s} ").into())
+    }
+
+    // a top level definition is bound to its name, so defining a name again (as a variable or as a
+    // macro) just replaces what it refers to, but the core forms, macros and primitives cannot be
+    // redefined as the derived forms expand into them
+    // a name introduced by a macro (so with more scopes than just the core scope) is bound to a
+    // fresh name instead, so it cannot replace (or be replaced by) a definition of the same name
+    // outside the macro
+    fn add_top_level_bindings(&self, ids: Vec<Syntax<Symbol>>) -> Result<Vec<Symbol>, Error> {
+        ids.into_iter()
+            .map(|id| -> Result<_, Error> {
+                if id.1.iter().any(|scope| *scope != self.core_scope) {
+                    let name = UniqueNumberManager::gen_sym(&id.0 .0);
+                    Self::add_binding(id, Binding::TopLevel(name.0.clone()))?;
+                    return Ok(name);
+                }
+                let name = id.0 .0.clone();
+                if self.core_forms.contains_key(&name)
+                    || self.core_macros.contains_key(&name)
+                    || self.core_primitives.contains_key(&name)
+                {
+                    Err(format!("cannot redefine core binding {name}"))?;
+                }
+                Self::add_binding(id, Binding::TopLevel(name.clone()))?;
+                Ok(name.into())
+            })
+            .collect()
     }
 }
@@ -0,0 +1,95 @@
+use std::fmt;
+
+use itertools::Itertools;
+
+use crate::{
+    ast::{scope::ScopeSet, syntax::Syntax, Ast, Symbol},
+    error::{AmbiguousBinding, Error, FreeVariable},
+};
+
+use super::{binding::Binding, Expander};
+
+/// How an identifier resolves: its scope set, every binding of its name whose scope set is a subset
+/// of the identifier's (the candidates), and what [`Expander::resolve`] picked from them.
+#[derive(Debug)]
+pub struct Resolution {
+    pub id: Syntax<Symbol>,
+    pub binding: Result<Binding, Error>,
+    pub candidates: Vec<(ScopeSet, Binding)>,
+}
+
+struct Scopes<'a>(&'a ScopeSet);
+
+impl fmt::Display for Scopes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{{}}}", self.0.iter().map(|scope| scope.0).join(" "))
+    }
+}
+
+struct ShowBinding<'a>(&'a Binding);
+
+impl fmt::Display for ShowBinding<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Binding::Local(s) => write!(f, "local {s}"),
+            Binding::TopLevel(s) => write!(f, "top level {s}"),
+        }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.id.0, Scopes(&self.id.1))?;
+        match &self.binding {
+            Ok(binding) => write!(f, " => {}", ShowBinding(binding))?,
+            Err(Error::FreeVariable(_)) => write!(f, " => free")?,
+            Err(Error::AmbiguousBinding(_)) => write!(f, " => ambiguous")?,
+            Err(e) => write!(f, " => {e:?}")?,
+        }
+        for (scopes, binding) in &self.candidates {
+            write!(f, "\n    {} {}", Scopes(scopes), ShowBinding(binding))?;
+        }
+        Ok(())
+    }
+}
+
+impl Expander {
+    #[must_use]
+    pub fn resolution(id: Syntax<Symbol>) -> Resolution {
+        let candidates = Self::find_all_matching_bindings(&id, &id.1)
+            .sorted_by_key(|(scopes, _)| scopes.len())
+            .collect();
+        Resolution {
+            binding: Self::resolve(&id, false),
+            candidates,
+            id,
+        }
+    }
+
+    /// The resolution of every identifier in `s`, from left to right.
+    #[must_use]
+    pub fn resolutions(s: &Ast) -> Vec<Resolution> {
+        match s {
+            Ast::Syntax(syntax) => match &syntax.0 {
+                Ast::Symbol(symbol) => vec![Self::resolution(syntax.with_ref(symbol.clone()))],
+                inner => Self::resolutions(inner),
+            },
+            Ast::Pair(p) => {
+                let mut resolutions = Self::resolutions(&p.0);
+                resolutions.extend(Self::resolutions(&p.1));
+                resolutions
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The resolution of the identifier that caused a free variable or ambiguous binding error.
+    #[must_use]
+    pub fn error_resolution(e: &Error) -> Option<Resolution> {
+        match e {
+            Error::FreeVariable(FreeVariable(id))
+            | Error::AmbiguousBinding(AmbiguousBinding(id)) => Some(Self::resolution(id.clone())),
+            _ => None,
+        }
+    }
+}
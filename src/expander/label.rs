@@ -0,0 +1,273 @@
+use std::{collections::HashMap, fmt, rc::Rc};
+
+use crate::{
+    ast::{
+        ast1::Label,
+        scope::ScopeSet,
+        syntax::{SourceLocation, Syntax},
+        Symbol,
+    },
+    error::{Error, InvalidLabels},
+    UniqueNumberManager,
+};
+
+use super::{binding::Binding, Expander};
+
+/// A label as it was written in the program, along with what it resolved to.
+#[derive(Clone, Debug)]
+pub struct LabelSite {
+    pub name: Rc<str>,
+    pub label: Label,
+    pub srcloc: SourceLocation,
+    // the function the label is in (0 for the top level), a goto cannot leave or enter a function
+    function: usize,
+    // the innermost with-continuation-mark body the label is in (0 for none), a goto cannot leave
+    // or enter one either, as the compiled code would not remove or add its mark
+    mark_body: usize,
+}
+
+#[derive(Clone, Debug)]
+struct LinkSite {
+    dest: LabelSite,
+    srcs: Vec<LabelSite>,
+}
+
+// every label placed and every link compiled, in the order they were compiled in, they can only be
+// checked once the whole program is compiled as a label can be linked before it is placed
+#[derive(Default, Debug)]
+pub(crate) struct LabelUses {
+    placements: Vec<LabelSite>,
+    links: Vec<LinkSite>,
+    function: usize,
+    mark_body: usize,
+}
+
+/// A problem with how the labels of a program are placed or linked.
+#[derive(Clone, Debug)]
+pub enum LabelDiagnostic {
+    /// A label is used in a `link`, but is not placed anywhere.
+    NeverPlaced(LabelSite),
+    /// A label is placed more than once, so it is not clear where to jump to.
+    PlacedTwice(LabelSite, LabelSite),
+    /// A label is in more than one `link`.
+    LinkedTwice(LabelSite, LabelSite),
+    /// A label is linked to a label in another function, which a goto cannot jump to.
+    Unreachable { src: LabelSite, dest: LabelSite },
+    /// A label is linked to a label that is not in the same `with-continuation-mark` body.
+    AcrossMark { src: LabelSite, dest: LabelSite },
+    /// A label is placed, but not in any `link`, this is only a warning.
+    NeverLinked(LabelSite),
+}
+
+impl LabelDiagnostic {
+    #[must_use]
+    pub const fn is_error(&self) -> bool {
+        !matches!(self, Self::NeverLinked(_))
+    }
+}
+
+struct At<'a>(&'a str, &'a LabelSite);
+
+impl fmt::Display for At<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the location is only known for syntax objects that were given one
+        if self.1.srcloc == SourceLocation::default() {
+            Ok(())
+        } else {
+            write!(f, "\n    {} at {}", self.0, self.1.srcloc)
+        }
+    }
+}
+
+impl fmt::Display for LabelDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", if self.is_error() { "error" } else { "warning" })?;
+        match self {
+            Self::NeverPlaced(site) => write!(
+                f,
+                "label @{} is linked but never placed{}",
+                site.name,
+                At("linked", site)
+            ),
+            Self::PlacedTwice(first, second) => write!(
+                f,
+                "label @{} is placed more than once{}{}",
+                first.name,
+                At("placed", first),
+                At("placed", second)
+            ),
+            Self::LinkedTwice(first, second) => write!(
+                f,
+                "label @{} is in more than one link{}{}",
+                first.name,
+                At("linked", first),
+                At("linked", second)
+            ),
+            Self::Unreachable { src, dest } => write!(
+                f,
+                "label @{} cannot jump to label @{} as they are in different functions{}{}",
+                src.name,
+                dest.name,
+                At("placed", src),
+                At("placed", dest)
+            ),
+            Self::AcrossMark { src, dest } => write!(
+                f,
+                "label @{} cannot jump to label @{} as they are in different with-continuation-mark bodies{}{}",
+                src.name,
+                dest.name,
+                At("placed", src),
+                At("placed", dest)
+            ),
+            Self::NeverLinked(site) => write!(
+                f,
+                "label @{} is placed but never linked{}",
+                site.name,
+                At("placed", site)
+            ),
+        }
+    }
+}
+
+impl Expander {
+    // labels are global (a goto can jump to a label anywhere in the program), so unlike an
+    // identifier, the scopes a label gets from the binding forms it is in do not matter, only the
+    // introduction scopes of the macros that introduced it, which keep a label introduced by a macro
+    // distinct from a label with the same name from the user or from another use of the macro
+    // the label is resolved exactly with those scopes (along with the label scope, so that it can
+    // never be seen by an identifier), and the first time a label is seen it is bound to a fresh
+    // name, which is the name it is compiled to
+    pub(crate) fn resolve_label(&self, label: &Syntax<Rc<str>>) -> Result<Label, Error> {
+        let mut scopes: ScopeSet = label
+            .1
+            .intersection(&self.introduction_scopes)
+            .cloned()
+            .collect();
+        scopes.insert(self.label_scope.clone());
+        let id = Syntax(
+            Symbol(format!("@{}", label.0).into()),
+            scopes,
+            label.2.clone(),
+            label.3.clone(),
+        );
+        match Self::resolve(&id, true) {
+            Ok(Binding::Local(name)) => Ok(Label(name.0)),
+            Ok(binding) => Err(format!("bad label binding {binding}").into()),
+            Err(Error::FreeVariable(_)) => {
+                let name = UniqueNumberManager::gen_sym(&label.0);
+                Self::add_binding(id, Binding::Local(name.clone()))?;
+                Ok(Label(name.0))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn label_site(&self, label: &Syntax<Rc<str>>) -> Result<LabelSite, Error> {
+        Ok(LabelSite {
+            name: label.0.clone(),
+            label: self.resolve_label(label)?,
+            srcloc: label.2.clone(),
+            function: self.labels.function,
+            mark_body: self.labels.mark_body,
+        })
+    }
+
+    pub(crate) fn place_label(&mut self, label: &Syntax<Rc<str>>) -> Result<Label, Error> {
+        let site = self.label_site(label)?;
+        self.labels.placements.push(site.clone());
+        Ok(site.label)
+    }
+
+    pub(crate) fn link_labels(
+        &mut self,
+        dest: &Syntax<Rc<str>>,
+        srcs: &[Syntax<Rc<str>>],
+    ) -> Result<(), Error> {
+        let dest = self.label_site(dest)?;
+        let srcs = srcs
+            .iter()
+            .map(|src| self.label_site(src))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.links.insert(
+            dest.label.clone(),
+            srcs.iter().map(|src| src.label.clone()).collect(),
+        );
+        self.labels.links.push(LinkSite { dest, srcs });
+        Ok(())
+    }
+
+    // the labels placed in the body of a function are in their own function
+    pub(crate) fn with_label_function<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = std::mem::replace(&mut self.labels.function, UniqueNumberManager::next());
+        let res = f(self);
+        self.labels.function = outer;
+        res
+    }
+
+    // the labels placed in the body of a with-continuation-mark are in their own mark body
+    pub(crate) fn with_label_mark_body<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = std::mem::replace(&mut self.labels.mark_body, UniqueNumberManager::next());
+        let res = f(self);
+        self.labels.mark_body = outer;
+        res
+    }
+
+    /// Checks the labels of everything compiled so far, which should be the whole program.
+    /// Returns the warnings if there are no errors.
+    ///
+    /// # Errors
+    /// If a label is linked but not placed, placed more than once, in more than one link, or
+    /// linked to a label in a different function or with-continuation-mark body.
+    pub fn check_labels(&self) -> Result<Vec<LabelDiagnostic>, Error> {
+        let mut diagnostics = vec![];
+        let mut placements: HashMap<&Label, &LabelSite> = HashMap::new();
+        for site in &self.labels.placements {
+            if let Some(first) = placements.get(&site.label) {
+                diagnostics.push(LabelDiagnostic::PlacedTwice((*first).clone(), site.clone()));
+            } else {
+                placements.insert(&site.label, site);
+            }
+        }
+        let mut linked: HashMap<&Label, &LabelSite> = HashMap::new();
+        for link in &self.labels.links {
+            for site in std::iter::once(&link.dest).chain(&link.srcs) {
+                if let Some(first) = linked.get(&site.label) {
+                    diagnostics.push(LabelDiagnostic::LinkedTwice((*first).clone(), site.clone()));
+                } else {
+                    linked.insert(&site.label, site);
+                }
+                if !placements.contains_key(&site.label) {
+                    diagnostics.push(LabelDiagnostic::NeverPlaced(site.clone()));
+                }
+            }
+            let Some(dest) = placements.get(&link.dest.label) else {
+                continue;
+            };
+            for src in &link.srcs {
+                if let Some(src) = placements.get(&src.label) {
+                    if src.function != dest.function {
+                        diagnostics.push(LabelDiagnostic::Unreachable {
+                            src: (*src).clone(),
+                            dest: (*dest).clone(),
+                        });
+                    } else if src.mark_body != dest.mark_body {
+                        diagnostics.push(LabelDiagnostic::AcrossMark {
+                            src: (*src).clone(),
+                            dest: (*dest).clone(),
+                        });
+                    }
+                }
+            }
+        }
+        for site in &self.labels.placements {
+            if !linked.contains_key(&site.label) {
+                diagnostics.push(LabelDiagnostic::NeverLinked(site.clone()));
+            }
+        }
+        if diagnostics.iter().any(LabelDiagnostic::is_error) {
+            Err(InvalidLabels(diagnostics).into())
+        } else {
+            Ok(diagnostics)
+        }
+    }
+}
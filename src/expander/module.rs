@@ -0,0 +1,298 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    rc::Rc,
+};
+
+use matcher_proc_macro::match_syntax;
+
+use crate::{
+    ast::{scope::AdjustScope, syntax::Syntax, Ast, Symbol},
+    error::{Error, ImportConflict},
+    list, UniqueNumberManager,
+};
+
+use super::{
+    binding::{Binding, CompileTimeBinding},
+    duplicate_check::{check_no_duplicate_ids, make_check_no_duplicate_table},
+    expand::{rebuild, to_id_list},
+    expand_context::ExpandContext,
+    expand_expr::list_to_cons,
+    Expander,
+};
+
+/// What a module makes available to `require`, each exported name and the binding it refers to.
+#[derive(Clone, Debug, Default)]
+pub struct Module {
+    pub(crate) exports: HashMap<Symbol, Binding>,
+}
+
+// a module's body after partial expansion, definitions are bound before any right hand side is
+// expanded so that definitions can refer to each other
+enum Partial {
+    Definition {
+        form: Ast,
+        define_values: Ast,
+        ids: Ast,
+        rhs: Ast,
+    },
+    // along with the core form it was partially expanded to
+    Expression(Ast, Option<Rc<str>>),
+    Expanded(Ast),
+}
+
+// module level definitions are top level bindings qualified by the module name, so `x` in module
+// `a` becomes `a#x`, which is only reachable from outside the module if it is provided
+fn module_binding(module: &Symbol, id: &Symbol) -> Binding {
+    Binding::TopLevel(format!("{module}#{id}").into())
+}
+
+fn spec_form(form: Ast) -> Option<Rc<str>> {
+    match form.unsyntax() {
+        Ast::Symbol(s) => Some(s.0),
+        _ => None,
+    }
+}
+
+impl Expander {
+    pub fn core_form_module(&mut self, s: Ast, ctx: ExpandContext) -> Result<Ast, Error> {
+        let m = match_syntax!((module name body ...))(s.clone())?;
+        let name: Syntax<Symbol> = m.name.clone().try_into()?;
+        let name = name.0;
+        let sc = UniqueNumberManager::new_scope();
+        let mut bodys: VecDeque<Ast> = m
+            .body
+            .map(|body| Ok::<_, Error>(body.add_scope(sc.clone())))?
+            .to_list_checked::<Error>()?
+            .into();
+        let mut partials = vec![];
+        let mut provides = vec![];
+        let mut duplicate = make_check_no_duplicate_table();
+        while let Some(body) = bodys.pop_front() {
+            let (exp_body, form) = self.expand_immediate(body, ctx.clone())?;
+            match form.as_deref() {
+                Some("begin") => {
+                    let m = match_syntax!((begin e ...))(exp_body)?;
+                    for e in m.e.to_list_checked::<Error>()?.into_iter().rev() {
+                        bodys.push_front(e);
+                    }
+                }
+                Some("define-values") => {
+                    let m = match_syntax!((define_values (id ...) rhs))(exp_body.clone())?;
+                    let ids = to_id_list(m.id.clone())?;
+                    duplicate = check_no_duplicate_ids(ids.clone(), &exp_body, duplicate)?;
+                    let keys = self.add_module_bindings(&name, ids)?;
+                    ctx.namespace
+                        .variables
+                        .borrow_mut()
+                        .extend(keys.into_iter().map(|key| (key.clone(), Ast::Symbol(key))));
+                    partials.push(Partial::Definition {
+                        form: exp_body,
+                        define_values: m.define_values,
+                        ids: m.id,
+                        rhs: m.rhs,
+                    });
+                }
+                Some("define-syntaxes") => {
+                    let m = match_syntax!((define_syntaxes (id ...) rhs))(exp_body.clone())?;
+                    let ids = to_id_list(m.id)?;
+                    duplicate = check_no_duplicate_ids(ids.clone(), &exp_body, duplicate)?;
+                    let id_count = ids.len();
+                    let keys = self.add_module_bindings(&name, ids)?;
+                    let vals = self.eval_for_syntaxes_binding(m.rhs, id_count, ctx.clone())?;
+                    ctx.namespace.transformers.borrow_mut().extend(
+                        keys.into_iter()
+                            .zip(vals.into_iter().map(CompileTimeBinding::Regular)),
+                    );
+                }
+                Some("begin-for-syntax") => {
+                    self.expand_begin_for_syntax(exp_body, ctx.clone(), Some(&name))?;
+                }
+                Some("provide") => provides.push(exp_body),
+                // requires have to happen now so later forms in the module can see the imports
+                Some("require") => {
+                    partials.push(Partial::Expanded(self.expand(exp_body, ctx.clone())?));
+                }
+                _ => partials.push(Partial::Expression(exp_body, form)),
+            }
+        }
+        let body = partials
+            .into_iter()
+            .map(|partial| match partial {
+                Partial::Definition {
+                    form,
+                    define_values,
+                    ids,
+                    rhs,
+                } => self
+                    .expand(rhs, ctx.clone())
+                    .map(|rhs| rebuild(form, list!(define_values, ids, rhs))),
+                Partial::Expression(e, form) => {
+                    self.finish_expanding(e, form.as_deref(), ctx.clone())
+                }
+                Partial::Expanded(e) => Ok(e),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let exports = Self::module_exports(provides)?;
+        self.modules.insert(name, Module { exports });
+        Ok(rebuild(
+            s,
+            list!(m.module, m.name; list_to_cons(body.into_iter(), |body| body)),
+        ))
+    }
+
+    pub fn core_form_provide(&mut self, s: Ast, _ctx: ExpandContext) -> Result<Ast, Error> {
+        Err(format!("provide: not at module level: {s}").into())
+    }
+
+    pub fn core_form_require(&mut self, s: Ast, _ctx: ExpandContext) -> Result<Ast, Error> {
+        let m = match_syntax!((require spec ...))(s.clone())?;
+        for spec in m.spec.to_list_checked::<Error>()? {
+            for (id, binding) in self.require_spec(spec)? {
+                self.check_import_conflict(&id, &binding)?;
+                Self::add_binding(id, binding)?;
+            }
+        }
+        Ok(s)
+    }
+
+    pub(crate) fn add_module_bindings(
+        &self,
+        module: &Symbol,
+        ids: Vec<Syntax<Symbol>>,
+    ) -> Result<Vec<Symbol>, Error> {
+        ids.into_iter()
+            .map(|id| -> Result<_, Error> {
+                let binding = module_binding(module, &id.0);
+                self.check_import_conflict(&id, &binding)?;
+                Self::add_binding(id, binding.clone())?;
+                Ok(binding.into())
+            })
+            .collect()
+    }
+
+    // the bindings of the core scope can be shadowed, but any other identifier with the exact same
+    // scopes cannot refer to two different things
+    fn check_import_conflict(&self, id: &Syntax<Symbol>, binding: &Binding) -> Result<(), Error> {
+        match Self::resolve(id, true) {
+            Ok(Binding::TopLevel(existing))
+                if self.core_forms.contains_key(&existing)
+                    || self.core_macros.contains_key(&existing)
+                    || self.core_primitives.contains_key(&existing) =>
+            {
+                Ok(())
+            }
+            Ok(existing) if existing != *binding => Err(ImportConflict(id.clone()).into()),
+            _ => Ok(()),
+        }
+    }
+
+    fn module_exports(provides: Vec<Ast>) -> Result<HashMap<Symbol, Binding>, Error> {
+        let mut exports = HashMap::new();
+        for provide in provides {
+            let m = match_syntax!((provide spec ...))(provide)?;
+            for spec in m.spec.to_list_checked::<Error>()? {
+                for (id, name) in Self::provide_spec(spec)? {
+                    let binding = Self::resolve(&id, false)?;
+                    match exports.insert(name.clone(), binding.clone()) {
+                        Some(old) if old != binding => Err(format!(
+                            "provide: {name} is exported with two different bindings"
+                        ))?,
+                        _ => {}
+                    }
+                }
+            }
+        }
+        Ok(exports)
+    }
+
+    // id | (rename-out (old new) ...)
+    fn provide_spec(spec: Ast) -> Result<Vec<(Syntax<Symbol>, Symbol)>, Error> {
+        if let Ok(id) = Syntax::<Symbol>::try_from(spec.clone()) {
+            let name = id.0.clone();
+            return Ok(vec![(id, name)]);
+        }
+        let m = match_syntax!((form renames ...))(spec.clone())?;
+        match spec_form(m.form).as_deref() {
+            Some("rename-out") => m
+                .renames
+                .to_list_checked::<Error>()?
+                .into_iter()
+                .map(|rename| -> Result<_, Error> {
+                    let m = match_syntax!((old new))(rename)?;
+                    let new: Syntax<Symbol> = m.new.try_into()?;
+                    Ok((m.old.try_into()?, new.0))
+                })
+                .collect(),
+            _ => Err(format!("provide: bad export specification {spec}").into()),
+        }
+    }
+
+    fn module_of(&self, name: &Syntax<Symbol>) -> Result<Module, Error> {
+        self.modules
+            .get(&name.0)
+            .cloned()
+            .ok_or_else(|| format!("require: unknown module {}", name.0).into())
+    }
+
+    // name | (only-in name id ...) | (rename-in name (old new) ...) | (prefix-in prefix name)
+    // imported identifiers take the scopes of the identifier that names them in the spec
+    fn require_spec(&self, spec: Ast) -> Result<Vec<(Syntax<Symbol>, Binding)>, Error> {
+        if let Ok(name) = Syntax::<Symbol>::try_from(spec.clone()) {
+            let module = self.module_of(&name)?;
+            return Ok(module
+                .exports
+                .into_iter()
+                .map(|(export, binding)| (name.with_ref(export), binding))
+                .collect());
+        }
+        let m = match_syntax!((form name rest ...))(spec.clone())?;
+        let export = |module: &Module, name: &Syntax<Symbol>, id: &Symbol| {
+            module
+                .exports
+                .get(id)
+                .cloned()
+                .ok_or_else(|| format!("require: {} does not provide {id}", name.0).into())
+        };
+        match spec_form(m.form).as_deref() {
+            Some("only-in") => {
+                let name = m.name.try_into()?;
+                let module = self.module_of(&name)?;
+                to_id_list(m.rest)?
+                    .into_iter()
+                    .map(|id| export(&module, &name, &id.0).map(|binding| (id, binding)))
+                    .collect()
+            }
+            Some("rename-in") => {
+                let name = m.name.try_into()?;
+                let module = self.module_of(&name)?;
+                m.rest
+                    .to_list_checked::<Error>()?
+                    .into_iter()
+                    .map(|rename| -> Result<_, Error> {
+                        let m = match_syntax!((old new))(rename)?;
+                        let old: Syntax<Symbol> = m.old.try_into()?;
+                        let new: Syntax<Symbol> = m.new.try_into()?;
+                        export(&module, &name, &old.0).map(|binding| (new, binding))
+                    })
+                    .collect()
+            }
+            Some("prefix-in") => {
+                let prefix: Syntax<Symbol> = m.name.try_into()?;
+                let [name] = <[Syntax<Symbol>; 1]>::try_from(to_id_list(m.rest)?)
+                    .map_err(|_| format!("require: bad prefix-in specification {spec}"))?;
+                let module = self.module_of(&name)?;
+                Ok(module
+                    .exports
+                    .into_iter()
+                    .map(|(export, binding)| {
+                        (
+                            prefix.with_ref(Symbol::from(format!("{}{export}", prefix.0))),
+                            binding,
+                        )
+                    })
+                    .collect())
+            }
+            _ => Err(format!("require: bad import specification {spec}").into()),
+        }
+    }
+}
@@ -1,12 +1,47 @@
-use std::collections::HashMap;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-use crate::ast::{Ast, Symbol};
+use crate::{
+    ast::{Ast, Symbol},
+    error::Error,
+};
 
 use super::binding::CompileTimeBinding;
 
 // Currently just keeps top level bindings + transformers
+// they are shared between clones, so that anything a module (or later top level form) defines
+// is visible to the forms expanded after it
 #[derive(Default, Clone, Debug)]
 pub struct NameSpace {
-    pub variables: HashMap<Symbol, Ast>,
-    pub transformers: HashMap<Symbol, CompileTimeBinding>,
+    pub variables: Rc<RefCell<HashMap<Symbol, Ast>>>,
+    pub transformers: Rc<RefCell<HashMap<Symbol, CompileTimeBinding>>>,
+    // the variables of the other phase (the ones defined for syntax if this is the run time
+    // namespace and the other way around), only used to tell apart a variable used at the wrong
+    // phase from a module level definition
+    pub other_phase_variables: Rc<RefCell<HashMap<Symbol, Ast>>>,
+    pub(crate) phase: Phase,
+}
+
+/// Whether code is run when the program is (run time), or while it is being expanded (for syntax).
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    #[default]
+    RunTime,
+    ForSyntax,
+}
+
+impl NameSpace {
+    /// The namespace that transformers and `begin-for-syntax` are expanded and compiled in.
+    pub fn for_syntax(&self) -> Result<Self, Error> {
+        match self.phase {
+            Phase::RunTime => Ok(Self {
+                variables: self.other_phase_variables.clone(),
+                transformers: self.transformers.clone(),
+                other_phase_variables: self.variables.clone(),
+                phase: Phase::ForSyntax,
+            }),
+            Phase::ForSyntax => {
+                Err("code for syntax cannot itself define syntax or code for syntax".into())
+            }
+        }
+    }
 }
@@ -0,0 +1,184 @@
+use matcher_proc_macro::match_syntax;
+
+use crate::{
+    ast::{syntax::Syntax, Ast, Boolean, Symbol},
+    error::Error,
+    list, sexpr,
+};
+
+use super::{binding::Binding, expand_expr::list_to_cons, Expander};
+
+// the derived forms, each one is a macro that rewrites to the core forms (or to another derived
+// form), any identifiers they introduce get the macro introduction scope so they cannot capture
+// (or be captured by) identifiers from the use site
+impl Expander {
+    pub fn add_prelude_macros(&mut self) {
+        self.add_core_macro("define".into(), Self::macro_define);
+        self.add_core_macro("define-for-syntax".into(), Self::macro_define_for_syntax);
+        self.add_core_macro("let".into(), Self::macro_let);
+        self.add_core_macro("let*".into(), Self::macro_let_star);
+        self.add_core_macro("letrec".into(), Self::macro_letrec);
+        self.add_core_macro("cond".into(), Self::macro_cond);
+        self.add_core_macro("case".into(), Self::macro_case);
+        self.add_core_macro("when".into(), Self::macro_when);
+        self.add_core_macro("and".into(), Self::macro_and);
+        self.add_core_macro("or".into(), Self::macro_or);
+        self.add_core_macro("else".into(), Self::macro_else);
+    }
+
+    // (define id rhs) => (define-values (id) rhs)
+    // (define (id argc [*|+]) body ...+) => (define-values (id) (lambda (argc [*|+]) body ...))
+    fn macro_define(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((define header body..+))(s.clone())?;
+        if Syntax::<Symbol>::try_from(m.header.clone()).is_ok() {
+            let m = match_syntax!((define id rhs))(s)?;
+            return Ok(self.core_datum_to_syntax(sexpr!(("define-values" (#(m.id)) #(m.rhs)))));
+        }
+        let header = match_syntax!((id.formals))(m.header)?;
+        let _: Syntax<Symbol> = header.id.clone().try_into()?;
+        Ok(self.core_datum_to_syntax(sexpr!(
+            ("define-values" (#(header.id)) (lambda #(header.formals) . #(m.body)))
+        )))
+    }
+
+    // (define-for-syntax header body ...+) => (begin-for-syntax (define header body ...))
+    fn macro_define_for_syntax(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((define_for_syntax header body..+))(s)?;
+        Ok(self.core_datum_to_syntax(sexpr!(
+            ("begin-for-syntax" (define #(m.header) . #(m.body)))
+        )))
+    }
+
+    // (let ((id rhs) ...) body ...+) => (let-values (((id) rhs) ...) body ...)
+    fn macro_let(&self, s: Ast) -> Result<Ast, Error> {
+        self.single_value_let(s, "let-values")
+    }
+
+    // (letrec ((id rhs) ...) body ...+) => (letrec-values (((id) rhs) ...) body ...)
+    fn macro_letrec(&self, s: Ast) -> Result<Ast, Error> {
+        self.single_value_let(s, "letrec-values")
+    }
+
+    fn single_value_let(&self, s: Ast, core_form: &str) -> Result<Ast, Error> {
+        let m = match_syntax!((r#let ((id rhs) ...) body..+))(s)?;
+        let bindings = list_to_cons(
+            m.id.to_list_checked::<Error>()?
+                .into_iter()
+                .zip(m.rhs.to_list_checked::<Error>()?),
+            |(id, rhs)| sexpr!(((#(id)) #(rhs))),
+        );
+        Ok(self.core_datum_to_syntax(list!(Ast::Symbol(core_form.into()), bindings; m.body)))
+    }
+
+    // (let* () body ...+) => (let-values () body ...)
+    // (let* ((id rhs) binding ...) body ...+) => (let ((id rhs)) (let* (binding ...) body ...))
+    fn macro_let_star(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((let_star (binding ...) body..+))(s)?;
+        let bindings = m.binding.to_list_checked::<Error>()?;
+        Ok(self.core_datum_to_syntax(match bindings.split_first() {
+            None => sexpr!(("let-values" () . #(m.body))),
+            Some((first, rest)) => {
+                let rest = list_to_cons(rest.iter().cloned(), |binding| binding);
+                sexpr!((let (#(first.clone())) ("let*" #(rest) . #(m.body))))
+            }
+        }))
+    }
+
+    // the clauses are gone through in order, the first one whose test is true has its body run:
+    // (test body ...+) runs body, (test) results in the value of test, and (else body ...+), which
+    // must be the last clause, is always run
+    // if no clause matches the result is the empty list
+    fn macro_cond(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((cond clause ...))(s)?;
+        let clauses = m.clause.to_list_checked::<Error>()?;
+        let last = clauses.len().saturating_sub(1);
+        let expr = clauses.into_iter().enumerate().try_rfold(
+            sexpr!((quote())),
+            |rest, (i, clause)| -> Result<_, Error> {
+                if let Ok(m) = match_syntax!((test body..+))(clause.clone()) {
+                    if Self::is_else(&m.test) {
+                        if i != last {
+                            Err(format!("cond: else clause must be last {clause}"))?;
+                        }
+                        return Ok(sexpr!(("let-values" () . #(m.body))));
+                    }
+                    return Ok(sexpr!((if #(m.test) ("let-values" () . #(m.body)) #(rest))));
+                }
+                let m = match_syntax!((test))(clause)?;
+                Ok(sexpr!(("let-values" (((t) #(m.test))) (if t t #(rest)))))
+            },
+        )?;
+        Ok(self.core_datum_to_syntax(expr))
+    }
+
+    // (case key ((datum ...) body ...+) ... [(else body ...+)]) runs the body of the first clause
+    // that has a datum which is equal? to the value of key
+    fn macro_case(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((case key clause ...))(s)?;
+        let clauses = m.clause.to_list_checked::<Error>()?;
+        let last = clauses.len().saturating_sub(1);
+        let expr = clauses.into_iter().enumerate().try_rfold(
+            sexpr!((quote())),
+            |rest, (i, clause)| -> Result<_, Error> {
+                let m = match_syntax!((data body..+))(clause.clone())?;
+                if Self::is_else(&m.data) {
+                    if i != last {
+                        Err(format!("case: else clause must be last {clause}"))?;
+                    }
+                    return Ok(sexpr!(("let-values" () . #(m.body))));
+                }
+                let tests = list_to_cons(
+                    m.data.to_list_checked::<Error>()?.into_iter(),
+                    |datum| sexpr!(("equal?" t (quote #(datum)))),
+                );
+                Ok(sexpr!((if (or . #(tests)) ("let-values" () . #(m.body)) #(rest))))
+            },
+        )?;
+        Ok(self.core_datum_to_syntax(sexpr!(("let-values" (((t) #(m.key))) #(expr)))))
+    }
+
+    // (when test body ...+) => (if test (let-values () body ...) (quote ()))
+    fn macro_when(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((when test body..+))(s)?;
+        Ok(self.core_datum_to_syntax(sexpr!(
+            (if #(m.test) ("let-values" () . #(m.body)) (quote ()))
+        )))
+    }
+
+    // (and) => true, (and e) => e, (and e rest ...) => (if e (and rest ...) false)
+    fn macro_and(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((and e ...))(s)?;
+        Ok(self.core_datum_to_syntax(match m.e {
+            Ast::TheEmptyList => Ast::Boolean(Boolean::True),
+            Ast::Pair(p) if p.1 == Ast::TheEmptyList => p.0,
+            Ast::Pair(p) => {
+                sexpr!((if #(p.0) (and . #(p.1)) #(Ast::Boolean(Boolean::False))))
+            }
+            e => Err(format!("and: bad syntax {e}"))?,
+        }))
+    }
+
+    // (or) => false, (or e) => e, (or e rest ...) => (let ((t e)) (if t t (or rest ...)))
+    fn macro_or(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((or e ...))(s)?;
+        Ok(self.core_datum_to_syntax(match m.e {
+            Ast::TheEmptyList => Ast::Boolean(Boolean::False),
+            Ast::Pair(p) if p.1 == Ast::TheEmptyList => p.0,
+            Ast::Pair(p) => sexpr!(("let-values" (((t) #(p.0))) (if t t (or . #(p.1))))),
+            e => Err(format!("or: bad syntax {e}"))?,
+        }))
+    }
+
+    // else is only bound so that cond and case can recognize it hygienically (a local variable
+    // named else is not an else clause)
+    fn macro_else(&self, s: Ast) -> Result<Ast, Error> {
+        Err(format!("else: not allowed as an expression {s}").into())
+    }
+
+    fn is_else(id: &Ast) -> bool {
+        Syntax::<Symbol>::try_from(id.clone())
+            .ok()
+            .and_then(|id| Self::resolve(&id, false).ok())
+            .is_some_and(|binding| binding == Binding::TopLevel("else".into()))
+    }
+}
@@ -0,0 +1,107 @@
+use itertools::Itertools;
+use matcher_proc_macro::match_syntax;
+
+use crate::{
+    ast::{syntax::Syntax, Ast, Symbol},
+    error::Error,
+    list, sexpr,
+};
+
+use super::{expand_expr::list_to_cons, Expander};
+
+// the lexer allows empty statement bodies, but let-values needs at least one expression
+fn block(body: Ast) -> Ast {
+    if body == Ast::TheEmptyList {
+        list!(sexpr!((quote())))
+    } else {
+        body
+    }
+}
+
+impl Expander {
+    pub fn add_core_macros(&mut self) {
+        self.add_core_macro("while".into(), Self::macro_while);
+        self.add_core_macro("for".into(), Self::macro_for);
+        self.add_core_macro("class".into(), Self::macro_class);
+    }
+
+    // (while test body ...) => (loop (lambda (0) (if test (let-values () body ...) (stop))))
+    fn macro_while(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((r#while test body ...))(s)?;
+        Ok(self.core_datum_to_syntax(sexpr!(
+            (loop (lambda (0) (if #(m.test) ("let-values" () . #(block(m.body))) (stop))))
+        )))
+    }
+
+    // the iteration protocol: we keep a stack of what is left to go through, which starts with just
+    // the thing we are iterating over, each iteration pops the top of the stack:
+    // - the empty list is skipped
+    // - a pair has its car and then cdr pushed back, so lists are gone through in order and trees
+    //   have all their leaves gone through from left to right
+    // - anything else is bound to name and the body is run
+    // we stop when the stack is empty
+    fn macro_for(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((r#for name iter body ...))(s)?;
+        let _: Syntax<Symbol> = m.name.clone().try_into()?;
+        let step = sexpr!(
+            ("let-values" (((next) (car pending)))
+                ("set-bang" pending (cdr pending))
+                (if ("pair?" next)
+                    (begin
+                        ("set-bang" pending (cons (car next) (cons (cdr next) pending)))
+                        (skip))
+                    (quote ()))
+                (if ("null?" next) (skip) (quote ()))
+                ("let-values" (((#(m.name)) next)) . #(block(m.body))))
+        );
+        Ok(self.core_datum_to_syntax(sexpr!(
+            ("let-values" (((pending) (cons #(m.iter) (quote ()))))
+                (loop (lambda (0) (if ("null?" pending) (stop) (quote ())) #(step))))
+        )))
+    }
+
+    // (class name (field) ... (method implementation) ...) defines name as a constructor that takes
+    // each field (in order), and each method as a function that given an object returns that
+    // objects method
+    // the methods (and each other) can use the fields by name, and the object is a list of the
+    // methods
+    fn macro_class(&self, s: Ast) -> Result<Ast, Error> {
+        let m = match_syntax!((class name member ...))(s)?;
+        let mut fields = vec![];
+        let mut methods = vec![];
+        for member in m.member.to_list_checked::<Error>()? {
+            if let Ok(field) = match_syntax!((field))(member.clone()) {
+                fields.push(field.field);
+            } else {
+                let method = match_syntax!((method implementation))(member)?;
+                methods.push((method.method, method.implementation));
+            }
+        }
+        let field_count = Ast::from(fields.len());
+        let field_bindings = list_to_cons(
+            fields.into_iter().enumerate(),
+            |(i, field)| sexpr!(((#(field)) (param #(Ast::from(i))))),
+        );
+        let method_names = methods.iter().map(|(name, _)| name.clone()).collect_vec();
+        let method_bindings = list_to_cons(
+            methods.into_iter(),
+            |(name, implementation)| sexpr!(((#(name)) #(implementation))),
+        );
+        let object = method_names.iter().rfold(
+            sexpr!((quote())),
+            |rest, name| sexpr!((cons #(name.clone()) #(rest))),
+        );
+        let constructor = sexpr!(
+            (lambda (#(field_count))
+                ("let-values" #(field_bindings) ("letrec-values" #(method_bindings) #(object))))
+        );
+        let selectors = (0..method_names.len()).map(|i| {
+            let method = (0..i).fold(sexpr!((param 0)), |object, _| sexpr!((cdr #(object))));
+            sexpr!((lambda (1) (car #(method))))
+        });
+        let ids = list!(m.name; list_to_cons(method_names.into_iter(), |name| name));
+        Ok(self.core_datum_to_syntax(sexpr!(
+            ("define-values" #(ids) (values #(constructor) . #(list_to_cons(selectors, |s| s))))
+        )))
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+
+use crate::ast::{scope::Scope, Ast, Symbol};
+
+use super::Expander;
+
+/// One application of a macro, as recorded by the macro stepper.
+#[derive(Clone, Debug)]
+pub struct Step {
+    /// The name of the binding the macro was resolved to, which is not the name it was used with
+    /// if it was renamed or prefixed when it was required.
+    pub name: Symbol,
+    pub input: Ast,
+    pub output: Ast,
+    /// The scope added to the input and flipped on the output, so it ends up on only the syntax the
+    /// macro introduced.
+    pub introduction_scope: Scope,
+    /// The scope added to the input when the macro is used in a definition context.
+    pub use_site_scope: Option<Scope>,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.name)?;
+        writeln!(f, "    {}", self.input.clone().syntax_to_datum())?;
+        writeln!(f, " => {}", self.output.clone().syntax_to_datum())?;
+        write!(f, "    introduction scope: {}", self.introduction_scope.0)?;
+        match &self.use_site_scope {
+            Some(scope) => write!(f, ", use-site scope: {}", scope.0),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Expander {
+    /// Starts recording every macro application, until the steps are taken with
+    /// [`Expander::take_steps`].
+    pub fn start_stepping(&mut self) {
+        self.steps.get_or_insert_with(Vec::new);
+    }
+
+    /// Calls `on_step` with the number and step of every macro application as soon as it is
+    /// recorded, so that steps can be shown while expanding instead of after it.
+    pub fn on_step(&mut self, on_step: impl FnMut(usize, &Step) + 'static) {
+        self.on_step = Some(Box::new(on_step));
+    }
+
+    /// Stops recording, and returns the steps recorded since [`Expander::start_stepping`] in the
+    /// order the macros were applied.
+    pub fn take_steps(&mut self) -> Vec<Step> {
+        self.steps.take().unwrap_or_default()
+    }
+
+    pub(crate) const fn stepping(&self) -> bool {
+        self.steps.is_some()
+    }
+
+    pub(crate) fn record_step(
+        &mut self,
+        name: Symbol,
+        input: Option<Ast>,
+        output: &Ast,
+        introduction_scope: Scope,
+        use_site_scope: Option<Scope>,
+    ) {
+        if let (Some(steps), Some(input)) = (&mut self.steps, input) {
+            let step = Step {
+                name,
+                input,
+                output: output.clone(),
+                introduction_scope,
+                use_site_scope,
+            };
+            if let Some(on_step) = &mut self.on_step {
+                on_step(steps.len() + 1, &step);
+            }
+            steps.push(step);
+        }
+    }
+}
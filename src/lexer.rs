@@ -1,12 +1,12 @@
 #![allow(dead_code)]
 
 use parse_int::parse;
-use std::iter;
+use std::{cell::RefCell, iter};
 // chars on us keyboard not used: `, , \,/,,,=
 // qussiquote -> :
 // unquote -> $
 use crate::{
-    ast::{Ast, Boolean, Pair},
+    ast::{syntax::SourceLocation, Ast, Boolean, Pair},
     list,
     pc::{
         alt, any_of, chain, char, choice, inbetween, keep_left, keep_right, many, many1, map,
@@ -149,6 +149,38 @@ pub fn everything_parse(input: &str) -> Result<Vec<Ast>, ParseError<'_, Error>>
     )
 }
 
+thread_local! {
+    // the name and text of what is being parsed by `everything_parse_file`
+    static SOURCE: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+/// Like [`everything_parse`], but each label is a syntax object with where it is in `file`, so
+/// that problems with labels can be reported with their location.
+pub fn everything_parse_file<'a>(
+    file: &str,
+    input: &'a str,
+) -> Result<Vec<Ast>, ParseError<'a, Error>> {
+    SOURCE.set(Some((file.to_string(), input.to_string())));
+    let result = everything_parse(input);
+    SOURCE.set(None);
+    result
+}
+
+// where `rest` (which is what is left of the input) starts in what is being parsed
+fn source_location(rest: &str) -> Option<SourceLocation> {
+    SOURCE.with_borrow(|source| {
+        let (file, text) = source.as_ref()?;
+        let before = text.get(..text.len().checked_sub(rest.len())?)?;
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+        Some(SourceLocation::new(
+            file.clone(),
+            u32::try_from(line).ok()?,
+            u32::try_from(column).ok()?,
+        ))
+    })
+}
+
 fn literal() -> Box<Parser<Ast, Error>> {
     choice([boolean(), hexnumber(), stringdot()].to_vec())
 }
@@ -575,7 +607,15 @@ fn unquoted_everything() -> Box<Parser<Ast, Error>> {
 }
 
 fn label_everything() -> Box<Parser<Ast, Error>> {
-    map(keep_right(char('@'), ident()), |res| Ast::Label(res.into()))
+    let label = map(keep_right(char('@'), ident()), |res| Ast::Label(res.into()));
+    Box::new(move |input| {
+        let (label, rest) = label(input)?;
+        let label = match source_location(input) {
+            Some(srcloc) => label.datum_to_syntax(None, Some(srcloc), None),
+            None => label,
+        };
+        Ok((label, rest))
+    })
 }
 
 fn param_everything() -> Box<Parser<Ast, Error>> {
@@ -17,7 +17,11 @@ use std::collections::HashMap;
 
 use itertools::Itertools;
 
-use crate::{ast::ast1::Ast1, interior_mut::RC, lexer::everything_parse};
+use crate::{
+    ast::ast1::Ast1,
+    interior_mut::RC,
+    lexer::{everything_parse, everything_parse_file},
+};
 use std::fs;
 use std::io::BufReader;
 use std::io::Read;
@@ -290,7 +294,7 @@ impl MacroExpander {
             let mut buf = BufReader::new(file);
             let mut contents = String::new();
             buf.read_to_string(&mut contents);
-            everything_parse(&contents).unwrap()
+            everything_parse_file(&path.to_string(), &contents).unwrap()
         } else {
             exprs.to_vec()
         };
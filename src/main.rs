@@ -10,18 +10,23 @@
 use std::{
     cell::RefCell,
     collections::HashMap,
+    env,
     error::Error,
-    fs, mem,
+    fs,
+    io::{self, Write},
+    mem,
     rc::Rc,
     sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
 };
 
-use ast::{ast2::Ast2, scope::Scope, Ast, AstTransformFrom, Symbol};
+use ast::{ast1::Ast1, ast2::Ast2, scope::Scope, Ast, AstTransformFrom, Symbol};
 use codegen::{
     register_to_llvm::CodeGen,
     sicp::{self, Linkage, Register},
 };
-use expander::{expand_context::ExpandContext, Expander};
+use evaluator::{Evaluator, Strategy};
+use expander::{expand_context::ExpandContext, label::LabelDiagnostic, stepper::Step, Expander};
 //use codegen::{
 //    register_to_llvm::CodeGen,
 //    sicp::{Linkage, Register},
@@ -73,19 +78,58 @@ pub struct Args {
 #[derive(Subcommand, Clone, Debug)]
 pub enum ArgType {
     /// Start an `Everything` repl
-    Repl,
+    Repl {
+        /// Show each macro application while expanding each input
+        #[arg(long)]
+        step: bool,
+        /// How the arguments of functions are evaluated
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+        /// Seed for resolving `?` [default: $EVERYTHING_SEED or a random seed]
+        #[arg(long)]
+        seed: Option<u32>,
+        /// Keep the outcome of the first test of each `?` value
+        #[arg(long)]
+        observe_once: bool,
+    },
     /// Compile some code
     Compile {
         filename: String,
         /// Output file name excluding file extension
         output: String,
+        /// Seed the program resolves `?` with [default: $EVERYTHING_SEED when the program is run
+        /// or the time]
+        #[arg(long)]
+        seed: Option<u32>,
+        /// Keep the outcome of the first test of each `?` value
+        #[arg(long)]
+        observe_once: bool,
     },
     /// Run some code
     Run {
         filename: String,
+        /// How the arguments of functions are evaluated
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+        /// Seed for resolving `?` [default: $EVERYTHING_SEED or a random seed]
+        #[arg(long)]
+        seed: Option<u32>,
+        /// Keep the outcome of the first test of each `?` value
+        #[arg(long)]
+        observe_once: bool,
     },
+    /// Show what some code expands to
     Expand {
         filename: String,
+        /// Show each macro application one by one
+        #[arg(long)]
+        step: bool,
+        /// Show the scopes of each identifier, and what it resolves to
+        #[arg(long)]
+        scopes: bool,
+        /// Time expanding the program this many times, with and without the expansion cache
+        #[arg(long, conflicts_with_all = ["step", "scopes"])]
+        bench: Option<u32>,
     },
 }
 
@@ -93,10 +137,35 @@ fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
     simple_file_logger::init_logger!("everything-lang", args.log_level.unwrap_or_default())?;
     match args.arg {
-        ArgType::Repl => repl(),
-        ArgType::Compile { filename, output } => compile(&filename, &output),
-        ArgType::Run { filename } => run(&filename),
-        ArgType::Expand { filename } => expand(&filename),
+        ArgType::Repl {
+            step,
+            strategy,
+            seed,
+            observe_once,
+        } => repl(step, strategy, seed, observe_once),
+        ArgType::Compile {
+            filename,
+            output,
+            seed,
+            observe_once,
+        } => compile(&filename, &output, seed, observe_once),
+        ArgType::Run {
+            filename,
+            strategy,
+            seed,
+            observe_once,
+        } => run(&filename, strategy, seed, observe_once),
+        ArgType::Expand {
+            filename,
+            bench: Some(iterations),
+            ..
+        } => bench_expand(&filename, iterations)?,
+        ArgType::Expand {
+            filename,
+            step,
+            scopes,
+            bench: None,
+        } => expand(&filename, step, scopes),
     }
     Ok(())
 }
@@ -131,27 +200,181 @@ fn init_function_optimizer<'ctx>(
     fpm
 }
 
-const fn repl() {}
+// the seed that `?` is resolved with, which is printed when something fails so that the run can be
+// replayed with `--seed` or `EVERYTHING_SEED`
+fn seed(seed: Option<u32>) -> u32 {
+    let seed = seed
+        .or_else(|| env::var("EVERYTHING_SEED").ok()?.parse().ok())
+        .unwrap_or_else(rand::random);
+    Evaluator::seed(seed);
+    seed
+}
+
+fn repl(step: bool, strategy: Strategy, seed: Option<u32>, observe_once: bool) {
+    let seed = self::seed(seed);
+    Evaluator::observe_once(observe_once);
+    let mut expander = Expander::new();
+    expander.strategy = strategy;
+    if step {
+        expander.on_step(print_step);
+    }
+    let ns = expander.namespace();
+    let ctx = ExpandContext::new(ns.clone());
+    let mut input = String::new();
+    loop {
+        print!("\n>> ");
+        io::stdout().flush().unwrap();
+        input.clear();
+        if io::stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
+        let Ok(program) = lexer::everything_parse_file("<repl>", &input) else {
+            println!("could not parse {input}");
+            continue;
+        };
+        for ele in program {
+            if step {
+                expander.start_stepping();
+            }
+            let ele = expander.namespace_syntax_introduce(ele.datum_to_syntax(None, None, None));
+            let expanded = expander.expand_top_level(ele, ctx.clone());
+            expander.take_steps();
+            match expanded
+                .and_then(|ele| expander.compile(ele, &ns))
+                .and_then(|ele| expander.run_time_eval(ele))
+            {
+                Ok(values) => println!("{values}"),
+                Err(e) => println!("{e:?}\nseed: {seed}"),
+            }
+        }
+    }
+}
 
-const fn run(file: &str) {}
+fn run(file: &str, strategy: Strategy, seed: Option<u32>, observe_once: bool) {
+    let seed = self::seed(seed);
+    Evaluator::observe_once(observe_once);
+    let mut expander = Expander::new();
+    expander.strategy = strategy;
+    let contents = fs::read_to_string(file).unwrap();
+    let ns = expander.namespace();
+    let ctx = ExpandContext::new(ns.clone());
+    let mut program = vec![];
+    for ele in lexer::everything_parse_file(file, &contents).unwrap() {
+        let ele = expander.namespace_syntax_introduce(ele.datum_to_syntax(None, None, None));
+        let ele = expander.expand_top_level(ele, ctx.clone()).unwrap();
+        program.push(expander.compile(ele, &ns).unwrap());
+    }
+    if !check_labels(&expander) || program.is_empty() {
+        return;
+    }
+    // the program is run as a whole, so a goto can jump between its top level forms
+    if let Err(e) = expander.run_time_eval(Ast1::Begin(program)) {
+        println!("{e:?}\nseed: {seed}");
+    }
+}
 
-fn expand(file: &str) {
+fn expand(file: &str, step: bool, scopes: bool) {
+    let mut expander = Expander::new();
+    if step {
+        expander.on_step(print_step);
+    }
     let contents = fs::read_to_string(file).unwrap();
-    //let program = parse_and_expand(&contents).unwrap();
-    //println!("{program:?}");
+    let ns = expander.namespace();
+    let ctx = ExpandContext::new(ns);
+    for ele in lexer::everything_parse_file(file, &contents).unwrap() {
+        if step {
+            expander.start_stepping();
+        }
+        let ele = expander.namespace_syntax_introduce(ele.datum_to_syntax(None, None, None));
+        let expanded = expander.expand_top_level(ele, ctx.clone());
+        expander.take_steps();
+        match expanded {
+            Ok(ele) => {
+                if scopes {
+                    for resolution in Expander::resolutions(&ele) {
+                        println!("{resolution}");
+                    }
+                }
+                println!("{}", ele.syntax_to_datum());
+            }
+            Err(e) => {
+                if let Some(resolution) = Expander::error_resolution(&e).filter(|_| scopes) {
+                    println!("{resolution}");
+                }
+                println!("{e:?}");
+            }
+        }
+    }
 }
 
-fn compile(file: &str, out: &str) {
+// times expanding the program (like `run` and `compile` do) `iterations` times with and without
+// the expansion cache, which should not change what the program expands to
+fn bench_expand(file: &str, iterations: u32) -> Result<(), Box<dyn Error>> {
+    let contents = fs::read_to_string(file)?;
+    let program = lexer::everything_parse_file(file, &contents)
+        .map_err(|e| format!("could not parse {file}: {e:?}"))?;
+    let run = |expansion_cache| -> Result<(Duration, Vec<Ast>), String> {
+        let mut elapsed = Duration::ZERO;
+        let mut expanded = vec![];
+        for _ in 0..iterations {
+            let mut expander = Expander::new();
+            expander.expansion_cache = expansion_cache;
+            let ctx = ExpandContext::new(expander.namespace());
+            let start = Instant::now();
+            expanded = program
+                .iter()
+                .map(|ele| {
+                    let ele = expander
+                        .namespace_syntax_introduce(ele.clone().datum_to_syntax(None, None, None));
+                    expander
+                        .expand_top_level(ele, ctx.clone())
+                        .map(Ast::syntax_to_datum)
+                })
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("{e:?}"))?;
+            elapsed += start.elapsed();
+        }
+        Ok((elapsed, expanded))
+    };
+    let (cached, cached_output) = run(true)?;
+    let (uncached, uncached_output) = run(false)?;
+    println!("with the expansion cache: {cached:?}, without it: {uncached:?}");
+    if cached_output != uncached_output {
+        return Err(format!("the expansion cache changed what {file} expands to").into());
+    }
+    Ok(())
+}
+
+// prints every problem with the labels of the program, and whether none of them are errors
+fn check_labels(expander: &Expander) -> bool {
+    let diagnostics = match expander.check_labels() {
+        Ok(warnings) => warnings,
+        Err(error::Error::InvalidLabels(error::InvalidLabels(diagnostics))) => diagnostics,
+        Err(e) => panic!("{e:?}"),
+    };
+    for diagnostic in &diagnostics {
+        eprintln!("{diagnostic}");
+    }
+    !diagnostics.iter().any(LabelDiagnostic::is_error)
+}
+
+// printed as soon as the macro is applied, so the steps before an expansion error are still shown
+fn print_step(i: usize, step: &Step) {
+    println!("step {i}: {step}\n");
+}
+
+fn compile(file: &str, out: &str, seed: Option<u32>, observe_once: bool) {
     let mut expander = Expander::new();
     // let env = CompileTimeEnvoirnment::new();
     let contents = fs::read_to_string(file).unwrap();
     let ns = expander.namespace();
     let ctx = ExpandContext::new(ns.clone());
-    for ele in lexer::everything_parse(&contents).unwrap() {
+    let mut program = vec![];
+    for ele in lexer::everything_parse_file(file, &contents).unwrap() {
         eprintln!("new ele");
         eprintln!("{ele}");
         let ele = expander.namespace_syntax_introduce(ele.datum_to_syntax(None, None, None));
-        let ele = expander.expand(ele, ctx.clone()).unwrap();
+        let ele = expander.expand_top_level(ele, ctx.clone()).unwrap();
         eprintln!("done expanding");
         eprintln!("{ele}");
         let ele = expander.compile(ele, &ns).unwrap();
@@ -160,12 +383,21 @@ fn compile(file: &str, out: &str) {
         // let ele_val = expander.run_time_eval(ele.clone()).unwrap();
         // eprintln!("done evaluation");
         // eprintln!("{ele_val}");
-        let links = MultiMap::from(
-            mem::take(&mut expander.links)
-                .into_iter()
-                .map(|(k, ks)| (ks, k.clone(), k)),
-        );
-        let (ele, _) = Ast2::transform(ele, links).unwrap();
+        program.push(ele);
+    }
+    // a label can be linked from anywhere in the program, so the labels can only be checked (and
+    // the links used) once the whole program is compiled
+    if !check_labels(&expander) {
+        return;
+    }
+    let mut links = MultiMap::from(
+        mem::take(&mut expander.links)
+            .into_iter()
+            .map(|(k, ks)| (ks, k.clone(), k)),
+    );
+    for ele in program {
+        let (ele, rest_links) = Ast2::transform(ele, links).unwrap();
+        links = rest_links;
         let ele = sicp::compile(
             ele,
             Register::Val,
@@ -180,7 +412,7 @@ fn compile(file: &str, out: &str) {
         let module = context.create_module(file);
         let builder = context.create_builder();
         let fpm = init_function_optimizer(&module);
-        let mut codegen = CodeGen::new(&context, &builder, &module, &fpm);
+        let mut codegen = CodeGen::new(&context, &builder, &module, &fpm, seed, observe_once);
         codegen.compile(ele);
 
         codegen.export_ir(out);
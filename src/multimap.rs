@@ -39,9 +39,6 @@ impl<K: Hash + Eq, V> MultiMap<K, V> {
         // so in short the method signatures of the MultiMap garuntee the safety of using unsafe
         self.keys.get(key).map(|v| unsafe { v.as_ref() })
     }
-    pub fn has_key(&self, key: &K) -> bool {
-        self.values.get(key).is_some()
-    }
     /// allows you to mutate a value based on its mutatable key
     pub fn set(&mut self, key: &K, setter: impl FnOnce(&V) -> V) -> Option<()> {
         // SAFETY: were allowed to obtain an exlusive reference to the value because you can only obtain an exclusive reference to the value
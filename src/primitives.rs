@@ -1,30 +1,112 @@
-use std::rc::Rc;
+use std::{iter, rc::Rc};
+
+use itertools::Itertools;
 
 use crate::{
-    ast::{Ast, Boolean, Function, Pair, Primitive},
+    ast::{syntax::Syntax, Ast, Boolean, Function, HashTable, Pair, Primitive, Symbol},
     error::Error,
-    evaluator::Values,
+    evaluator::{Evaluator, Values},
+    expander::{expand_expr::list_to_cons, Expander},
+    interior_mut::RC,
+    UniqueNumberManager,
 };
 
 impl Ast {
+    // (datum-to-syntax context datum [srcloc [properties]]) the scopes are taken from context, the
+    // source location from srcloc and the properties (which only go on the outermost syntax
+    // object) from properties
     pub fn primitive_datum_to_syntax(self) -> Result<Values, Error> {
         let arity = self.size();
-        let Self::Pair(e) = self else {
-            Err(format!("arity error: expected 2 argument, got {arity}",))?
-        };
-        let Pair(scopes, Self::Pair(syntax_object)) = *e else {
-            Err(format!("arity error: expected 2 argument, got {arity}"))?
-        };
-        let Pair(syntax_object, Self::TheEmptyList) = *syntax_object else {
-            Err(format!("arity error: expected 1 argument, got {arity}"))?
+        let args = self.to_list_checked::<Error>()?;
+        let (context, datum, srcloc, properties) = match args.as_slice() {
+            [context, datum] => (context, datum, None, None),
+            [context, datum, srcloc] => (context, datum, Some(srcloc), None),
+            [context, datum, srcloc, properties] => {
+                (context, datum, Some(srcloc), Some(properties))
+            }
+            _ => Err(format!(
+                "arity error: expected 2 to 4 arguments, got {arity}"
+            ))?,
         };
-        // TODO: properties and location
-        Ok(Values::Single(syntax_object.datum_to_syntax(
-            scopes.scope_set(),
-            None,
+        let syntax_object = datum.clone().datum_to_syntax(
+            context.scope_set(),
+            srcloc.and_then(Self::syntax_src_loc),
             None,
+        );
+        Ok(Values::Single(
+            match (syntax_object, properties.and_then(Self::properties)) {
+                (Self::Syntax(mut s), Some(properties)) if !matches!(datum, Self::Syntax(_)) => {
+                    s.3 = properties;
+                    Self::Syntax(s)
+                }
+                (syntax_object, _) => syntax_object,
+            },
+        ))
+    }
+    // (syntax-property syntax key) is the value of the property key (or false if there is none),
+    // and (syntax-property syntax key value) is syntax with key set to value
+    pub fn primitive_syntax_property(self) -> Result<Values, Error> {
+        let arity = self.size();
+        match self.to_list_checked::<Error>()?.as_slice() {
+            [Self::Syntax(s), Self::Symbol(key)] => Ok(Values::Single(
+                s.3.get(key).cloned().unwrap_or(Self::Boolean(Boolean::False)),
+            )),
+            [Self::Syntax(s), Self::Symbol(key), value] => {
+                let mut s = s.clone();
+                s.3.insert(key.clone(), value.clone());
+                Ok(Values::Single(Self::Syntax(s)))
+            }
+            _ => Err(format!(
+                "syntax-property: expected syntax, a symbol and maybe a value, got {arity} arguments"
+            ))?,
+        }
+    }
+    fn identifier_arguments(self, name: &str) -> Result<(Syntax<Symbol>, Syntax<Symbol>), Error> {
+        let arity = self.size();
+        let [a, b] = <[Self; 2]>::try_from(self.to_list_checked::<Error>()?)
+            .map_err(|_| format!("arity error: expected 2 argument, got {arity}, {name}"))?;
+        Ok((a.try_into()?, b.try_into()?))
+    }
+    // two identifiers are free-identifier=? if they refer to the same binding (or are both unbound
+    // and have the same name)
+    pub fn primitive_free_identifier(self) -> Result<Values, Error> {
+        let (a, b) = self.identifier_arguments("free-identifier=?")?;
+        Ok(Values::Single(Self::Boolean(
+            Expander::free_identifier(a, b)?.into(),
         )))
     }
+    // two identifiers are bound-identifier=? if a binding of one would bind the other, that is they
+    // have the same name and scopes
+    pub fn primitive_bound_identifier(self) -> Result<Values, Error> {
+        let (a, b) = self.identifier_arguments("bound-identifier=?")?;
+        Ok(Values::Single(Self::Boolean(a.bound_identifier(&b).into())))
+    }
+    // a fresh identifier for each element of the list, named after it if it is an identifier
+    pub fn primitive_generate_temporaries(self) -> Result<Values, Error> {
+        let arity = self.size();
+        let Self::Pair(e) = self else {
+            Err(format!(
+                "arity error: expected 1 argument, got {arity}, generate-temporaries"
+            ))?
+        };
+        let Pair(list, Self::TheEmptyList) = *e else {
+            Err(format!(
+                "arity error: expected 1 argument, got {arity}, generate-temporaries"
+            ))?
+        };
+        let temporaries = list
+            .unsyntax()
+            .to_list_checked::<Error>()?
+            .into_iter()
+            .map(|e| {
+                let name = match e.unsyntax() {
+                    Self::Symbol(name) => name.to_string(),
+                    _ => "temp".to_string(),
+                };
+                Self::Symbol(UniqueNumberManager::gen_sym(name)).datum_to_syntax(None, None, None)
+            });
+        Ok(Values::Single(list_to_cons(temporaries, |e| e)))
+    }
     pub fn primitive_syntax_to_datum(self) -> Result<Values, Error> {
         let Self::Pair(e) = self else {
             Err(format!(
@@ -146,6 +228,341 @@ impl Ast {
             _ => self.to_list_checked().map(Values::Many),
         }
     }
+    pub fn primitive_pair(self) -> Result<Values, Error> {
+        match self {
+            Self::Pair(p) if matches!(*p, Pair(Self::Pair(_), Self::TheEmptyList)) => {
+                Ok(Values::Single(Self::Boolean(Boolean::True)))
+            }
+            _ => Ok(Values::Single(Self::Boolean(Boolean::False))),
+        }
+    }
+    pub fn primitive_equal(self) -> Result<Values, Error> {
+        let arity = self.size();
+        let Self::Pair(e) = self else {
+            Err(format!(
+                "arity error: expected 2 argument, got {arity}, equal?"
+            ))?
+        };
+        let Pair(fst, Self::Pair(last)) = *e else {
+            Err(format!(
+                "arity error: expected 2 argument, got {arity}, equal?"
+            ))?
+        };
+        let Pair(snd, Self::TheEmptyList) = *last else {
+            Err(format!(
+                "arity error: expected 2 argument, got {arity}, equal?"
+            ))?
+        };
+        Ok(Values::Single(Self::Boolean(if fst == snd {
+            Boolean::True
+        } else {
+            Boolean::False
+        })))
+    }
+    // any value that is not a boolean is true
+    const fn to_boolean(&self) -> Boolean {
+        match self {
+            Self::Boolean(b) => *b,
+            _ => Boolean::True,
+        }
+    }
+    fn boolean_arguments(self, name: &str) -> Result<(Boolean, Boolean), Error> {
+        let arity = self.size();
+        let [a, b] = <[Self; 2]>::try_from(self.to_list_checked::<Error>()?)
+            .map_err(|_| format!("arity error: expected 2 argument, got {arity}, {name}"))?;
+        Ok((a.to_boolean(), b.to_boolean()))
+    }
+    fn single_argument(self, name: &str) -> Result<Self, Error> {
+        let [e] = self.arguments(name)?;
+        Ok(e)
+    }
+    fn arguments<const N: usize>(self, name: &str) -> Result<[Self; N], Error> {
+        let arity = self.size();
+        <[Self; N]>::try_from(self.to_list_checked::<Error>()?)
+            .map_err(|_| format!("arity error: expected {N} argument, got {arity}, {name}").into())
+    }
+    pub fn primitive_kleene_and(self) -> Result<Values, Error> {
+        let (a, b) = self.boolean_arguments("kleene-and")?;
+        Ok(Values::Single(Self::Boolean(a.and(b))))
+    }
+    pub fn primitive_kleene_or(self) -> Result<Values, Error> {
+        let (a, b) = self.boolean_arguments("kleene-or")?;
+        Ok(Values::Single(Self::Boolean(a.or(b))))
+    }
+    pub fn primitive_xor(self) -> Result<Values, Error> {
+        let (a, b) = self.boolean_arguments("xor")?;
+        Ok(Values::Single(Self::Boolean(a.xor(b))))
+    }
+    pub fn primitive_implies(self) -> Result<Values, Error> {
+        let (a, b) = self.boolean_arguments("implies")?;
+        Ok(Values::Single(Self::Boolean(a.implies(b))))
+    }
+    // a `?` becomes `&` or `|` (the same way an if would decide it), anything else is unchanged
+    pub fn primitive_collapse(self) -> Result<Values, Error> {
+        Ok(Values::Single(match self.single_argument("collapse")? {
+            Self::Boolean(Boolean::Maybe) => Self::Boolean(Evaluator::resolve_maybe().into()),
+            e => e,
+        }))
+    }
+    pub fn primitive_maybe(self) -> Result<Values, Error> {
+        let e = self.single_argument("maybe?")?;
+        Ok(Values::Single(Self::Boolean(
+            matches!(e, Self::Boolean(Boolean::Maybe)).into(),
+        )))
+    }
+    pub fn primitive_definitely(self) -> Result<Values, Error> {
+        let e = self.single_argument("definitely?")?;
+        Ok(Values::Single(Self::Boolean(
+            (!matches!(e, Self::Boolean(Boolean::Maybe))).into(),
+        )))
+    }
+    fn numbers(self, name: &str) -> Result<Vec<f64>, Error> {
+        self.to_list_checked::<Error>()?
+            .into_iter()
+            .map(|e| match e {
+                Self::Number(n) => Ok(n),
+                e => Err(format!("{name}: expected a number, got {e}").into()),
+            })
+            .collect()
+    }
+    // for (-) and (/), which have no identity to fall back on
+    pub(crate) fn at_least_one_argument(self, name: &str) -> Result<Self, Error> {
+        if matches!(self, Self::TheEmptyList) {
+            Err(format!("arity error: expected at least 1 argument, got 0, {name}").into())
+        } else {
+            Ok(self)
+        }
+    }
+    // (op) is identity, (op x) is (op identity x), and (op x y z) is (op (op x y) z)
+    pub(crate) fn fold_numbers(
+        self,
+        name: &str,
+        identity: f64,
+        op: fn(f64, f64) -> Result<f64, Error>,
+    ) -> Result<Values, Error> {
+        let numbers = self.numbers(name)?;
+        let (start, rest) = match numbers.as_slice() {
+            [first, rest @ ..] if !rest.is_empty() => (*first, rest),
+            numbers => (identity, numbers),
+        };
+        rest.iter()
+            .try_fold(start, |acc, &n| op(acc, n))
+            .map(|n| Values::Single(Self::Number(n)))
+    }
+    // whether each number is `compare` to the one after it
+    pub(crate) fn compare_numbers(
+        self,
+        name: &str,
+        compare: fn(&f64, &f64) -> bool,
+    ) -> Result<Values, Error> {
+        let numbers = self.numbers(name)?;
+        Ok(Values::Single(Self::Boolean(
+            numbers
+                .iter()
+                .tuple_windows()
+                .all(|(a, b)| compare(a, b))
+                .into(),
+        )))
+    }
+    // = only compares numbers, but it used to compare anything so point at equal?
+    pub fn primitive_number_equal(self) -> Result<Values, Error> {
+        if let Some(e) = self
+            .clone()
+            .to_list_checked::<Error>()?
+            .into_iter()
+            .find(|e| !matches!(e, Self::Number(_)))
+        {
+            return Err(format!(
+                "=: expected a number, got {e}, use equal? to compare anything else"
+            )
+            .into());
+        }
+        self.compare_numbers("=", f64::eq)
+    }
+    pub(crate) fn unary_number(self, name: &str, op: fn(f64) -> f64) -> Result<Values, Error> {
+        let arity = self.size();
+        let [n] = <[f64; 1]>::try_from(self.numbers(name)?)
+            .map_err(|_| format!("arity error: expected 1 argument, got {arity}, {name}"))?;
+        Ok(Values::Single(Self::Number(op(n))))
+    }
+    pub(crate) fn binary_number(
+        self,
+        name: &str,
+        op: fn(f64, f64) -> Result<f64, Error>,
+    ) -> Result<Values, Error> {
+        let arity = self.size();
+        let [a, b] = <[f64; 2]>::try_from(self.numbers(name)?)
+            .map_err(|_| format!("arity error: expected 2 argument, got {arity}, {name}"))?;
+        op(a, b).map(|n| Values::Single(Self::Number(n)))
+    }
+    fn string(self, name: &str) -> Result<RC<str>, Error> {
+        match self {
+            Self::String(s) => Ok(s),
+            e => Err(format!("{name}: expected a string, got {e}").into()),
+        }
+    }
+    // an index into a string, which counts characters (not bytes)
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn index(self, name: &str) -> Result<usize, Error> {
+        match self {
+            Self::Number(n) if n >= 0. && n.fract() == 0. => Ok(n as usize),
+            e => Err(format!("{name}: expected an index, got {e}").into()),
+        }
+    }
+    fn string_result(s: impl Into<RC<str>>) -> Result<Values, Error> {
+        Ok(Values::Single(Self::String(s.into())))
+    }
+    pub fn primitive_string_length(self) -> Result<Values, Error> {
+        let s = self
+            .single_argument("string-length")?
+            .string("string-length")?;
+        Ok(Values::Single(Self::from(s.chars().count())))
+    }
+    pub fn primitive_string_append(self) -> Result<Values, Error> {
+        let strings = self
+            .to_list_checked::<Error>()?
+            .into_iter()
+            .map(|s| s.string("string-append"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::string_result(strings.concat())
+    }
+    // (substring s start [end]) the characters of s from start up to (but not including) end,
+    // which defaults to the length of s
+    pub fn primitive_substring(self) -> Result<Values, Error> {
+        let arity = self.size();
+        let (s, start, end) = match <[Self; 3]>::try_from(self.to_list_checked::<Error>()?) {
+            Ok([s, start, end]) => (s, start, Some(end)),
+            Err(args) => match <[Self; 2]>::try_from(args) {
+                Ok([s, start]) => (s, start, None),
+                Err(_) => Err(format!(
+                    "arity error: expected 2 or 3 arguments, got {arity}, substring"
+                ))?,
+            },
+        };
+        let s = s.string("substring")?;
+        let start = start.index("substring")?;
+        let end = end.map_or_else(|| Ok(s.chars().count()), |end| end.index("substring"))?;
+        Self::string_result(substring(&s, start, end, "substring")?)
+    }
+    // a string with just the character at the index
+    pub fn primitive_string_ref(self) -> Result<Values, Error> {
+        let [s, i] = self.arguments("string-ref")?;
+        let s = s.string("string-ref")?;
+        let i = i.index("string-ref")?;
+        Self::string_result(substring(&s, i, i + 1, "string-ref")?)
+    }
+    fn compare_strings(self, name: &str, compare: fn(&str, &str) -> bool) -> Result<Values, Error> {
+        let [a, b] = self.arguments(name)?;
+        let (a, b) = (a.string(name)?, b.string(name)?);
+        Ok(Values::Single(Self::Boolean(compare(&a, &b).into())))
+    }
+    pub fn primitive_string_equal(self) -> Result<Values, Error> {
+        self.compare_strings("string=?", |a, b| a == b)
+    }
+    // strings are ordered by their characters, from the first one on
+    pub fn primitive_string_less(self) -> Result<Values, Error> {
+        self.compare_strings("string<?", |a, b| a < b)
+    }
+    pub fn primitive_string_to_symbol(self) -> Result<Values, Error> {
+        let s = self
+            .single_argument("string->symbol")?
+            .string("string->symbol")?;
+        Ok(Values::Single(Self::Symbol(Symbol(s.to_string().into()))))
+    }
+    pub fn primitive_symbol_to_string(self) -> Result<Values, Error> {
+        match self.single_argument("symbol->string")? {
+            Self::Symbol(Symbol(s)) => Self::string_result(s.to_string()),
+            e => Err(format!("symbol->string: expected a symbol, got {e}"))?,
+        }
+    }
+    pub fn primitive_number_to_string(self) -> Result<Values, Error> {
+        match self.single_argument("number->string")? {
+            Self::Number(n) => Self::string_result(number_to_string(n)),
+            e => Err(format!("number->string: expected a number, got {e}"))?,
+        }
+    }
+    // false if the string is not a number
+    pub fn primitive_string_to_number(self) -> Result<Values, Error> {
+        let s = self
+            .single_argument("string->number")?
+            .string("string->number")?;
+        Ok(Values::Single(
+            string_to_number(&s).map_or(Self::Boolean(Boolean::False), Self::Number),
+        ))
+    }
+    // (string-split s separator) the parts of s between each separator
+    pub fn primitive_string_split(self) -> Result<Values, Error> {
+        let [s, separator] = self.arguments("string-split")?;
+        let (s, separator) = (s.string("string-split")?, separator.string("string-split")?);
+        if separator.is_empty() {
+            Err("string-split: the separator cannot be empty")?;
+        }
+        Ok(Values::Single(list_to_cons(
+            s.split(&*separator).map(|part| Self::String(part.into())),
+            |part| part,
+        )))
+    }
+    // (string-join strings separator) the strings with the separator between each of them
+    pub fn primitive_string_join(self) -> Result<Values, Error> {
+        let [strings, separator] = self.arguments("string-join")?;
+        let strings = strings
+            .to_list_checked::<Error>()?
+            .into_iter()
+            .map(|s| s.string("string-join"))
+            .collect::<Result<Vec<_>, _>>()?;
+        Self::string_result(strings.join(&*separator.string("string-join")?))
+    }
+    fn hash_table(self, name: &str) -> Result<HashTable, Error> {
+        match self {
+            Self::Hash(table) => Ok(table),
+            e => Err(format!("{name}: expected a hash table, got {e}").into()),
+        }
+    }
+    pub fn primitive_make_hash(self) -> Result<Values, Error> {
+        let [] = self.arguments::<0>("make-hash")?;
+        Ok(Values::Single(Self::Hash(HashTable::default())))
+    }
+    // (hash-ref table key [default]) without a default it is an error for the key to be missing
+    pub fn primitive_hash_ref(self) -> Result<Values, Error> {
+        let arity = self.size();
+        let (table, key, default) = match <[Self; 3]>::try_from(self.to_list_checked::<Error>()?) {
+            Ok([table, key, default]) => (table, key, Some(default)),
+            Err(args) => match <[Self; 2]>::try_from(args) {
+                Ok([table, key]) => (table, key, None),
+                Err(_) => Err(format!(
+                    "arity error: expected 2 or 3 arguments, got {arity}, hash-ref"
+                ))?,
+            },
+        };
+        match table.hash_table("hash-ref")?.get(&key).or(default) {
+            Some(value) => Ok(Values::Single(value)),
+            None => Err(format!("hash-ref: no value for the key {key}"))?,
+        }
+    }
+    pub fn primitive_hash_set(self) -> Result<Values, Error> {
+        let [table, key, value] = self.arguments("hash-set!")?;
+        table.hash_table("hash-set!")?.insert(key, value);
+        Ok(Values::Single(Self::TheEmptyList))
+    }
+    // removing a key that is not in the table does nothing
+    pub fn primitive_hash_remove(self) -> Result<Values, Error> {
+        let [table, key] = self.arguments("hash-remove!")?;
+        table.hash_table("hash-remove!")?.remove(&key);
+        Ok(Values::Single(Self::TheEmptyList))
+    }
+    pub fn primitive_hash_keys(self) -> Result<Values, Error> {
+        let table = self.single_argument("hash-keys")?.hash_table("hash-keys")?;
+        Ok(Values::Single(list_to_cons(
+            table.keys().into_iter(),
+            |key| key,
+        )))
+    }
+    pub fn primitive_hash_count(self) -> Result<Values, Error> {
+        let table = self
+            .single_argument("hash-count")?
+            .hash_table("hash-count")?;
+        Ok(Values::Single(Self::from(table.len())))
+    }
     pub fn primitive_null(self) -> Result<Values, Error> {
         match self {
             Self::Pair(p) if *p == Pair(Self::TheEmptyList, Self::TheEmptyList) => {
@@ -156,6 +573,107 @@ impl Ast {
     }
 }
 
+// the characters of s from start up to end
+fn substring<'a>(s: &'a str, start: usize, end: usize, name: &str) -> Result<&'a str, Error> {
+    let offset = |i| {
+        s.char_indices()
+            .map(|(offset, _)| offset)
+            .chain(iter::once(s.len()))
+            .nth(i)
+            .ok_or_else(|| format!("{name}: index {i} is out of range for {s}"))
+    };
+    if start > end {
+        Err(format!("{name}: start {start} is after end {end}"))?;
+    }
+    Ok(&s[offset(start)?..offset(end)?])
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+// written the way numbers are, in hexadecimal with a `%` before the fraction, the digits are found
+// by dividing (or multiplying) by 16, which is exact for floats
+fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        return "nan".to_string();
+    }
+    if n.is_infinite() {
+        return if n > 0. { "inf" } else { "-inf" }.to_string();
+    }
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let digit = |d: f64| char::from(HEX_DIGITS[d as usize]);
+    let mut s = String::new();
+    if n < 0. {
+        s.push('-');
+    }
+    s.push_str("0x");
+    let n = n.abs();
+    let mut whole = n.floor();
+    let mut fraction = n - whole;
+    let mut digits = vec![];
+    loop {
+        let rest = (whole / 16.).floor();
+        digits.push(digit(16f64.mul_add(-rest, whole)));
+        whole = rest;
+        if whole == 0. {
+            break;
+        }
+    }
+    s.extend(digits.into_iter().rev());
+    if fraction > 0. {
+        s.push('%');
+    }
+    while fraction > 0. {
+        fraction *= 16.;
+        let d = fraction.floor();
+        s.push(digit(d));
+        fraction -= d;
+    }
+    s
+}
+
+// the same numbers that can be written in code (with 0x for hexadecimal digits, and `%` before the
+// fraction) which can also be negative
+fn string_to_number(s: &str) -> Option<f64> {
+    let (negative, s) = s.strip_prefix('-').map_or((false, s), |s| (true, s));
+    let (hex, s) = s.strip_prefix("0x").map_or((false, s), |s| (true, s));
+    let (whole, fraction) = s.split_once('%').map_or((s, None), |(w, f)| (w, Some(f)));
+    if fraction.map_or(whole.is_empty(), str::is_empty) {
+        return None;
+    }
+    let digit = |c: char| {
+        c.to_digit(if hex { 16 } else { 10 })
+            .filter(|_| !c.is_ascii_uppercase())
+            .map(f64::from)
+    };
+    let mut n = 0.;
+    for c in whole.chars() {
+        n = n.mul_add(16., digit(c)?);
+    }
+    let mut scale = 1.;
+    for c in fraction.unwrap_or_default().chars() {
+        scale /= 16.;
+        n = digit(c)?.mul_add(scale, n);
+    }
+    Some(if negative { -n } else { n })
+}
+
+fn check_divisor(name: &str, divisor: f64) -> Result<(), Error> {
+    if divisor == 0. {
+        Err(format!("{name}: division by zero"))?;
+    }
+    Ok(())
+}
+
+// like remainder, but with the sign of the divisor
+fn modulo(a: f64, b: f64) -> f64 {
+    let remainder = a % b;
+    if remainder != 0. && (remainder < 0.) != (b < 0.) {
+        remainder + b
+    } else {
+        remainder
+    }
+}
+
 pub fn new_primitive_env(mut adder: impl FnMut(Rc<str>, Ast)) {
     // add code here
     adder(
@@ -179,6 +697,34 @@ pub fn new_primitive_env(mut adder: impl FnMut(Rc<str>, Ast)) {
             operation: Ast::primitive_datum_to_syntax,
         })),
     );
+    adder(
+        "syntax-property".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "syntax-property",
+            operation: Ast::primitive_syntax_property,
+        })),
+    );
+    adder(
+        "free-identifier=?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "free-identifier=?",
+            operation: Ast::primitive_free_identifier,
+        })),
+    );
+    adder(
+        "bound-identifier=?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "bound-identifier=?",
+            operation: Ast::primitive_bound_identifier,
+        })),
+    );
+    adder(
+        "generate-temporaries".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "generate-temporaries",
+            operation: Ast::primitive_generate_temporaries,
+        })),
+    );
     adder(
         "syntax-to-datum".into(),
         Ast::Function(Function::Primitive(Primitive {
@@ -235,6 +781,344 @@ pub fn new_primitive_env(mut adder: impl FnMut(Rc<str>, Ast)) {
             operation: Ast::primitive_null,
         })),
     );
+    adder(
+        "pair?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "pair?",
+            operation: Ast::primitive_pair,
+        })),
+    );
+    adder(
+        "equal?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "equal?",
+            operation: Ast::primitive_equal,
+        })),
+    );
+    adder(
+        "kleene-and".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "kleene-and",
+            operation: Ast::primitive_kleene_and,
+        })),
+    );
+    adder(
+        "kleene-or".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "kleene-or",
+            operation: Ast::primitive_kleene_or,
+        })),
+    );
+    adder(
+        "xor".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "xor",
+            operation: Ast::primitive_xor,
+        })),
+    );
+    adder(
+        "implies".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "implies",
+            operation: Ast::primitive_implies,
+        })),
+    );
+    adder(
+        "collapse".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "collapse",
+            operation: Ast::primitive_collapse,
+        })),
+    );
+    adder(
+        "maybe?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "maybe?",
+            operation: Ast::primitive_maybe,
+        })),
+    );
+    adder(
+        "definitely?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "definitely?",
+            operation: Ast::primitive_definitely,
+        })),
+    );
+    adder(
+        "+".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "+",
+            operation: |e| e.fold_numbers("+", 0., |a, b| Ok(a + b)),
+        })),
+    );
+    adder(
+        "-".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "-",
+            operation: |e| {
+                e.at_least_one_argument("-")?
+                    .fold_numbers("-", 0., |a, b| Ok(a - b))
+            },
+        })),
+    );
+    adder(
+        "*".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "*",
+            operation: |e| e.fold_numbers("*", 1., |a, b| Ok(a * b)),
+        })),
+    );
+    adder(
+        "/".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "/",
+            operation: |e| {
+                e.at_least_one_argument("/")?.fold_numbers("/", 1., |a, b| {
+                    check_divisor("/", b)?;
+                    Ok(a / b)
+                })
+            },
+        })),
+    );
+    adder(
+        "quotient".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "quotient",
+            operation: |e| {
+                e.binary_number("quotient", |a, b| {
+                    check_divisor("quotient", b)?;
+                    Ok((a / b).trunc())
+                })
+            },
+        })),
+    );
+    adder(
+        "remainder".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "remainder",
+            operation: |e| {
+                e.binary_number("remainder", |a, b| {
+                    check_divisor("remainder", b)?;
+                    Ok(a % b)
+                })
+            },
+        })),
+    );
+    adder(
+        "modulo".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "modulo",
+            operation: |e| {
+                e.binary_number("modulo", |a, b| {
+                    check_divisor("modulo", b)?;
+                    Ok(modulo(a, b))
+                })
+            },
+        })),
+    );
+    adder(
+        "<".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "<",
+            operation: |e| e.compare_numbers("<", f64::lt),
+        })),
+    );
+    adder(
+        "<=".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "<=",
+            operation: |e| e.compare_numbers("<=", f64::le),
+        })),
+    );
+    adder(
+        ">".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: ">",
+            operation: |e| e.compare_numbers(">", f64::gt),
+        })),
+    );
+    adder(
+        ">=".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: ">=",
+            operation: |e| e.compare_numbers(">=", f64::ge),
+        })),
+    );
+    adder(
+        "=".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "=",
+            operation: Ast::primitive_number_equal,
+        })),
+    );
+    adder(
+        "abs".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "abs",
+            operation: |e| e.unary_number("abs", f64::abs),
+        })),
+    );
+    adder(
+        "floor".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "floor",
+            operation: |e| e.unary_number("floor", f64::floor),
+        })),
+    );
+    adder(
+        "ceiling".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "ceiling",
+            operation: |e| e.unary_number("ceiling", f64::ceil),
+        })),
+    );
+    adder(
+        "round".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "round",
+            operation: |e| e.unary_number("round", f64::round_ties_even),
+        })),
+    );
+    adder(
+        "sqrt".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "sqrt",
+            operation: |e| e.unary_number("sqrt", f64::sqrt),
+        })),
+    );
+    adder(
+        "expt".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "expt",
+            operation: |e| e.binary_number("expt", |a, b| Ok(a.powf(b))),
+        })),
+    );
+    adder(
+        "string-length".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string-length",
+            operation: Ast::primitive_string_length,
+        })),
+    );
+    adder(
+        "string-append".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string-append",
+            operation: Ast::primitive_string_append,
+        })),
+    );
+    adder(
+        "substring".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "substring",
+            operation: Ast::primitive_substring,
+        })),
+    );
+    adder(
+        "string-ref".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string-ref",
+            operation: Ast::primitive_string_ref,
+        })),
+    );
+    adder(
+        "string=?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string=?",
+            operation: Ast::primitive_string_equal,
+        })),
+    );
+    adder(
+        "string<?".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string<?",
+            operation: Ast::primitive_string_less,
+        })),
+    );
+    adder(
+        "string->symbol".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string->symbol",
+            operation: Ast::primitive_string_to_symbol,
+        })),
+    );
+    adder(
+        "symbol->string".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "symbol->string",
+            operation: Ast::primitive_symbol_to_string,
+        })),
+    );
+    adder(
+        "number->string".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "number->string",
+            operation: Ast::primitive_number_to_string,
+        })),
+    );
+    adder(
+        "string->number".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string->number",
+            operation: Ast::primitive_string_to_number,
+        })),
+    );
+    adder(
+        "string-split".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string-split",
+            operation: Ast::primitive_string_split,
+        })),
+    );
+    adder(
+        "string-join".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "string-join",
+            operation: Ast::primitive_string_join,
+        })),
+    );
+    adder(
+        "make-hash".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "make-hash",
+            operation: Ast::primitive_make_hash,
+        })),
+    );
+    adder(
+        "hash-ref".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "hash-ref",
+            operation: Ast::primitive_hash_ref,
+        })),
+    );
+    adder(
+        "hash-set!".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "hash-set!",
+            operation: Ast::primitive_hash_set,
+        })),
+    );
+    adder(
+        "hash-remove!".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "hash-remove!",
+            operation: Ast::primitive_hash_remove,
+        })),
+    );
+    adder(
+        "hash-keys".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "hash-keys",
+            operation: Ast::primitive_hash_keys,
+        })),
+    );
+    adder(
+        "hash-count".into(),
+        Ast::Function(Function::Primitive(Primitive {
+            name: "hash-count",
+            operation: Ast::primitive_hash_count,
+        })),
+    );
     adder(
         "values".into(),
         Ast::Function(Function::Primitive(Primitive {